@@ -0,0 +1,220 @@
+use crate::conditions::{split_conditional, TimeCondition};
+use chrono::NaiveDateTime;
+use osmpbfreader::objects::Tags;
+use serde::{Deserialize, Serialize};
+
+/// Access keys applying to motorcycles, from the most specific to the most general
+const ACCESS_KEYS: [&str; 4] = ["motorcycle", "motor_vehicle", "vehicle", "access"];
+
+/// Access of motorcycles to a way
+/// # Variants
+/// * `Allowed` - The way can be used by any motorcycle
+/// * `Destination` - The way can only be used to reach or leave a place along it
+/// * `Forbidden` - The way can't be used by motorcycles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum Access {
+    #[default]
+    Allowed,
+    Destination,
+    Forbidden,
+}
+
+/// Access implementation
+impl Access {
+    /// Parse the value of an access tag
+    /// # Arguments
+    /// * `value` - The value of the tag
+    /// # Returns
+    /// * `Option<Access>` - The access, None if the value is unknown
+    pub fn parse(value: &str) -> Option<Access> {
+        match value.trim() {
+            "yes" | "permissive" | "designated" | "official" | "discouraged" => {
+                Some(Access::Allowed)
+            }
+            "destination" | "delivery" | "customers" => Some(Access::Destination),
+            "no" | "private" | "permit" | "agricultural" | "forestry" | "use_sidepath" => {
+                Some(Access::Forbidden)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Access of motorcycles to an edge during the period of a condition
+/// # Fields
+/// * `access` - The access while the condition is met
+/// * `condition` - The period of the condition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConditionalAccess {
+    pub access: Access,
+    pub condition: TimeCondition,
+}
+
+/// Access of motorcycles to an edge, with its conditional restrictions
+/// # Fields
+/// * `access` - The access when no condition is met
+/// * `conditions` - The conditional accesses, the first one whose condition is met applies
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EdgeAccess {
+    pub access: Access,
+    pub conditions: Vec<ConditionalAccess>,
+}
+
+/// EdgeAccess implementation
+impl EdgeAccess {
+    /// Get the access of motorcycles to a way
+    /// The most specific key among `motorcycle`, `motor_vehicle`, `vehicle` and `access` gives the
+    /// access. The `:conditional` keys of the same or more specific keys override it while their
+    /// conditions are met, the last condition of a key taking precedence. The conditions that
+    /// can't be parsed are ignored
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `EdgeAccess` - The access of motorcycles to the way, in both directions
+    pub fn of_way(tags: &Tags) -> EdgeAccess {
+        let tag = |key: &str| tags.get(key).map(|value| value.as_str());
+
+        let (level, access) = ACCESS_KEYS
+            .iter()
+            .enumerate()
+            .find_map(|(level, key)| Some((level, tag(key).and_then(Access::parse)?)))
+            .unwrap_or((ACCESS_KEYS.len() - 1, Access::Allowed));
+
+        let mut conditions = Vec::new();
+
+        for key in &ACCESS_KEYS[..=level] {
+            let conditional = tag(&format!("{}:conditional", key)).unwrap_or("");
+
+            for (value, condition) in split_conditional(conditional).into_iter().rev() {
+                if let (Some(access), Some(condition)) =
+                    (Access::parse(value), TimeCondition::parse(condition))
+                {
+                    conditions.push(ConditionalAccess { access, condition });
+                }
+            }
+        }
+
+        EdgeAccess { access, conditions }
+    }
+
+    /// Get the access in one direction of a way, combined with its one-way restrictions
+    /// A direction closed by the one-way restrictions is forbidden. A direction only open while
+    /// a one-way condition is met gets the access of the way during this condition
+    /// # Arguments
+    /// * `allowed` - True if the direction is open when no one-way condition is met
+    /// * `direction_conditions` - For each one-way condition, true if the direction is open while it is met
+    /// # Returns
+    /// * `EdgeAccess` - The access in the direction
+    pub fn in_direction(
+        &self,
+        allowed: bool,
+        direction_conditions: &[(bool, TimeCondition)],
+    ) -> EdgeAccess {
+        if allowed {
+            let mut conditions: Vec<ConditionalAccess> = direction_conditions
+                .iter()
+                .filter(|(open, _)| !open)
+                .map(|(_, condition)| ConditionalAccess {
+                    access: Access::Forbidden,
+                    condition: condition.clone(),
+                })
+                .collect();
+
+            conditions.extend(self.conditions.iter().cloned());
+
+            EdgeAccess {
+                access: self.access,
+                conditions,
+            }
+        } else {
+            EdgeAccess {
+                access: Access::Forbidden,
+                conditions: direction_conditions
+                    .iter()
+                    .filter(|(open, _)| *open)
+                    .map(|(_, condition)| ConditionalAccess {
+                        access: self.access,
+                        condition: condition.clone(),
+                    })
+                    .collect(),
+            }
+        }
+    }
+
+    /// Check if the edge can never be used by motorcycles
+    /// # Returns
+    /// * `bool` - True if the edge is forbidden whether the conditions are met or not
+    pub fn is_never_allowed(&self) -> bool {
+        self.access == Access::Forbidden
+            && self
+                .conditions
+                .iter()
+                .all(|condition| condition.access == Access::Forbidden)
+    }
+
+    /// Get the access at a date and time
+    /// Without date, the most restrictive access is used, so that a route is valid at any time
+    /// # Arguments
+    /// * `date_time` - The local date and time, None if it is unknown
+    /// # Returns
+    /// * `Access` - The access of the first conditional access whose condition is met, or the
+    ///   access when no condition is met
+    pub fn at(&self, date_time: Option<&NaiveDateTime>) -> Access {
+        match date_time {
+            Some(date_time) => self
+                .conditions
+                .iter()
+                .find(|condition| condition.condition.matches(date_time))
+                .map_or(self.access, |condition| condition.access),
+            None => self
+                .conditions
+                .iter()
+                .map(|condition| condition.access)
+                .fold(self.access, Access::max),
+        }
+    }
+}
+
+/// Part of a route, destination-only edges can only be used at the start or at the end of a route
+/// # Variants
+/// * `Start` - The route has only used destination-only edges since the start
+/// * `Through` - The route has used an allowed edge
+/// * `End` - The route has used a destination-only edge after an allowed edge, it can't use
+///   allowed edges anymore
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum AccessPhase {
+    #[default]
+    Start,
+    Through,
+    End,
+}
+
+/// AccessPhase implementation
+impl AccessPhase {
+    /// Get the phase of a route after an edge
+    /// # Arguments
+    /// * `access` - The access of the edge
+    /// # Returns
+    /// * `Option<AccessPhase>` - The phase after the edge, None if the edge can't be used
+    pub fn next(self, access: Access) -> Option<AccessPhase> {
+        match (self, access) {
+            (_, Access::Forbidden) => None,
+            (AccessPhase::End, Access::Allowed) => None,
+            (_, Access::Allowed) => Some(AccessPhase::Through),
+            (AccessPhase::Start, Access::Destination) => Some(AccessPhase::Start),
+            (_, Access::Destination) => Some(AccessPhase::End),
+        }
+    }
+
+    /// Check if a path only uses destination-only edges at its start and at its end
+    /// # Arguments
+    /// * `accesses` - The access of each edge of the path
+    /// # Returns
+    /// * `bool` - True if every edge of the path can be used
+    pub fn allows_path(accesses: impl IntoIterator<Item = Access>) -> bool {
+        accesses
+            .into_iter()
+            .try_fold(AccessPhase::Start, AccessPhase::next)
+            .is_some()
+    }
+}
@@ -0,0 +1,103 @@
+use osmpbfreader::objects::Tags;
+use serde::Deserialize;
+use std::str::FromStr;
+
+/// Factor applied to the cost of the avoided edges, high enough to only use them when there is
+/// no other way, for example when the start point is in a tunnel
+pub const AVOID_PENALTY: f64 = 1000.0;
+
+/// Highway classes of the Swiss national roads, which can only be used with a vignette
+/// The graph is built from the Swiss extract, so these ways are considered as toll roads
+const VIGNETTE_HIGHWAYS: [&str; 2] = ["motorway", "motorway_link"];
+
+/// Surfaces considered as unpaved
+const UNPAVED_SURFACES: [&str; 13] = [
+    "unpaved",
+    "compacted",
+    "gravel",
+    "fine_gravel",
+    "pebblestone",
+    "rock",
+    "ground",
+    "dirt",
+    "earth",
+    "grass",
+    "mud",
+    "sand",
+    "woodchips",
+];
+
+/// Road feature that a route can avoid
+/// # Variants
+/// * `Motorway` - Motorways and their links
+/// * `Toll` - Toll roads, including the Swiss motorways that need a vignette
+/// * `Ferry` - Ferry routes
+/// * `Unpaved` - Roads without asphalt or paving, and tracks of grade 2 to 5
+/// * `Tunnel` - Tunnels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AvoidFeature {
+    Motorway,
+    Toll,
+    Ferry,
+    Unpaved,
+    Tunnel,
+}
+
+/// AvoidFeature implementation
+impl AvoidFeature {
+    /// Check if a way has the feature
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `bool` - True if the way has the feature
+    pub fn matches(&self, tags: &Tags) -> bool {
+        let tag = |key: &str| tags.get(key).map(|value| value.as_str());
+        let highway = tag("highway").unwrap_or("");
+
+        match self {
+            AvoidFeature::Motorway => highway == "motorway" || highway == "motorway_link",
+            AvoidFeature::Toll => {
+                matches!(tag("toll"), Some("yes"))
+                    || matches!(tag("toll:motorcycle"), Some("yes"))
+                    || VIGNETTE_HIGHWAYS.contains(&highway)
+            }
+            AvoidFeature::Ferry => tag("route") == Some("ferry") || tag("ferry").is_some(),
+            AvoidFeature::Unpaved => match tag("surface") {
+                Some(surface) => UNPAVED_SURFACES.contains(&surface),
+                None => highway == "track" && !matches!(tag("tracktype"), Some("grade1")),
+            },
+            AvoidFeature::Tunnel => matches!(tag("tunnel"), Some(tunnel) if tunnel != "no"),
+        }
+    }
+
+    /// Parse a comma separated list of features, such as `motorway,toll,tunnel`
+    /// # Arguments
+    /// * `value` - The list of features
+    /// # Returns
+    /// * `Result<Vec<AvoidFeature>, String>` - The features, or an error message with the unknown feature
+    pub fn parse_list(value: &str) -> Result<Vec<AvoidFeature>, String> {
+        value
+            .split(',')
+            .map(str::trim)
+            .filter(|feature| !feature.is_empty())
+            .map(AvoidFeature::from_str)
+            .collect()
+    }
+}
+
+/// Parse a feature from its name
+impl FromStr for AvoidFeature {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "motorway" => Ok(AvoidFeature::Motorway),
+            "toll" => Ok(AvoidFeature::Toll),
+            "ferry" => Ok(AvoidFeature::Ferry),
+            "unpaved" => Ok(AvoidFeature::Unpaved),
+            "tunnel" => Ok(AvoidFeature::Tunnel),
+            _ => Err(format!("Unknown avoid feature: {}", value)),
+        }
+    }
+}
@@ -0,0 +1,372 @@
+use serde_json::Value;
+
+/// Polygon that a route avoids, such as a city center, a construction zone or a closed pass
+/// The rings are tested with the even-odd rule, so the inner rings are holes in the polygon
+/// # Fields
+/// * `rings` - The rings of the polygon as (latitude, longitude), the first one is the outer ring
+#[derive(Debug, Clone, PartialEq)]
+pub struct AvoidZone {
+    pub rings: Vec<Vec<(f64, f64)>>,
+}
+
+/// AvoidZone implementation
+impl AvoidZone {
+    /// Read the polygons of a GeoJSON object
+    /// Polygons and multi polygons are accepted, alone or in features, feature collections and
+    /// geometry collections
+    /// # Arguments
+    /// * `geojson` - The GeoJSON object
+    /// # Returns
+    /// * `Result<Vec<AvoidZone>, String>` - The polygons, or an error message if the object is invalid
+    pub fn from_geojson(geojson: &Value) -> Result<Vec<AvoidZone>, String> {
+        let mut zones = Vec::new();
+
+        match geojson["type"].as_str() {
+            Some("FeatureCollection") => {
+                for feature in geojson["features"].as_array().ok_or("Invalid features")? {
+                    zones.extend(AvoidZone::from_geojson(feature)?);
+                }
+            }
+            Some("Feature") => zones.extend(AvoidZone::from_geojson(&geojson["geometry"])?),
+            Some("GeometryCollection") => {
+                for geometry in geojson["geometries"]
+                    .as_array()
+                    .ok_or("Invalid geometries")?
+                {
+                    zones.extend(AvoidZone::from_geojson(geometry)?);
+                }
+            }
+            Some("Polygon") => zones.push(AvoidZone::from_coordinates(&geojson["coordinates"])?),
+            Some("MultiPolygon") => {
+                for polygon in geojson["coordinates"]
+                    .as_array()
+                    .ok_or("Invalid coordinates")?
+                {
+                    zones.push(AvoidZone::from_coordinates(polygon)?);
+                }
+            }
+            Some(geometry_type) => {
+                return Err(format!("Unsupported geometry type: {}", geometry_type))
+            }
+            None => return Err("Missing GeoJSON type".to_string()),
+        }
+
+        Ok(zones)
+    }
+
+    /// Read a polygon from its GeoJSON coordinates, rings of [longitude, latitude] positions
+    /// # Arguments
+    /// * `coordinates` - The coordinates of the polygon
+    /// # Returns
+    /// * `Result<AvoidZone, String>` - The polygon, or an error message if the coordinates are invalid
+    fn from_coordinates(coordinates: &Value) -> Result<AvoidZone, String> {
+        let mut rings = Vec::new();
+
+        for ring in coordinates.as_array().ok_or("Invalid coordinates")? {
+            let mut points = Vec::new();
+
+            for position in ring.as_array().ok_or("Invalid ring")? {
+                let lon = position[0].as_f64().ok_or("Invalid position")?;
+                let lat = position[1].as_f64().ok_or("Invalid position")?;
+
+                points.push((lat, lon));
+            }
+
+            if points.len() < 3 {
+                return Err("A ring needs at least 3 positions".to_string());
+            }
+
+            rings.push(points);
+        }
+
+        if rings.is_empty() {
+            return Err("A polygon needs at least one ring".to_string());
+        }
+
+        Ok(AvoidZone { rings })
+    }
+
+    /// Get the bounding box of the polygon
+    /// # Returns
+    /// * `(f64, f64, f64, f64)` - The minimum latitude, minimum longitude, maximum latitude and maximum longitude
+    pub fn bbox(&self) -> (f64, f64, f64, f64) {
+        self.rings[0].iter().fold(
+            (
+                f64::INFINITY,
+                f64::INFINITY,
+                f64::NEG_INFINITY,
+                f64::NEG_INFINITY,
+            ),
+            |(min_lat, min_lon, max_lat, max_lon), (lat, lon)| {
+                (
+                    min_lat.min(*lat),
+                    min_lon.min(*lon),
+                    max_lat.max(*lat),
+                    max_lon.max(*lon),
+                )
+            },
+        )
+    }
+
+    /// Check if a point is inside the polygon, by casting a ray towards the east
+    /// # Arguments
+    /// * `lat` - The latitude of the point
+    /// * `lon` - The longitude of the point
+    /// # Returns
+    /// * `bool` - True if the point is inside the polygon and outside its holes
+    pub fn contains(&self, lat: f64, lon: f64) -> bool {
+        let mut inside = false;
+
+        for ring in &self.rings {
+            for index in 0..ring.len() {
+                let (lat_1, lon_1) = ring[index];
+                let (lat_2, lon_2) = ring[(index + 1) % ring.len()];
+
+                if (lat_1 > lat) != (lat_2 > lat)
+                    && lon < lon_1 + (lat - lat_1) / (lat_2 - lat_1) * (lon_2 - lon_1)
+                {
+                    inside = !inside;
+                }
+            }
+        }
+
+        inside
+    }
+
+    /// Check if a segment crosses or is inside the polygon
+    /// # Arguments
+    /// * `from` - The first point of the segment as (latitude, longitude)
+    /// * `to` - The second point of the segment as (latitude, longitude)
+    /// # Returns
+    /// * `bool` - True if a point of the segment is inside the polygon
+    pub fn intersects_segment(&self, from: (f64, f64), to: (f64, f64)) -> bool {
+        if self.contains(from.0, from.1) || self.contains(to.0, to.1) {
+            return true;
+        }
+
+        self.rings.iter().any(|ring| {
+            (0..ring.len()).any(|index| {
+                segments_intersect(from, to, ring[index], ring[(index + 1) % ring.len()])
+            })
+        })
+    }
+}
+
+/// Check if two segments intersect, including when they only touch
+/// # Arguments
+/// * `a` - The first point of the first segment
+/// * `b` - The second point of the first segment
+/// * `c` - The first point of the second segment
+/// * `d` - The second point of the second segment
+/// # Returns
+/// * `bool` - True if the segments have a common point
+fn segments_intersect(a: (f64, f64), b: (f64, f64), c: (f64, f64), d: (f64, f64)) -> bool {
+    // sign of the turn from p -> q to p -> r
+    let orientation = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        let cross = (q.0 - p.0) * (r.1 - p.1) - (q.1 - p.1) * (r.0 - p.0);
+
+        if cross > 0.0 {
+            1
+        } else if cross < 0.0 {
+            -1
+        } else {
+            0
+        }
+    };
+
+    // r is on the segment p -> q, knowing that the three points are aligned
+    let on_segment = |p: (f64, f64), q: (f64, f64), r: (f64, f64)| {
+        r.0 >= p.0.min(q.0) && r.0 <= p.0.max(q.0) && r.1 >= p.1.min(q.1) && r.1 <= p.1.max(q.1)
+    };
+
+    let o_1 = orientation(a, b, c);
+    let o_2 = orientation(a, b, d);
+    let o_3 = orientation(c, d, a);
+    let o_4 = orientation(c, d, b);
+
+    if o_1 != o_2 && o_3 != o_4 {
+        return true;
+    }
+
+    (o_1 == 0 && on_segment(a, b, c))
+        || (o_2 == 0 && on_segment(a, b, d))
+        || (o_3 == 0 && on_segment(c, d, a))
+        || (o_4 == 0 && on_segment(c, d, b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::osm_graph::{Edge, OSMGraph, SnappedPoint};
+    use crate::route_calculation::{dijkstra, RouteOptions};
+    use crate::test_graph::{jittered_grid, path_ways};
+    use osmpbfreader::objects::{NodeId, WayId};
+    use serde_json::json;
+
+    /// Build a square zone in GeoJSON
+    /// # Arguments
+    /// * `lat` - The latitude of the center of the square
+    /// * `lon` - The longitude of the center of the square
+    /// * `half_size` - Half of the side of the square in degrees
+    /// # Returns
+    /// * `Value` - The zone as a GeoJSON polygon
+    fn square(lat: f64, lon: f64, half_size: f64) -> Value {
+        json!({
+            "type": "Polygon",
+            "coordinates": [[
+                [lon - half_size, lat - half_size],
+                [lon + half_size, lat - half_size],
+                [lon + half_size, lat + half_size],
+                [lon - half_size, lat + half_size],
+                [lon - half_size, lat - half_size],
+            ]],
+        })
+    }
+
+    #[test]
+    fn geojson_objects() {
+        let polygon = square(47.0, 7.0, 0.01);
+
+        let zones = AvoidZone::from_geojson(&polygon).unwrap();
+        assert_eq!(zones.len(), 1);
+        assert_eq!(zones[0].rings[0][0], (46.99, 6.99));
+        assert_eq!(zones[0].bbox(), (46.99, 6.99, 47.01, 7.01));
+
+        let feature = json!({"type": "Feature", "properties": {}, "geometry": polygon});
+        let collection = json!({"type": "FeatureCollection", "features": [feature, feature]});
+        assert_eq!(AvoidZone::from_geojson(&collection).unwrap().len(), 2);
+
+        let multi_polygon = json!({
+            "type": "MultiPolygon",
+            "coordinates": [polygon["coordinates"], square(46.0, 8.0, 0.1)["coordinates"]],
+        });
+        let geometries = json!({"type": "GeometryCollection", "geometries": [multi_polygon]});
+        assert_eq!(AvoidZone::from_geojson(&geometries).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn malformed_geojson_is_rejected() {
+        for geojson in [
+            json!({}),
+            json!("Polygon"),
+            json!({"type": "Point", "coordinates": [7.0, 47.0]}),
+            json!({"type": "Polygon"}),
+            json!({"type": "Polygon", "coordinates": []}),
+            json!({"type": "Polygon", "coordinates": [[[7.0, 47.0], [7.1, 47.0]]]}),
+            json!({"type": "Polygon", "coordinates": [[[7.0, 47.0], [7.1], [7.1, 47.1]]]}),
+            json!({"type": "Polygon", "coordinates": [[[7.0, 47.0], ["7.1", 47.0], [7.1, 47.1]]]}),
+            json!({"type": "MultiPolygon", "coordinates": {}}),
+            json!({"type": "FeatureCollection", "features": [{"type": "Feature"}]}),
+        ] {
+            assert!(AvoidZone::from_geojson(&geojson).is_err(), "{}", geojson);
+        }
+    }
+
+    #[test]
+    fn holes_are_outside() {
+        let mut zone = AvoidZone::from_geojson(&square(47.0, 7.0, 0.01)).unwrap()[0].clone();
+        zone.rings
+            .push(AvoidZone::from_geojson(&square(47.0, 7.0, 0.005)).unwrap()[0].rings[0].clone());
+
+        assert!(zone.contains(47.0, 7.008));
+        assert!(!zone.contains(47.0, 7.0));
+        assert!(!zone.contains(47.0, 7.02));
+
+        // crossing the zone without any end inside it
+        assert!(zone.intersects_segment((46.98, 7.008), (47.02, 7.008)));
+        assert!(!zone.intersects_segment((46.98, 7.02), (47.02, 7.02)));
+        // inside the hole
+        assert!(!zone.intersects_segment((47.0, 6.998), (47.0, 7.002)));
+    }
+
+    /// Find the shortest route across a 4 x 4 grid, and a zone around the middle of one of its
+    /// edges, away from the other roads
+    /// # Returns
+    /// * `(OSMGraph, SnappedPoint, SnappedPoint, WayId, Value)` - The grid, the start and end
+    ///   points of the route, the way of the edge in the zone and the zone as a GeoJSON polygon
+    fn route_and_zone() -> (OSMGraph, SnappedPoint, SnappedPoint, WayId, Value) {
+        let graph = jittered_grid(4);
+        let start = graph.snap_to_edge(47.0, 7.005).unwrap();
+        let end = graph.snap_to_edge(47.035, 7.031).unwrap();
+
+        let (_, edges) = dijkstra(&graph, &start, &end, &RouteOptions::default());
+        let edges = edges.unwrap();
+
+        // an edge of the graph in the middle of the route, not a part of the snapped edges
+        let edge = &edges[edges.len() / 2];
+        let from = graph.get_node(edge.from).unwrap();
+        let to = graph.get_node(edge.to).unwrap();
+        let zone = square(
+            (from.lat() + to.lat()) / 2.0,
+            (from.lon() + to.lon()) / 2.0,
+            0.001,
+        );
+
+        (graph, start, end, edge.way_id, zone)
+    }
+
+    #[test]
+    fn both_directions_are_penalized() {
+        let (graph, _, _, way_id, zone) = route_and_zone();
+        let zones = AvoidZone::from_geojson(&zone).unwrap();
+
+        let mut options = RouteOptions::default();
+        options.avoid_zones(&graph, &zones, Some(5.0));
+
+        let mut keys: Vec<(NodeId, NodeId, WayId)> = options.penalties.keys().copied().collect();
+        keys.sort();
+
+        assert_eq!(keys.len(), 2);
+        assert_eq!((keys[0].0, keys[0].1), (keys[1].1, keys[1].0));
+        assert!(keys.iter().all(|key| key.2 == way_id));
+        assert!(options.penalties.values().all(|penalty| *penalty == 5.0));
+
+        for edge in graph.get_edges() {
+            let expected = if edge.way_id == way_id { 5.0 } else { 1.0 };
+            assert_eq!(
+                options.edge_cost(&graph, edge),
+                edge.weight * expected,
+                "{:?}",
+                edge.key()
+            );
+        }
+    }
+
+    #[test]
+    fn route_avoids_the_zone() {
+        let (graph, start, end, way_id, zone) = route_and_zone();
+        let zones = AvoidZone::from_geojson(&zone).unwrap();
+
+        let (_, edges) = dijkstra(&graph, &start, &end, &RouteOptions::default());
+        let shortest = edges.unwrap();
+        assert!(path_ways(&shortest).contains(&way_id.0));
+
+        for penalty in [None, Some(100.0)] {
+            let mut options = RouteOptions::default();
+            options.avoid_zones(&graph, &zones, penalty);
+
+            let (_, edges) = dijkstra(&graph, &start, &end, &options);
+            let detour = edges.unwrap();
+
+            assert!(!path_ways(&detour).contains(&way_id.0), "{:?}", penalty);
+            assert!(detour.iter().all(|edge| {
+                edge.nodes_ids.windows(2).all(|segment| {
+                    let from = graph.get_node(segment[0]).unwrap();
+                    let to = graph.get_node(segment[1]).unwrap();
+
+                    !zones[0].intersects_segment((from.lat(), from.lon()), (to.lat(), to.lon()))
+                })
+            }));
+
+            let length = |edges: &[Edge]| edges.iter().map(|edge| edge.distance_m).sum::<f64>();
+            assert!(length(&detour) > length(&shortest));
+        }
+
+        // a small penalty keeps the shortest route
+        let mut options = RouteOptions::default();
+        options.avoid_zones(&graph, &zones, Some(1.01));
+
+        let (_, edges) = dijkstra(&graph, &start, &end, &options);
+        assert_eq!(path_ways(&edges.unwrap()), path_ways(&shortest));
+    }
+}
@@ -0,0 +1,434 @@
+use chrono::{Datelike, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+
+/// Abbreviations of the months in the opening hours syntax
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// Abbreviations of the weekdays in the opening hours syntax, from Monday
+const WEEKDAYS: [&str; 7] = ["Mo", "Tu", "We", "Th", "Fr", "Sa", "Su"];
+
+/// Approximate time of the sunrise in minutes after midnight, used for `sunrise`
+const SUNRISE_MINUTES: u32 = 6 * 60;
+
+/// Approximate time of the sunset in minutes after midnight, used for `sunset`
+const SUNSET_MINUTES: u32 = 20 * 60;
+
+/// Split the value of a conditional tag into its values and conditions
+/// The conditions are separated by semicolons outside of the parentheses, for example
+/// `yes @ (Mo-Fr 07:00-09:00; Sa 10:00-12:00); -1 @ (16:00-18:00)`
+/// # Arguments
+/// * `value` - The value of the conditional tag
+/// # Returns
+/// * `Vec<(&str, &str)>` - The value and the condition of each part, without the parentheses
+pub fn split_conditional(value: &str) -> Vec<(&str, &str)> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (index, character) in value.char_indices() {
+        match character {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth == 0 => {
+                parts.push(&value[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    parts.push(&value[start..]);
+
+    parts
+        .into_iter()
+        .filter_map(|part| part.split_once('@'))
+        .map(|(value, condition)| {
+            let condition = condition.trim();
+            let condition = condition
+                .strip_prefix('(')
+                .and_then(|condition| condition.strip_suffix(')'))
+                .unwrap_or(condition);
+
+            (value.trim(), condition.trim())
+        })
+        .collect()
+}
+
+/// Period of time of a conditional restriction, in the opening hours syntax
+/// The condition is met when one of its rules is met
+/// # Fields
+/// * `rules` - The rules of the condition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeCondition {
+    pub rules: Vec<TimeRule>,
+}
+
+/// Rule of a time condition, such as `Nov-May`, `Sa,Su` or `Mo-Fr 07:00-09:00,16:00-18:00`
+/// A rule is met when its date, its weekday and its time are in one of their ranges, an empty
+/// list of ranges accepts any value
+/// # Fields
+/// * `dates` - The ranges of dates, as month * 100 + day
+/// * `weekdays` - The ranges of weekdays, from 0 for Monday to 6 for Sunday
+/// * `times` - The ranges of times, in minutes after midnight
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeRule {
+    pub dates: Vec<(u32, u32)>,
+    pub weekdays: Vec<(u32, u32)>,
+    pub times: Vec<(u32, u32)>,
+}
+
+/// Token of a time condition
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token<'a> {
+    Word(&'a str),
+    Number(u32),
+    Time(u32),
+    Dash,
+    Comma,
+}
+
+/// TimeCondition implementation
+impl TimeCondition {
+    /// Parse a condition in the opening hours syntax
+    /// Months, month days, weekdays, times, `sunrise`, `sunset` and `24/7` are supported. The
+    /// conditions on something else than the time, such as `weight>7.5` or public holidays, can't
+    /// be evaluated and are rejected
+    /// # Arguments
+    /// * `value` - The condition, without parentheses
+    /// # Returns
+    /// * `Option<TimeCondition>` - The condition, None if it can't be parsed
+    pub fn parse(value: &str) -> Option<TimeCondition> {
+        let rules = value
+            .split(';')
+            .map(str::trim)
+            .filter(|rule| !rule.is_empty())
+            .map(TimeRule::parse)
+            .collect::<Option<Vec<TimeRule>>>()?;
+
+        if rules.is_empty() {
+            return None;
+        }
+
+        Some(TimeCondition { rules })
+    }
+
+    /// Check if the condition is met at a date and time
+    /// # Arguments
+    /// * `date_time` - The local date and time
+    /// # Returns
+    /// * `bool` - True if one of the rules is met
+    pub fn matches(&self, date_time: &NaiveDateTime) -> bool {
+        self.rules.iter().any(|rule| rule.matches(date_time))
+    }
+}
+
+/// TimeRule implementation
+impl TimeRule {
+    /// Parse a rule of a condition
+    /// # Arguments
+    /// * `value` - The rule
+    /// # Returns
+    /// * `Option<TimeRule>` - The rule, None if it can't be parsed
+    fn parse(value: &str) -> Option<TimeRule> {
+        let mut rule = TimeRule::default();
+
+        if value == "24/7" {
+            return Some(rule);
+        }
+
+        let tokens = tokenize(value)?;
+        let mut index = 0;
+
+        while index < tokens.len() {
+            match tokens[index] {
+                Token::Word(word) if month(word).is_some() => {
+                    let (range, next) = parse_date_range(&tokens, index)?;
+                    rule.dates.push(range);
+                    index = next;
+                }
+                Token::Word(word) if weekday(word).is_some() => {
+                    let start = weekday(word)?;
+
+                    match tokens.get(index + 1) {
+                        Some(Token::Dash) => {
+                            let end = match tokens.get(index + 2)? {
+                                Token::Word(word) => weekday(word)?,
+                                _ => return None,
+                            };
+
+                            rule.weekdays.push((start, end));
+                            index += 3;
+                        }
+                        _ => {
+                            rule.weekdays.push((start, start));
+                            index += 1;
+                        }
+                    }
+                }
+                Token::Time(_) | Token::Word("sunrise") | Token::Word("sunset") => {
+                    let start = time(tokens[index])?;
+
+                    if tokens.get(index + 1) != Some(&Token::Dash) {
+                        return None;
+                    }
+
+                    let end = time(*tokens.get(index + 2)?)?;
+
+                    rule.times.push((start, end));
+                    index += 3;
+                }
+                Token::Comma => index += 1,
+                _ => return None,
+            }
+        }
+
+        Some(rule)
+    }
+
+    /// Check if the rule is met at a date and time
+    /// # Arguments
+    /// * `date_time` - The local date and time
+    /// # Returns
+    /// * `bool` - True if the date, the weekday and the time are in the ranges of the rule
+    fn matches(&self, date_time: &NaiveDateTime) -> bool {
+        let date = date_time.month() * 100 + date_time.day();
+        let weekday = date_time.weekday().num_days_from_monday();
+        let time = date_time.hour() * 60 + date_time.minute();
+
+        // the ranges whose end is before their start wrap around, such as Nov-May or 22:00-06:00
+        let in_ranges = |ranges: &[(u32, u32)], value: u32, inclusive: bool| {
+            ranges.is_empty()
+                || ranges.iter().any(|(start, end)| {
+                    let before_end = if inclusive {
+                        value <= *end
+                    } else {
+                        value < *end
+                    };
+
+                    if start <= end {
+                        value >= *start && before_end
+                    } else {
+                        value >= *start || before_end
+                    }
+                })
+        };
+
+        in_ranges(&self.dates, date, true)
+            && in_ranges(&self.weekdays, weekday, true)
+            && in_ranges(&self.times, time, false)
+    }
+}
+
+/// Parse a range of dates, such as `Nov-May`, `Dec 24-Jan 06`, `Jul 14` or `Aug 01-15`
+/// # Arguments
+/// * `tokens` - The tokens of the rule
+/// * `index` - The index of the month starting the range
+/// # Returns
+/// * `Option<((u32, u32), usize)>` - The range as month * 100 + day, and the index of the next token
+fn parse_date_range(tokens: &[Token], index: usize) -> Option<((u32, u32), usize)> {
+    let start_month = match tokens[index] {
+        Token::Word(word) => month(word)?,
+        _ => return None,
+    };
+    let mut index = index + 1;
+
+    let start_day = match tokens.get(index) {
+        Some(Token::Number(day)) => {
+            index += 1;
+            Some(month_day(*day)?)
+        }
+        _ => None,
+    };
+
+    if tokens.get(index) != Some(&Token::Dash) {
+        return Some(match start_day {
+            Some(day) => ((start_month * 100 + day, start_month * 100 + day), index),
+            None => ((start_month * 100 + 1, start_month * 100 + 31), index),
+        });
+    }
+
+    index += 1;
+
+    let (end_month, end_day) = match (tokens.get(index)?, tokens.get(index + 1)) {
+        (Token::Word(word), Some(Token::Number(day))) => {
+            index += 2;
+            (month(word)?, month_day(*day)?)
+        }
+        (Token::Word(word), _) => {
+            index += 1;
+            (month(word)?, 31)
+        }
+        (Token::Number(day), _) => {
+            index += 1;
+            (start_month, month_day(*day)?)
+        }
+        _ => return None,
+    };
+
+    Some((
+        (
+            start_month * 100 + start_day.unwrap_or(1),
+            end_month * 100 + end_day,
+        ),
+        index,
+    ))
+}
+
+/// Split a rule into tokens
+/// # Arguments
+/// * `value` - The rule
+/// # Returns
+/// * `Option<Vec<Token>>` - The tokens, None if the rule contains an unknown character
+fn tokenize(value: &str) -> Option<Vec<Token<'_>>> {
+    let mut tokens = Vec::new();
+    let mut characters = value.char_indices().peekable();
+
+    while let Some((start, character)) = characters.next() {
+        let mut end = start + character.len_utf8();
+
+        match character {
+            ' ' => {}
+            '-' => tokens.push(Token::Dash),
+            ',' => tokens.push(Token::Comma),
+            c if c.is_ascii_alphabetic() => {
+                while let Some((index, _)) = characters.next_if(|(_, c)| c.is_ascii_alphabetic()) {
+                    end = index + 1;
+                }
+
+                tokens.push(Token::Word(&value[start..end]));
+            }
+            c if c.is_ascii_digit() => {
+                while let Some((index, _)) =
+                    characters.next_if(|(_, c)| c.is_ascii_digit() || *c == ':')
+                {
+                    end = index + 1;
+                }
+
+                let number = &value[start..end];
+
+                tokens.push(match number.split_once(':') {
+                    Some((hours, minutes)) => Token::Time(clock_time(hours, minutes)?),
+                    None => Token::Number(number.parse().ok()?),
+                });
+            }
+            _ => return None,
+        }
+    }
+
+    Some(tokens)
+}
+
+/// Get the time of a clock value, such as `07:30` or `24:00`
+/// # Arguments
+/// * `hours` - The hours, up to 24
+/// * `minutes` - The minutes, up to 59
+/// # Returns
+/// * `Option<u32>` - The time in minutes after midnight, None if the value is not a valid time
+fn clock_time(hours: &str, minutes: &str) -> Option<u32> {
+    let hours: u32 = hours.parse().ok().filter(|hours| *hours <= 24)?;
+    let minutes: u32 = minutes.parse().ok().filter(|minutes| *minutes <= 59)?;
+
+    hours.checked_mul(60)?.checked_add(minutes)
+}
+
+/// Get the number of a month from its abbreviation
+/// # Arguments
+/// * `word` - The abbreviation of the month
+/// # Returns
+/// * `Option<u32>` - The month from 1 to 12, None if the word is not a month
+fn month(word: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .position(|month| *month == word)
+        .map(|index| index as u32 + 1)
+}
+
+/// Check the day of a month
+/// # Arguments
+/// * `day` - The day
+/// # Returns
+/// * `Option<u32>` - The day, None if it is not between 1 and 31
+fn month_day(day: u32) -> Option<u32> {
+    Some(day).filter(|day| (1..=31).contains(day))
+}
+
+/// Get the number of a weekday from its abbreviation
+/// # Arguments
+/// * `word` - The abbreviation of the weekday
+/// # Returns
+/// * `Option<u32>` - The weekday from 0 for Monday to 6 for Sunday, None if the word is not a weekday
+fn weekday(word: &str) -> Option<u32> {
+    WEEKDAYS
+        .iter()
+        .position(|weekday| *weekday == word)
+        .map(|index| index as u32)
+}
+
+/// Get the time of a token
+/// # Arguments
+/// * `token` - A time, `sunrise` or `sunset`
+/// # Returns
+/// * `Option<u32>` - The time in minutes after midnight, None if the token is not a time
+fn time(token: Token) -> Option<u32> {
+    match token {
+        Token::Time(minutes) => Some(minutes),
+        Token::Word("sunrise") => Some(SUNRISE_MINUTES),
+        Token::Word("sunset") => Some(SUNSET_MINUTES),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    /// Create a date and time
+    /// # Arguments
+    /// * `month` - The month, in 2024
+    /// * `day` - The day
+    /// * `hour` - The hour
+    /// # Returns
+    /// * `NaiveDateTime` - The date and time
+    fn at(month: u32, day: u32, hour: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, month, day)
+            .and_then(|date| date.and_hms_opt(hour, 0, 0))
+            .unwrap()
+    }
+
+    #[test]
+    fn winter_closure() {
+        let condition = TimeCondition::parse("Nov-May").unwrap();
+
+        assert!(condition.matches(&at(1, 15, 12)));
+        assert!(condition.matches(&at(11, 1, 12)));
+        assert!(!condition.matches(&at(7, 14, 12)));
+    }
+
+    #[test]
+    fn weekend_hours() {
+        let condition = TimeCondition::parse("Sa,Su 10:00-18:00").unwrap();
+
+        // 2024-01-13 is a Saturday and 2024-01-15 a Monday
+        assert!(condition.matches(&at(1, 13, 12)));
+        assert!(!condition.matches(&at(1, 13, 20)));
+        assert!(!condition.matches(&at(1, 15, 12)));
+    }
+
+    #[test]
+    fn invalid_times_are_rejected() {
+        assert_eq!(TimeCondition::parse("99999999:00-10:00"), None);
+        assert_eq!(TimeCondition::parse("Mo-Fr 25:00-26:00"), None);
+        assert_eq!(TimeCondition::parse("Mo-Fr 07:60-09:00"), None);
+        assert!(TimeCondition::parse("Mo-Fr 22:00-24:00").is_some());
+    }
+
+    #[test]
+    fn invalid_days_are_rejected() {
+        assert_eq!(TimeCondition::parse("Dec 4294967295"), None);
+        assert_eq!(TimeCondition::parse("Aug 01-32"), None);
+        assert!(TimeCondition::parse("Aug 01-15").is_some());
+    }
+}
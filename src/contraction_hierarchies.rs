@@ -0,0 +1,822 @@
+use crate::osm_graph::Edge;
+use crate::osm_graph::OSMGraph;
+use crate::osm_graph::SnappedPoint;
+use crate::route_calculation::{get_direct_edge, get_sources, get_targets, RouteOptions};
+use log::{info, warn};
+use osmpbfreader::objects::NodeId;
+use serde::{Deserialize, Serialize};
+use std::cmp::{Ordering, Reverse};
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// Maximum number of nodes settled by a witness search during the contraction
+const WITNESS_SEARCH_MAX_SETTLED: usize = 200;
+
+/// Arc of the contraction hierarchy, either an edge of the graph or a shortcut
+/// # Variants
+/// * `Edge` - The index of the edge in `OSMGraph::edges`
+/// * `Shortcut` - The two arcs replaced by the shortcut, in order
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum ArcKind {
+    Edge(u32),
+    Shortcut(u32, u32),
+}
+
+/// Arc of the contraction hierarchy
+/// # Fields
+/// * `from` - The index of the node where the arc starts
+/// * `to` - The index of the node where the arc ends
+/// * `weight` - The weight of the arc, the sum of the weights of the edges it replaces
+/// * `kind` - What the arc replaces
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Arc {
+    pub from: u32,
+    pub to: u32,
+    pub weight: f64,
+    pub kind: ArcKind,
+}
+
+/// Contraction hierarchy built over the edges of an OSMGraph
+/// Nodes are contracted one by one in the order of their importance, shortcuts are added
+/// between their neighbours when no witness path is as short. A query only follows arcs going
+/// to more important nodes, from both ends, which settles a few hundred nodes at most
+/// # Fields
+/// * `fingerprint` - The fingerprint of the graph the hierarchy was built for
+/// * `node_ids` - The ids of the nodes (key: node index)
+/// * `node_indexes` - The indexes of the nodes (key: node id)
+/// * `arcs` - The edges and the shortcuts
+/// * `upward_arcs` - The arcs going from a node to a more important node (key: node index)
+/// * `downward_arcs` - The arcs coming to a node from a more important node (key: node index)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContractionHierarchy {
+    pub fingerprint: u64,
+    node_ids: Vec<NodeId>,
+    node_indexes: HashMap<NodeId, u32>,
+    arcs: Vec<Arc>,
+    upward_arcs: Vec<Vec<u32>>,
+    downward_arcs: Vec<Vec<u32>>,
+}
+
+/// Entry of the heaps used by the searches in the hierarchy
+/// # Fields
+/// * `index` - The index of the node
+/// * `distance` - The distance of the node from the start of the search
+#[derive(Debug, Clone, Copy)]
+struct QueueEntry {
+    index: u32,
+    distance: f64,
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+/// Remaining graph during the contraction
+/// # Fields
+/// * `out_arcs` - The arcs leaving each node
+/// * `in_arcs` - The arcs entering each node
+/// * `contracted` - True for the nodes already contracted
+/// * `contracted_neighbours` - The number of contracted neighbours of each node
+struct ContractionGraph {
+    out_arcs: Vec<Vec<u32>>,
+    in_arcs: Vec<Vec<u32>>,
+    contracted: Vec<bool>,
+    contracted_neighbours: Vec<i64>,
+}
+
+/// ContractionHierarchy implementation
+impl ContractionHierarchy {
+    /// Compute the fingerprint of a graph, used to check that a stored hierarchy matches it
+    /// The FNV-1a hash of the edges is used as it is stable across builds
+    /// # Arguments
+    /// * `graph` - The graph
+    /// # Returns
+    /// * `u64` - The fingerprint of the graph
+    pub fn fingerprint(graph: &OSMGraph) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+
+        let mut write = |value: u64| {
+            for byte in value.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(0x100000001b3);
+            }
+        };
+
+        write(graph.get_node_count() as u64);
+        write(graph.get_edge_count() as u64);
+
+        for edge in graph.get_edges() {
+            write(edge.from.0 as u64);
+            write(edge.to.0 as u64);
+            write(edge.weight.to_bits());
+        }
+
+        hash
+    }
+
+    /// Build the contraction hierarchy of a graph
+    /// # Arguments
+    /// * `graph` - The graph
+    /// # Returns
+    /// * `ContractionHierarchy` - The contraction hierarchy of the graph
+    pub fn build(graph: &OSMGraph) -> Self {
+        let start_time = std::time::Instant::now();
+
+        let mut node_ids: Vec<NodeId> = Vec::new();
+        let mut node_indexes: HashMap<NodeId, u32> = HashMap::new();
+        let mut arcs: Vec<Arc> = Vec::new();
+
+        for (edge_index, edge) in graph.get_edges().iter().enumerate() {
+            let mut index_of = |node_id: NodeId| {
+                *node_indexes.entry(node_id).or_insert_with(|| {
+                    node_ids.push(node_id);
+                    (node_ids.len() - 1) as u32
+                })
+            };
+
+            let from = index_of(edge.from);
+            let to = index_of(edge.to);
+
+            if from != to {
+                arcs.push(Arc {
+                    from,
+                    to,
+                    weight: edge.weight,
+                    kind: ArcKind::Edge(edge_index as u32),
+                });
+            }
+        }
+
+        let node_count = node_ids.len();
+
+        let mut remaining = ContractionGraph {
+            out_arcs: vec![Vec::new(); node_count],
+            in_arcs: vec![Vec::new(); node_count],
+            contracted: vec![false; node_count],
+            contracted_neighbours: vec![0; node_count],
+        };
+
+        for (arc_index, arc) in arcs.iter().enumerate() {
+            remaining.out_arcs[arc.from as usize].push(arc_index as u32);
+            remaining.in_arcs[arc.to as usize].push(arc_index as u32);
+        }
+
+        // initial order, using the priority of every node
+        let mut queue: BinaryHeap<Reverse<(i64, u32)>> = BinaryHeap::new();
+
+        for node in 0..node_count as u32 {
+            let priority = ContractionHierarchy::priority(&remaining, &arcs, node);
+            queue.push(Reverse((priority, node)));
+        }
+
+        let mut ranks = vec![0; node_count];
+        let mut rank = 0;
+
+        while let Some(Reverse((priority, node))) = queue.pop() {
+            if remaining.contracted[node as usize] {
+                continue;
+            }
+
+            // lazy update: the priority may have changed since the node was queued
+            let current_priority = ContractionHierarchy::priority(&remaining, &arcs, node);
+
+            if current_priority > priority {
+                if let Some(Reverse((next_priority, _))) = queue.peek() {
+                    if current_priority > *next_priority {
+                        queue.push(Reverse((current_priority, node)));
+                        continue;
+                    }
+                }
+            }
+
+            for shortcut in ContractionHierarchy::shortcuts(&remaining, &arcs, node) {
+                let arc_index = arcs.len() as u32;
+                remaining.out_arcs[shortcut.from as usize].push(arc_index);
+                remaining.in_arcs[shortcut.to as usize].push(arc_index);
+                arcs.push(shortcut);
+            }
+
+            remaining.contracted[node as usize] = true;
+            ranks[node as usize] = rank;
+            rank += 1;
+
+            for arc_index in remaining.out_arcs[node as usize]
+                .iter()
+                .chain(remaining.in_arcs[node as usize].iter())
+            {
+                let arc = &arcs[*arc_index as usize];
+                let neighbour = if arc.from == node { arc.to } else { arc.from };
+                remaining.contracted_neighbours[neighbour as usize] += 1;
+            }
+        }
+
+        let mut upward_arcs = vec![Vec::new(); node_count];
+        let mut downward_arcs = vec![Vec::new(); node_count];
+
+        for (arc_index, arc) in arcs.iter().enumerate() {
+            if ranks[arc.from as usize] < ranks[arc.to as usize] {
+                upward_arcs[arc.from as usize].push(arc_index as u32);
+            } else {
+                downward_arcs[arc.to as usize].push(arc_index as u32);
+            }
+        }
+
+        info!(
+            "Build contraction hierarchy in {} seconds ({} shortcuts)",
+            start_time.elapsed().as_secs(),
+            arcs.len() - graph.get_edge_count()
+        );
+        println!(
+            "Build contraction hierarchy in {} seconds ({} shortcuts)",
+            start_time.elapsed().as_secs(),
+            arcs.len() - graph.get_edge_count()
+        );
+
+        ContractionHierarchy {
+            fingerprint: ContractionHierarchy::fingerprint(graph),
+            node_ids,
+            node_indexes,
+            arcs,
+            upward_arcs,
+            downward_arcs,
+        }
+    }
+
+    /// Load the contraction hierarchy of a graph from the cache directory, or build and store it
+    /// # Arguments
+    /// * `graph` - The graph
+    /// * `cache_dir` - The directory where the hierarchies are stored
+    /// # Returns
+    /// * `ContractionHierarchy` - The contraction hierarchy of the graph
+    pub fn load_or_build(graph: &OSMGraph, cache_dir: &Path) -> Self {
+        let fingerprint = ContractionHierarchy::fingerprint(graph);
+        let file_path = cache_dir.join(format!("ch-{:016x}.bin", fingerprint));
+
+        if let Ok(file) = File::open(&file_path) {
+            match bincode::deserialize_from::<_, ContractionHierarchy>(BufReader::new(file)) {
+                Ok(hierarchy) if hierarchy.fingerprint == fingerprint => {
+                    info!("Contraction hierarchy loaded from {:?}", file_path);
+                    println!("Contraction hierarchy loaded from {:?}", file_path);
+                    return hierarchy;
+                }
+                _ => warn!("Invalid contraction hierarchy file {:?}", file_path),
+            }
+        }
+
+        let hierarchy = ContractionHierarchy::build(graph);
+
+        match hierarchy.save(&file_path) {
+            Ok(_) => info!("Contraction hierarchy saved to {:?}", file_path),
+            Err(error) => warn!("Failed to save contraction hierarchy: {}", error),
+        }
+
+        hierarchy
+    }
+
+    /// Save the contraction hierarchy to a cache file
+    /// The hierarchy is written to a temporary file which is then renamed, so an interrupted save
+    /// never leaves a truncated cache file
+    /// # Arguments
+    /// * `file_path` - The path of the cache file
+    /// # Returns
+    /// * `Result<(), String>` - An error message if the file can't be written
+    fn save(&self, file_path: &Path) -> Result<(), String> {
+        if let Some(cache_dir) = file_path.parent() {
+            fs::create_dir_all(cache_dir).map_err(|error| error.to_string())?;
+        }
+
+        let temp_path = file_path.with_extension("tmp");
+
+        {
+            let file = File::create(&temp_path).map_err(|error| error.to_string())?;
+            let mut writer = BufWriter::new(file);
+
+            bincode::serialize_into(&mut writer, self).map_err(|error| error.to_string())?;
+            writer.flush().map_err(|error| error.to_string())?;
+        }
+
+        fs::rename(&temp_path, file_path).map_err(|error| error.to_string())
+    }
+
+    /// Priority of a node for the contraction, smaller is contracted first
+    /// The edge difference (shortcuts added minus arcs removed) plus the number of contracted
+    /// neighbours, which spreads the contraction uniformly over the graph
+    /// # Arguments
+    /// * `remaining` - The remaining graph
+    /// * `arcs` - The arcs
+    /// * `node` - The index of the node
+    /// # Returns
+    /// * `i64` - The priority of the node
+    fn priority(remaining: &ContractionGraph, arcs: &[Arc], node: u32) -> i64 {
+        let shortcuts = ContractionHierarchy::shortcuts(remaining, arcs, node).len() as i64;
+
+        let removed_arcs = remaining.out_arcs[node as usize]
+            .iter()
+            .filter(|arc| !remaining.contracted[arcs[**arc as usize].to as usize])
+            .count()
+            + remaining.in_arcs[node as usize]
+                .iter()
+                .filter(|arc| !remaining.contracted[arcs[**arc as usize].from as usize])
+                .count();
+
+        shortcuts - removed_arcs as i64 + remaining.contracted_neighbours[node as usize]
+    }
+
+    /// Get the shortcuts needed to contract a node
+    /// A shortcut u -> x replaces u -> node -> x when no path from u to x avoiding the node is as short
+    /// # Arguments
+    /// * `remaining` - The remaining graph
+    /// * `arcs` - The arcs
+    /// * `node` - The index of the node
+    /// # Returns
+    /// * `Vec<Arc>` - The shortcuts
+    fn shortcuts(remaining: &ContractionGraph, arcs: &[Arc], node: u32) -> Vec<Arc> {
+        // cheapest arc from each remaining neighbour, and to each remaining neighbour
+        let mut incoming: HashMap<u32, u32> = HashMap::new();
+        let mut outgoing: HashMap<u32, u32> = HashMap::new();
+
+        for arc_index in &remaining.in_arcs[node as usize] {
+            let arc = &arcs[*arc_index as usize];
+
+            if !remaining.contracted[arc.from as usize]
+                && incoming
+                    .get(&arc.from)
+                    .is_none_or(|best| arc.weight < arcs[*best as usize].weight)
+            {
+                incoming.insert(arc.from, *arc_index);
+            }
+        }
+
+        for arc_index in &remaining.out_arcs[node as usize] {
+            let arc = &arcs[*arc_index as usize];
+
+            if !remaining.contracted[arc.to as usize]
+                && outgoing
+                    .get(&arc.to)
+                    .is_none_or(|best| arc.weight < arcs[*best as usize].weight)
+            {
+                outgoing.insert(arc.to, *arc_index);
+            }
+        }
+
+        let mut shortcuts = Vec::new();
+
+        for (from, in_arc) in &incoming {
+            let in_weight = arcs[*in_arc as usize].weight;
+
+            let max_weight = outgoing
+                .iter()
+                .filter(|(to, _)| *to != from)
+                .map(|(_, out_arc)| in_weight + arcs[*out_arc as usize].weight)
+                .fold(0.0, f64::max);
+
+            if max_weight == 0.0 {
+                continue;
+            }
+
+            let witnesses =
+                ContractionHierarchy::witness_search(remaining, arcs, *from, node, max_weight);
+
+            for (to, out_arc) in &outgoing {
+                if to == from {
+                    continue;
+                }
+
+                let weight = in_weight + arcs[*out_arc as usize].weight;
+
+                if witnesses.get(to).is_none_or(|witness| *witness > weight) {
+                    shortcuts.push(Arc {
+                        from: *from,
+                        to: *to,
+                        weight,
+                        kind: ArcKind::Shortcut(*in_arc, *out_arc),
+                    });
+                }
+            }
+        }
+
+        shortcuts
+    }
+
+    /// Dijkstra search in the remaining graph avoiding a node, limited in weight and in settled nodes
+    /// # Arguments
+    /// * `remaining` - The remaining graph
+    /// * `arcs` - The arcs
+    /// * `source` - The index of the node where the search starts
+    /// * `excluded` - The index of the node to avoid
+    /// * `max_weight` - The weight where the search stops
+    /// # Returns
+    /// * `HashMap<u32, f64>` - The distances found (key: node index)
+    fn witness_search(
+        remaining: &ContractionGraph,
+        arcs: &[Arc],
+        source: u32,
+        excluded: u32,
+        max_weight: f64,
+    ) -> HashMap<u32, f64> {
+        let mut distances: HashMap<u32, f64> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+        let mut settled = 0;
+
+        distances.insert(source, 0.0);
+        heap.push(QueueEntry {
+            index: source,
+            distance: 0.0,
+        });
+
+        while let Some(QueueEntry { index, distance }) = heap.pop() {
+            if distance > distances[&index] {
+                continue;
+            }
+
+            settled += 1;
+
+            if distance > max_weight || settled > WITNESS_SEARCH_MAX_SETTLED {
+                break;
+            }
+
+            for arc_index in &remaining.out_arcs[index as usize] {
+                let arc = &arcs[*arc_index as usize];
+
+                if arc.to == excluded || remaining.contracted[arc.to as usize] {
+                    continue;
+                }
+
+                let new_dist = distance + arc.weight;
+
+                if new_dist < *distances.get(&arc.to).unwrap_or(&f64::INFINITY) {
+                    distances.insert(arc.to, new_dist);
+                    heap.push(QueueEntry {
+                        index: arc.to,
+                        distance: new_dist,
+                    });
+                }
+            }
+        }
+
+        distances
+    }
+
+    /// Find the shortest path between two points snapped onto edges
+    /// A forward search follows the upward arcs from the start and a backward search follows the
+    /// downward arcs from the end, the shortcuts of the best route are unpacked into graph edges
+    /// Returns the same result as `route_calculation::dijkstra`
+    /// # Arguments
+    /// * `graph` - The graph the hierarchy was built for
+    /// * `start` - The snapped start point
+    /// * `end` - The snapped end point
+    pub fn query(
+        &self,
+        graph: &OSMGraph,
+        start: &SnappedPoint,
+        end: &SnappedPoint,
+    ) -> (Option<Vec<NodeId>>, Option<Vec<Edge>>) {
+        // the hierarchy is built on the weights of the edges, which the default options use
+        let options = RouteOptions::default();
+
+        let sources = get_sources(graph, start, &options);
+        let targets = get_targets(graph, end, &options);
+
+        let (forward_distances, forward_arcs) = self.upward_search(
+            sources
+                .iter()
+                .filter_map(|source| Some((*self.node_indexes.get(&source.node_id)?, source.cost))),
+            &self.upward_arcs,
+            true,
+        );
+        let (backward_distances, backward_arcs) = self.upward_search(
+            targets
+                .iter()
+                .filter_map(|target| Some((*self.node_indexes.get(&target.node_id)?, target.cost))),
+            &self.downward_arcs,
+            false,
+        );
+
+        // route staying on the snapped edge, used if no route through the graph is shorter
+        let direct_edge = get_direct_edge(graph, start, end, &options);
+        let mut best_distance = direct_edge
+            .as_ref()
+            .map_or(f64::INFINITY, |(_, cost)| *cost);
+        let mut meeting_node: Option<u32> = None;
+
+        for (index, forward_distance) in &forward_distances {
+            if let Some(backward_distance) = backward_distances.get(index) {
+                if forward_distance + backward_distance < best_distance {
+                    best_distance = forward_distance + backward_distance;
+                    meeting_node = Some(*index);
+                }
+            }
+        }
+
+        let meeting_node = match meeting_node {
+            Some(meeting_node) => meeting_node,
+            None => {
+                if let Some((edge, _)) = direct_edge {
+                    return (Some(vec![edge.from, edge.to]), Some(vec![edge]));
+                }
+
+                warn!("No path found");
+
+                return (None, None);
+            }
+        };
+
+        // arcs from the start to the meeting node, then from the meeting node to the end
+        let mut path_arcs: Vec<u32> = Vec::new();
+        let mut current = meeting_node;
+
+        while let Some(arc_index) = forward_arcs.get(&current) {
+            path_arcs.push(*arc_index);
+            current = self.arcs[*arc_index as usize].from;
+        }
+
+        let first_node = self.node_ids[current as usize];
+        path_arcs.reverse();
+
+        current = meeting_node;
+
+        while let Some(arc_index) = backward_arcs.get(&current) {
+            path_arcs.push(*arc_index);
+            current = self.arcs[*arc_index as usize].to;
+        }
+
+        let last_node = self.node_ids[current as usize];
+
+        let source = sources
+            .iter()
+            .filter(|source| source.node_id == first_node)
+            .min_by(|a, b| a.cost.total_cmp(&b.cost))
+            .unwrap();
+        let target = targets
+            .iter()
+            .filter(|target| target.node_id == last_node)
+            .min_by(|a, b| a.cost.total_cmp(&b.cost))
+            .unwrap();
+
+        let mut path: Vec<NodeId> = vec![first_node];
+        let mut edge_path: Vec<Edge> = vec![source.edge.clone()];
+
+        for edge_index in self.unpack(&path_arcs) {
+            let edge = &graph.get_edges()[edge_index as usize];
+            path.push(edge.to);
+            edge_path.push(edge.clone());
+        }
+
+        edge_path.push(target.edge.clone());
+
+        (Some(path), Some(edge_path))
+    }
+
+    /// Dijkstra search following only the given arcs, until the heap is empty
+    /// # Arguments
+    /// * `seeds` - The indexes of the nodes where the search starts and their initial distances
+    /// * `arcs_by_node` - The arcs to follow from each node
+    /// * `forward` - True to follow the arcs from `from` to `to`, false to follow them backwards
+    /// # Returns
+    /// * `(HashMap<u32, f64>, HashMap<u32, u32>)` - The distances (key: node index) and the arc used to reach each node
+    fn upward_search(
+        &self,
+        seeds: impl Iterator<Item = (u32, f64)>,
+        arcs_by_node: &[Vec<u32>],
+        forward: bool,
+    ) -> (HashMap<u32, f64>, HashMap<u32, u32>) {
+        let mut distances: HashMap<u32, f64> = HashMap::new();
+        let mut prev_arcs: HashMap<u32, u32> = HashMap::new();
+        let mut heap = BinaryHeap::new();
+
+        for (index, distance) in seeds {
+            if distance < *distances.get(&index).unwrap_or(&f64::INFINITY) {
+                distances.insert(index, distance);
+                heap.push(QueueEntry { index, distance });
+            }
+        }
+
+        while let Some(QueueEntry { index, distance }) = heap.pop() {
+            if distance > distances[&index] {
+                continue;
+            }
+
+            for arc_index in &arcs_by_node[index as usize] {
+                let arc = &self.arcs[*arc_index as usize];
+                let next = if forward { arc.to } else { arc.from };
+                let new_dist = distance + arc.weight;
+
+                if new_dist < *distances.get(&next).unwrap_or(&f64::INFINITY) {
+                    distances.insert(next, new_dist);
+                    prev_arcs.insert(next, *arc_index);
+                    heap.push(QueueEntry {
+                        index: next,
+                        distance: new_dist,
+                    });
+                }
+            }
+        }
+
+        (distances, prev_arcs)
+    }
+
+    /// Replace the shortcuts of a list of arcs by the edges they replace
+    /// # Arguments
+    /// * `arcs` - The indexes of the arcs, in order
+    /// # Returns
+    /// * `Vec<u32>` - The indexes of the edges in `OSMGraph::edges`, in order
+    fn unpack(&self, arcs: &[u32]) -> Vec<u32> {
+        let mut edges = Vec::new();
+        let mut stack: Vec<u32> = arcs.iter().rev().copied().collect();
+
+        while let Some(arc_index) = stack.pop() {
+            match self.arcs[arc_index as usize].kind {
+                ArcKind::Edge(edge_index) => edges.push(edge_index),
+                ArcKind::Shortcut(first, second) => {
+                    stack.push(second);
+                    stack.push(first);
+                }
+            }
+        }
+
+        edges
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route_calculation::dijkstra;
+    use crate::test_graph::jittered_grid;
+
+    /// Get the points where the routes of the tests start and end, on the roads of a 5 x 5 grid
+    /// # Arguments
+    /// * `graph` - The grid
+    /// # Returns
+    /// * `Vec<SnappedPoint>` - The points snapped onto the grid
+    fn points(graph: &OSMGraph) -> Vec<SnappedPoint> {
+        [
+            (47.0, 7.005),
+            (47.012, 7.031),
+            (47.035, 7.002),
+            (47.041, 7.044),
+            (47.02, 7.02),
+            (47.003, 7.039),
+        ]
+        .iter()
+        .map(|(lat, lon)| graph.snap_to_edge(*lat, *lon).unwrap())
+        .collect()
+    }
+
+    /// Check that the hierarchy finds the same routes as Dijkstra between every pair of points
+    /// # Arguments
+    /// * `graph` - The graph
+    /// * `hierarchy` - The contraction hierarchy of the graph
+    fn assert_same_routes(graph: &OSMGraph, hierarchy: &ContractionHierarchy) {
+        let options = RouteOptions::default();
+        let points = points(graph);
+
+        for start in &points {
+            for end in &points {
+                let (expected_nodes, expected_edges) = dijkstra(graph, start, end, &options);
+                let (nodes, edges) = hierarchy.query(graph, start, end);
+
+                let expected_edges = expected_edges.unwrap();
+                let edges = edges.unwrap();
+
+                assert_eq!(nodes, expected_nodes);
+                assert_eq!(
+                    edges.iter().map(Edge::key).collect::<Vec<_>>(),
+                    expected_edges.iter().map(Edge::key).collect::<Vec<_>>()
+                );
+
+                let cost: f64 = edges.iter().map(|edge| edge.weight).sum();
+                let expected_cost: f64 = expected_edges.iter().map(|edge| edge.weight).sum();
+                assert!((cost - expected_cost).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn query_matches_dijkstra() {
+        let graph = jittered_grid(5);
+        let hierarchy = ContractionHierarchy::build(&graph);
+
+        assert!(hierarchy.arcs.len() > graph.get_edge_count());
+
+        assert_same_routes(&graph, &hierarchy);
+    }
+
+    #[test]
+    fn shortcuts_unpack_into_their_edges() {
+        let graph = jittered_grid(5);
+        let hierarchy = ContractionHierarchy::build(&graph);
+        let edges = graph.get_edges();
+
+        for (arc_index, arc) in hierarchy.arcs.iter().enumerate() {
+            let unpacked = hierarchy.unpack(&[arc_index as u32]);
+            let first = &edges[unpacked[0] as usize];
+            let last = &edges[unpacked[unpacked.len() - 1] as usize];
+
+            assert_eq!(first.from, hierarchy.node_ids[arc.from as usize]);
+            assert_eq!(last.to, hierarchy.node_ids[arc.to as usize]);
+            assert!(unpacked
+                .windows(2)
+                .all(|pair| edges[pair[0] as usize].to == edges[pair[1] as usize].from));
+
+            let weight: f64 = unpacked
+                .iter()
+                .map(|edge_index| edges[*edge_index as usize].weight)
+                .sum();
+            assert!((weight - arc.weight).abs() < 1e-6);
+
+            if let ArcKind::Edge(edge_index) = arc.kind {
+                assert_eq!(unpacked, vec![edge_index]);
+            }
+        }
+    }
+
+    #[test]
+    fn witness_search_is_capped() {
+        // a chain of 1000 nodes where every arc weighs 1
+        let node_count = 1000;
+        let arcs: Vec<Arc> = (0..node_count - 1)
+            .map(|index| Arc {
+                from: index,
+                to: index + 1,
+                weight: 1.0,
+                kind: ArcKind::Edge(index),
+            })
+            .collect();
+
+        let mut remaining = ContractionGraph {
+            out_arcs: vec![Vec::new(); node_count as usize],
+            in_arcs: vec![Vec::new(); node_count as usize],
+            contracted: vec![false; node_count as usize],
+            contracted_neighbours: vec![0; node_count as usize],
+        };
+
+        for (arc_index, arc) in arcs.iter().enumerate() {
+            remaining.out_arcs[arc.from as usize].push(arc_index as u32);
+            remaining.in_arcs[arc.to as usize].push(arc_index as u32);
+        }
+
+        let distances =
+            ContractionHierarchy::witness_search(&remaining, &arcs, 0, u32::MAX, f64::INFINITY);
+
+        // the settled nodes and the neighbour of the last one
+        assert_eq!(distances.len(), WITNESS_SEARCH_MAX_SETTLED + 1);
+        assert_eq!(distances[&200], 200.0);
+
+        let distances = ContractionHierarchy::witness_search(&remaining, &arcs, 0, u32::MAX, 10.0);
+        assert_eq!(distances.len(), 12);
+
+        // the excluded node cuts the chain
+        let distances =
+            ContractionHierarchy::witness_search(&remaining, &arcs, 0, 5, f64::INFINITY);
+        assert_eq!(distances.len(), 5);
+    }
+
+    #[test]
+    fn cache_round_trip() {
+        let graph = jittered_grid(5);
+        let cache_dir = std::env::temp_dir().join(format!("ch-test-{}", std::process::id()));
+        let file_path = cache_dir.join(format!(
+            "ch-{:016x}.bin",
+            ContractionHierarchy::fingerprint(&graph)
+        ));
+
+        let built = ContractionHierarchy::load_or_build(&graph, &cache_dir);
+        assert!(file_path.exists());
+        assert!(!file_path.with_extension("tmp").exists());
+
+        let loaded = ContractionHierarchy::load_or_build(&graph, &cache_dir);
+        assert_eq!(loaded.fingerprint, built.fingerprint);
+        assert_eq!(loaded.node_ids, built.node_ids);
+        assert_eq!(loaded.arcs.len(), built.arcs.len());
+        assert_same_routes(&graph, &loaded);
+
+        // a truncated file is rebuilt and replaced
+        let length = fs::metadata(&file_path).unwrap().len();
+        fs::write(&file_path, b"truncated").unwrap();
+
+        let rebuilt = ContractionHierarchy::load_or_build(&graph, &cache_dir);
+        assert_eq!(rebuilt.arcs.len(), built.arcs.len());
+        assert_eq!(fs::metadata(&file_path).unwrap().len(), length);
+
+        fs::remove_dir_all(&cache_dir).unwrap();
+    }
+}
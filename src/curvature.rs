@@ -0,0 +1,87 @@
+use crate::osm_graph::OSMGraph;
+use osmpbfreader::objects::Node;
+
+/// Curvature levels as (maximum radius in meters, weight), from the tightest to the widest
+/// A section of road with a larger radius is considered straight
+const CURVATURE_LEVELS: [(f64, f64); 4] = [(30.0, 2.0), (60.0, 1.6), (100.0, 1.3), (175.0, 1.0)];
+
+/// Weight factor of a straight edge, the default weight of an edge decreases with its curviness
+const STRAIGHT_WEIGHT_FACTOR: f64 = 1.5;
+
+/// Get the weight of a section of road from its radius
+/// # Arguments
+/// * `radius_m` - The radius of the curve in meters
+/// # Returns
+/// * `f64` - The weight of the curve level, 0 for a straight section
+pub fn radius_weight(radius_m: f64) -> f64 {
+    CURVATURE_LEVELS
+        .iter()
+        .find(|(max_radius, _)| radius_m < *max_radius)
+        .map_or(0.0, |(_, weight)| *weight)
+}
+
+/// Compute the curvature score of a line of nodes
+/// Each segment takes the smallest radius of the circles going through it and its neighbours,
+/// and the score is the sum of the segment lengths weighted by the level of their radius
+/// A straight road has a score of 0, a road made only of hairpins has a score of twice its length
+/// # Arguments
+/// * `nodes` - The nodes of the line
+/// # Returns
+/// * `f64` - The curvature score in meters
+pub fn curvature_score(nodes: &[&Node]) -> f64 {
+    if nodes.len() < 3 {
+        return 0.0;
+    }
+
+    // radius of the circle going through each node and its two neighbours
+    let radii: Vec<f64> = nodes
+        .windows(3)
+        .map(|triple| OSMGraph::circle_radius(triple[0], triple[1], triple[2]))
+        .collect();
+
+    let mut score = 0.0;
+
+    for (index, segment) in nodes.windows(2).enumerate() {
+        let before = if index > 0 {
+            radii[index - 1]
+        } else {
+            f64::INFINITY
+        };
+        let after = radii.get(index).copied().unwrap_or(f64::INFINITY);
+
+        let length = OSMGraph::haversine_distance(
+            segment[0].lat(),
+            segment[0].lon(),
+            segment[1].lat(),
+            segment[1].lon(),
+        );
+
+        score += length * radius_weight(before.min(after));
+    }
+
+    score
+}
+
+/// Get the curviness of an edge, its curvature score per meter
+/// # Arguments
+/// * `curvature` - The curvature score in meters
+/// * `distance_m` - The length in meters
+/// # Returns
+/// * `f64` - The curviness, between 0 for a straight road and 2 for a road made only of hairpins
+pub fn curviness(curvature: f64, distance_m: f64) -> f64 {
+    if distance_m > 0.0 {
+        curvature / distance_m
+    } else {
+        0.0
+    }
+}
+
+/// Get the default weight of an edge, lower on curvy roads to rank them first for motorcyclists
+/// # Arguments
+/// * `curvature` - The curvature score in meters
+/// * `distance_m` - The length in meters
+/// # Returns
+/// * `f64` - The weight, from 1.5 times the length on a straight road to half the length on hairpins
+pub fn edge_weight(curvature: f64, distance_m: f64) -> f64 {
+    distance_m * STRAIGHT_WEIGHT_FACTOR / (1.0 + curviness(curvature, distance_m))
+}
@@ -0,0 +1,436 @@
+use crate::maneuver::{Location, Maneuver, ManeuverType};
+use crate::osm_graph::OSMGraph;
+use crate::turns;
+use osmpbfreader::objects::Node;
+use serde::{Deserialize, Serialize};
+
+/// Largest radius in meters of a curve worth a warning
+const CURVE_MAX_RADIUS_M: f64 = 60.0;
+
+/// Smallest turning angle in degrees of a curve worth a warning
+const CURVE_MIN_ANGLE: f64 = 30.0;
+
+/// Largest radius in meters of a sharp curve
+const SHARP_MAX_RADIUS_M: f64 = 30.0;
+
+/// Smallest turning angle in degrees of a sharp curve
+const SHARP_MIN_ANGLE: f64 = 90.0;
+
+/// Smallest turning angle in degrees of a hairpin
+const HAIRPIN_MIN_ANGLE: f64 = 135.0;
+
+/// A curve tightens when the radius of its second half is smaller than this ratio of the radius
+/// of its first half
+const TIGHTENING_RATIO: f64 = 0.7;
+
+/// Severity of a curve
+/// # Variants
+/// * `Moderate` - A curve with a radius under 60 meters
+/// * `Sharp` - A curve with a radius under 30 meters or turning by 90 degrees or more
+/// * `Hairpin` - A curve turning by 135 degrees or more
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CurveSeverity {
+    Moderate,
+    Sharp,
+    Hairpin,
+}
+
+/// Side to which a curve turns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CurveDirection {
+    Left,
+    Right,
+}
+
+/// Warning of an upcoming curve along a route, given to the rider as a `curve_warning` maneuver
+/// # Fields
+/// * `severity` - The severity of the curve
+/// * `direction` - The side to which the curve turns
+/// * `tightening` - True if the radius of the curve decreases along it
+/// * `radius` - The smallest radius of the curve in meters
+/// * `angle` - The turning angle of the curve in degrees
+/// * `distance` - The distance from the start of the route to the entry of the curve in meters
+/// * `latitude` - The latitude of the entry of the curve
+/// * `longitude` - The longitude of the entry of the curve
+#[derive(Debug, Clone, PartialEq)]
+pub struct CurveWarning {
+    pub severity: CurveSeverity,
+    pub direction: CurveDirection,
+    pub tightening: bool,
+    pub radius: f64,
+    pub angle: f64,
+    pub distance: f64,
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Curvature of a route at one of its nodes
+/// # Fields
+/// * `index` - The index of the node in the route
+/// * `radius` - The radius of the circle going through the node and its neighbours in meters
+/// * `turn` - The change of bearing at the node in degrees, positive to the right
+struct NodeCurvature {
+    index: usize,
+    radius: f64,
+    turn: f64,
+}
+
+/// Detect the curves of a route that deserve a warning
+/// Consecutive nodes turning to the same side with a radius under 60 meters form a curve. The
+/// nodes where the route changes of way are junction maneuvers and never belong to a curve
+/// # Arguments
+/// * `nodes` - The nodes of the route, in order and without repetition
+/// * `junctions` - For each node, true if the route changes of way at the node
+/// # Returns
+/// * `Vec<CurveWarning>` - The warnings of the curves, in the order of the route
+pub fn curve_warnings(nodes: &[&Node], junctions: &[bool]) -> Vec<CurveWarning> {
+    let mut distances = vec![0.0; nodes.len()];
+
+    for index in 1..nodes.len() {
+        distances[index] = distances[index - 1]
+            + OSMGraph::haversine_distance(
+                nodes[index - 1].lat(),
+                nodes[index - 1].lon(),
+                nodes[index].lat(),
+                nodes[index].lon(),
+            );
+    }
+
+    let mut warnings = Vec::new();
+    let mut curve: Vec<NodeCurvature> = Vec::new();
+
+    for index in 1..nodes.len().saturating_sub(1) {
+        let (before, node, after) = (nodes[index - 1], nodes[index], nodes[index + 1]);
+
+        let bearing_in = OSMGraph::bearing(before.lat(), before.lon(), node.lat(), node.lon());
+        let bearing_out = OSMGraph::bearing(node.lat(), node.lon(), after.lat(), after.lon());
+        let turn = turns::turn_angle(bearing_in, bearing_out);
+        let radius = OSMGraph::circle_radius(before, node, after);
+
+        let curved = !junctions.get(index).copied().unwrap_or(false)
+            && radius < CURVE_MAX_RADIUS_M
+            && turn != 0.0;
+        let same_side = curve
+            .last()
+            .is_none_or(|last| (last.turn > 0.0) == (turn > 0.0));
+
+        if !curved || !same_side {
+            warnings.extend(curve_warning(&curve, nodes, &distances));
+            curve.clear();
+        }
+
+        if curved {
+            curve.push(NodeCurvature {
+                index,
+                radius,
+                turn,
+            });
+        }
+    }
+
+    warnings.extend(curve_warning(&curve, nodes, &distances));
+
+    warnings
+}
+
+/// Grade a curve and build its warning
+/// # Arguments
+/// * `curve` - The curvature of the consecutive nodes of the curve
+/// * `nodes` - The nodes of the route
+/// * `distances` - The distance from the start of the route to each node in meters
+/// # Returns
+/// * `Option<CurveWarning>` - The warning, None if the curve doesn't turn enough to deserve one
+fn curve_warning(
+    curve: &[NodeCurvature],
+    nodes: &[&Node],
+    distances: &[f64],
+) -> Option<CurveWarning> {
+    let entry = curve.first()?;
+
+    let turn: f64 = curve.iter().map(|node| node.turn).sum();
+    let angle = turn.abs();
+
+    if angle < CURVE_MIN_ANGLE {
+        return None;
+    }
+
+    let min_radius = |nodes: &[NodeCurvature]| {
+        nodes
+            .iter()
+            .map(|node| node.radius)
+            .fold(f64::INFINITY, f64::min)
+    };
+
+    let radius = min_radius(curve);
+    let (first_half, second_half) = curve.split_at(curve.len() / 2);
+    let tightening = !first_half.is_empty()
+        && min_radius(second_half) < TIGHTENING_RATIO * min_radius(first_half);
+
+    let severity = if angle >= HAIRPIN_MIN_ANGLE {
+        CurveSeverity::Hairpin
+    } else if radius < SHARP_MAX_RADIUS_M || angle >= SHARP_MIN_ANGLE {
+        CurveSeverity::Sharp
+    } else {
+        CurveSeverity::Moderate
+    };
+
+    let direction = if turn > 0.0 {
+        CurveDirection::Right
+    } else {
+        CurveDirection::Left
+    };
+
+    Some(CurveWarning {
+        severity,
+        direction,
+        tightening,
+        radius,
+        angle,
+        distance: distances[entry.index],
+        latitude: nodes[entry.index].lat(),
+        longitude: nodes[entry.index].lon(),
+    })
+}
+
+/// Insert the curve warnings of a route between its maneuvers, in the order of the route
+/// The distance and the travel time from the previous maneuver are split at each warning, the
+/// travel time in proportion to the distance
+/// # Arguments
+/// * `instructions` - The maneuvers of the route, without curve warnings
+/// * `warnings` - The warnings of the curves of the route, in order
+/// * `total_distance` - The length of the route in meters
+/// * `total_time` - The travel time of the route in seconds
+/// # Returns
+/// * `Vec<Maneuver>` - The maneuvers and the curve warnings of the route
+pub fn add_to_instructions(
+    instructions: Vec<Maneuver>,
+    warnings: &[CurveWarning],
+    total_distance: f64,
+    total_time: f64,
+) -> Vec<Maneuver> {
+    let instructed_distance: f64 = instructions.iter().map(|maneuver| maneuver.distance).sum();
+    let instructed_time: f64 = instructions.iter().map(|maneuver| maneuver.duration).sum();
+
+    let mut merged = Vec::with_capacity(instructions.len() + warnings.len());
+    let mut warnings = warnings.iter().peekable();
+
+    // distance from the start of the route to the last maneuver added
+    let mut travelled = 0.0;
+
+    // the part of the route after the last maneuver takes the warnings left
+    for maneuver in instructions.into_iter().map(Some).chain([None]) {
+        let (mut distance, mut duration) = maneuver.as_ref().map_or(
+            (
+                (total_distance - instructed_distance).max(0.0),
+                (total_time - instructed_time).max(0.0),
+            ),
+            |maneuver| (maneuver.distance, maneuver.duration),
+        );
+        let end = travelled + distance;
+
+        while let Some(warning) =
+            warnings.next_if(|warning| maneuver.is_none() || warning.distance < end)
+        {
+            let warning_distance = (warning.distance - travelled).clamp(0.0, distance);
+            let warning_duration = if distance > 0.0 {
+                duration * warning_distance / distance
+            } else {
+                0.0
+            };
+
+            merged.push(
+                Maneuver::new(
+                    ManeuverType::CurveWarning,
+                    warning_distance,
+                    warning_duration,
+                    Location {
+                        latitude: warning.latitude,
+                        longitude: warning.longitude,
+                    },
+                )
+                .with_curve(warning),
+            );
+
+            travelled += warning_distance;
+            distance -= warning_distance;
+            duration -= warning_duration;
+        }
+
+        if let Some(mut maneuver) = maneuver {
+            maneuver.distance = distance;
+            maneuver.duration = duration;
+            merged.push(maneuver);
+        }
+
+        travelled += distance;
+    }
+
+    merged
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maneuver::ManeuverModifier;
+    use crate::test_graph::node;
+
+    /// Meters per degree of latitude
+    const METERS_PER_DEGREE: f64 = 6371.0 * 1000.0 * std::f64::consts::PI / 180.0;
+
+    /// Build a route going north for 100 meters, then following a circular curve, then going
+    /// straight for 100 meters, with a node every 10 degrees of the curve
+    /// # Arguments
+    /// * `radius` - The radius of the curve in meters
+    /// * `angle` - The turning angle of the curve in degrees
+    /// * `right` - True if the curve turns to the right
+    /// # Returns
+    /// * `Vec<Node>` - The nodes of the route, from (47.0, 7.0)
+    fn curve(radius: f64, angle: f64, right: bool) -> Vec<Node> {
+        let side = if right { 1.0 } else { -1.0 };
+        let mut points = vec![(0.0, 0.0), (0.0, 100.0)];
+
+        let steps = (angle / 10.0).ceil() as usize;
+
+        for step in 1..=steps {
+            let turned = (angle * step as f64 / steps as f64).to_radians();
+            points.push((
+                side * radius * (1.0 - turned.cos()),
+                100.0 + radius * turned.sin(),
+            ));
+        }
+
+        let (x, y) = points[points.len() - 1];
+        let heading = side * angle.to_radians();
+        points.push((x + 100.0 * heading.sin(), y + 100.0 * heading.cos()));
+
+        let cos_lat = 47.0_f64.to_radians().cos();
+
+        points
+            .iter()
+            .enumerate()
+            .map(|(index, (x, y))| {
+                node(
+                    index as i64 + 1,
+                    47.0 + y / METERS_PER_DEGREE,
+                    7.0 + x / (METERS_PER_DEGREE * cos_lat),
+                )
+            })
+            .collect()
+    }
+
+    /// Detect the curves of a route without junctions
+    /// # Arguments
+    /// * `nodes` - The nodes of the route
+    /// # Returns
+    /// * `Vec<CurveWarning>` - The warnings of the curves
+    fn warnings(nodes: &[Node]) -> Vec<CurveWarning> {
+        let nodes: Vec<&Node> = nodes.iter().collect();
+
+        curve_warnings(&nodes, &vec![false; nodes.len()])
+    }
+
+    #[test]
+    fn curves_are_graded() {
+        for (radius, angle, severity) in [
+            (15.0, 180.0, CurveSeverity::Hairpin),
+            (50.0, 120.0, CurveSeverity::Sharp),
+            (20.0, 60.0, CurveSeverity::Sharp),
+            (50.0, 60.0, CurveSeverity::Moderate),
+        ] {
+            let warnings = warnings(&curve(radius, angle, true));
+
+            assert_eq!(warnings.len(), 1, "{} m, {}°", radius, angle);
+            assert_eq!(warnings[0].severity, severity, "{} m, {}°", radius, angle);
+            assert!((warnings[0].radius - radius).abs() < 1.0);
+            assert!(!warnings[0].tightening);
+        }
+    }
+
+    #[test]
+    fn gentle_curves_are_ignored() {
+        assert!(warnings(&curve(100.0, 90.0, true)).is_empty());
+        assert!(warnings(&curve(50.0, 20.0, true)).is_empty());
+    }
+
+    #[test]
+    fn curve_directions() {
+        let right = warnings(&curve(15.0, 180.0, true));
+        let left = warnings(&curve(15.0, 180.0, false));
+
+        assert_eq!(right[0].direction, CurveDirection::Right);
+        assert_eq!(left[0].direction, CurveDirection::Left);
+
+        // the curve starts after the first 100 meters
+        for warning in [&right[0], &left[0]] {
+            assert!((warning.distance - 100.0).abs() < 5.0);
+            assert!(warning.latitude > 47.0);
+        }
+    }
+
+    #[test]
+    fn junctions_are_not_curves() {
+        let nodes = curve(15.0, 180.0, true);
+        let nodes: Vec<&Node> = nodes.iter().collect();
+
+        // a junction in the middle of the hairpin splits it into two sharp curves
+        let mut junctions = vec![false; nodes.len()];
+        junctions[nodes.len() / 2] = true;
+
+        let split = curve_warnings(&nodes, &junctions);
+        assert_eq!(split.len(), 2);
+        assert!(split
+            .iter()
+            .all(|warning| warning.severity == CurveSeverity::Sharp));
+
+        // a turn at a junction is a maneuver, not a curve
+        let junctions = vec![true; nodes.len()];
+        assert!(curve_warnings(&nodes, &junctions).is_empty());
+    }
+
+    #[test]
+    fn warnings_are_inserted_between_maneuvers() {
+        let location = Location {
+            latitude: 47.0,
+            longitude: 7.0,
+        };
+        let instructions = vec![
+            Maneuver::new(ManeuverType::Turn, 100.0, 10.0, location),
+            Maneuver::new(ManeuverType::Turn, 200.0, 20.0, location),
+        ];
+        let hairpin = warnings(&curve(15.0, 180.0, true)).remove(0);
+        let warnings: Vec<CurveWarning> = [50.0, 250.0, 400.0]
+            .iter()
+            .map(|distance| CurveWarning {
+                distance: *distance,
+                ..hairpin.clone()
+            })
+            .collect();
+
+        let merged = add_to_instructions(instructions, &warnings, 500.0, 50.0);
+
+        let types: Vec<ManeuverType> = merged
+            .iter()
+            .map(|maneuver| maneuver.maneuver_type)
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ManeuverType::CurveWarning,
+                ManeuverType::Turn,
+                ManeuverType::CurveWarning,
+                ManeuverType::Turn,
+                ManeuverType::CurveWarning,
+            ]
+        );
+
+        let distances: Vec<f64> = merged.iter().map(|maneuver| maneuver.distance).collect();
+        assert_eq!(distances, vec![50.0, 50.0, 150.0, 50.0, 100.0]);
+
+        let durations: Vec<f64> = merged.iter().map(|maneuver| maneuver.duration).collect();
+        assert_eq!(durations, vec![5.0, 5.0, 15.0, 5.0, 10.0]);
+
+        assert_eq!(merged[0].modifier, Some(ManeuverModifier::Right));
+        assert_eq!(merged[0].severity, Some(CurveSeverity::Hairpin));
+    }
+}
@@ -0,0 +1,93 @@
+use crate::elevation_profile::ElevationProfile;
+use crate::maneuver::{Location, Maneuver};
+use crate::osm_graph::SnappedPoint;
+use serde::Serialize;
+
+/// Point of a request snapped onto the nearest road
+/// # Fields
+/// * `latitude` - The latitude of the point on the road
+/// * `longitude` - The longitude of the point on the road
+/// * `distance_to_road` - The distance between the requested point and the road in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct SnappedLocation {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub distance_to_road: f64,
+}
+
+/// SnappedLocation implementation
+impl SnappedLocation {
+    /// Create the location of a snapped point
+    /// # Arguments
+    /// * `point` - The snapped point
+    /// # Returns
+    /// * `SnappedLocation` - The location of the point
+    pub fn new(point: &SnappedPoint) -> Self {
+        SnappedLocation {
+            latitude: point.lat,
+            longitude: point.lon,
+            distance_to_road: point.distance_to_road_m,
+        }
+    }
+}
+
+/// Path and directions of a route between two points, built by
+/// `OSMGraph::directions_instructions_and_path`
+/// # Fields
+/// * `path` - The coordinates of the points of the route, in order
+/// * `instructions` - The maneuvers and the curve warnings of the route
+/// * `total_distance` - The length of the route in meters
+/// * `total_time` - The travel time of the route in seconds
+/// * `total_curvature` - The curvature of the route
+/// * `elevation` - The elevation profile of the route, None if the altitude of the nodes is unknown
+/// * `snapped_start` - The start point snapped onto the road, see `OSMGraph::add_snapped_points`
+/// * `snapped_end` - The end point snapped onto the road, see `OSMGraph::add_snapped_points`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Directions {
+    pub path: Vec<Location>,
+    pub instructions: Vec<Maneuver>,
+    pub total_distance: f64,
+    pub total_time: f64,
+    pub total_curvature: f64,
+    pub elevation: Option<ElevationProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapped_start: Option<SnappedLocation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapped_end: Option<SnappedLocation>,
+}
+
+/// Summary of a leg of a route through waypoints
+/// # Fields
+/// * `start` - The start point of the leg
+/// * `end` - The end point of the leg
+/// * `distance` - The length of the leg in meters
+/// * `time` - The travel time of the leg in seconds
+/// * `curvature` - The curvature of the leg
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct LegSummary {
+    pub start: Option<SnappedLocation>,
+    pub end: Option<SnappedLocation>,
+    pub distance: f64,
+    pub time: f64,
+    pub curvature: f64,
+}
+
+/// Route through waypoints, built by `OSMGraph::combine_legs`
+/// # Fields
+/// * `directions` - The path and directions of the whole route, serialized at the top level
+/// * `legs` - The summary of each leg
+/// * `order` - The order in which the waypoints are visited, if it has been optimized
+/// * `stretch` - The ratio between the cost of an alternative route and the cost of the best route
+/// * `overlap` - The share of the distance of an alternative route on the edges of the best route
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Route {
+    #[serde(flatten)]
+    pub directions: Directions,
+    pub legs: Vec<LegSummary>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub order: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stretch: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub overlap: Option<f64>,
+}
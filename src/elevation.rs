@@ -0,0 +1,366 @@
+use log::warn;
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Value of the samples without data in the SRTM tiles
+const VOID_VALUE: i16 = -32768;
+
+/// Extension of the SRTM tile files
+const HGT_EXTENSION: &str = "hgt";
+
+/// SRTM tile covering one degree of latitude and longitude
+/// The samples are big-endian 16-bit integers in meters, stored row by row from the north-west
+/// corner. The tiles have 1201 (3 arc seconds) or 3601 (1 arc second) samples per side, the edges
+/// being shared with the neighbouring tiles
+/// # Fields
+/// * `lat` - The latitude of the south-west corner of the tile
+/// * `lon` - The longitude of the south-west corner of the tile
+/// * `size` - The number of samples per side
+/// * `samples` - The altitudes of the samples, in meters
+#[derive(Debug, Clone)]
+pub struct HgtTile {
+    pub lat: i32,
+    pub lon: i32,
+    pub size: usize,
+    pub samples: Vec<i16>,
+}
+
+/// HgtTile implementation
+impl HgtTile {
+    /// Parse the data of a tile
+    /// # Arguments
+    /// * `lat` - The latitude of the south-west corner of the tile
+    /// * `lon` - The longitude of the south-west corner of the tile
+    /// * `bytes` - The content of the tile file
+    /// # Returns
+    /// * `Option<HgtTile>` - The tile, None if the data is not a square grid of samples
+    pub fn from_bytes(lat: i32, lon: i32, bytes: &[u8]) -> Option<HgtTile> {
+        let count = bytes.len() / 2;
+        let size = (count as f64).sqrt().round() as usize;
+
+        if size < 2 || size * size * 2 != bytes.len() {
+            return None;
+        }
+
+        let samples = bytes
+            .chunks_exact(2)
+            .map(|sample| i16::from_be_bytes([sample[0], sample[1]]))
+            .collect();
+
+        Some(HgtTile {
+            lat,
+            lon,
+            size,
+            samples,
+        })
+    }
+
+    /// Read a tile file, named after its south-west corner such as `N46E007.hgt`
+    /// # Arguments
+    /// * `file_path` - The path of the tile file
+    /// # Returns
+    /// * `io::Result<HgtTile>` - The tile, or an error if the file can't be read or is invalid
+    pub fn from_file(file_path: &Path) -> io::Result<HgtTile> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message);
+
+        let (lat, lon) = file_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(tile_corner)
+            .ok_or_else(|| invalid("invalid tile name"))?;
+
+        let bytes = fs::read(file_path)?;
+
+        HgtTile::from_bytes(lat, lon, &bytes).ok_or_else(|| invalid("invalid tile size"))
+    }
+
+    /// Get the altitude of a point by bilinear interpolation of the four samples around it
+    /// The samples without data are ignored and the weights of the others are scaled accordingly
+    /// # Arguments
+    /// * `lat` - The latitude of the point
+    /// * `lon` - The longitude of the point
+    /// # Returns
+    /// * `Option<f64>` - The altitude in meters, None if the point is outside of the tile or if
+    ///   the samples around it have no data
+    pub fn elevation(&self, lat: f64, lon: f64) -> Option<f64> {
+        let cells = (self.size - 1) as f64;
+        let x = (lon - self.lon as f64) * cells;
+        let y = (self.lat as f64 + 1.0 - lat) * cells;
+
+        if !(0.0..=cells).contains(&x) || !(0.0..=cells).contains(&y) {
+            return None;
+        }
+
+        // the last row and column are handled as the far side of the previous cell
+        let column = (x.floor() as usize).min(self.size - 2);
+        let row = (y.floor() as usize).min(self.size - 2);
+        let dx = x - column as f64;
+        let dy = y - row as f64;
+
+        let corners = [
+            (row, column, (1.0 - dx) * (1.0 - dy)),
+            (row, column + 1, dx * (1.0 - dy)),
+            (row + 1, column, (1.0 - dx) * dy),
+            (row + 1, column + 1, dx * dy),
+        ];
+
+        let (sum, weights) = corners
+            .iter()
+            .map(|(row, column, weight)| (self.samples[row * self.size + column], *weight))
+            .filter(|(sample, _)| *sample != VOID_VALUE)
+            .fold((0.0, 0.0), |(sum, weights), (sample, weight)| {
+                (sum + sample as f64 * weight, weights + weight)
+            });
+
+        if weights > 0.0 {
+            Some(sum / weights)
+        } else {
+            None
+        }
+    }
+}
+
+/// Digital elevation model made of SRTM tiles
+/// # Fields
+/// * `tiles` - The tiles by latitude and longitude of their south-west corner
+#[derive(Debug, Clone, Default)]
+pub struct ElevationModel {
+    pub tiles: HashMap<(i32, i32), HgtTile>,
+}
+
+/// ElevationModel implementation
+impl ElevationModel {
+    /// Load the tiles of a directory covering some points
+    /// Only the tiles containing at least one of the points are read. The files that can't be read
+    /// are skipped with a warning
+    /// # Arguments
+    /// * `dir` - The directory of the `.hgt` files
+    /// * `points` - The latitude and longitude of the points
+    /// # Returns
+    /// * `ElevationModel` - The model with the tiles found in the directory
+    pub fn load(dir: &Path, points: impl IntoIterator<Item = (f64, f64)>) -> ElevationModel {
+        let needed: HashSet<(i32, i32)> = points
+            .into_iter()
+            .map(|(lat, lon)| (lat.floor() as i32, lon.floor() as i32))
+            .collect();
+
+        let mut tiles = HashMap::new();
+
+        for (corner, file_path) in ElevationModel::tile_files(dir) {
+            if !needed.contains(&corner) {
+                continue;
+            }
+
+            match HgtTile::from_file(&file_path) {
+                Ok(tile) => {
+                    tiles.insert(corner, tile);
+                }
+                Err(error) => warn!("Failed to read elevation tile {:?}: {}", file_path, error),
+            }
+        }
+
+        ElevationModel { tiles }
+    }
+
+    /// List the tile files of a directory
+    /// # Arguments
+    /// * `dir` - The directory of the `.hgt` files
+    /// # Returns
+    /// * `Vec<((i32, i32), PathBuf)>` - The south-west corner and the path of each tile, sorted by name
+    pub fn tile_files(dir: &Path) -> Vec<((i32, i32), PathBuf)> {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(error) => {
+                warn!("Unable to read elevation directory {:?}: {}", dir, error);
+                return Vec::new();
+            }
+        };
+
+        let mut files: Vec<((i32, i32), PathBuf)> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.extension()
+                    .and_then(|extension| extension.to_str())
+                    .is_some_and(|extension| extension.eq_ignore_ascii_case(HGT_EXTENSION))
+            })
+            .filter_map(|path| {
+                let corner = tile_corner(path.file_name()?.to_str()?)?;
+                Some((corner, path))
+            })
+            .collect();
+
+        files.sort_by(|a, b| a.1.cmp(&b.1));
+        files
+    }
+
+    /// Get the altitude of a point
+    /// # Arguments
+    /// * `lat` - The latitude of the point
+    /// * `lon` - The longitude of the point
+    /// # Returns
+    /// * `Option<f64>` - The altitude in meters, None if no tile covers the point
+    pub fn elevation(&self, lat: f64, lon: f64) -> Option<f64> {
+        self.tiles
+            .get(&(lat.floor() as i32, lon.floor() as i32))
+            .and_then(|tile| tile.elevation(lat, lon))
+    }
+}
+
+/// Get the south-west corner of a tile from its file name, such as `N46E007.hgt` or `S12W077.hgt`
+/// # Arguments
+/// * `name` - The file name of the tile
+/// # Returns
+/// * `Option<(i32, i32)>` - The latitude and longitude of the corner, None if the name is invalid
+fn tile_corner(name: &str) -> Option<(i32, i32)> {
+    let name = name
+        .get(..7)
+        .filter(|name| name.is_ascii())?
+        .to_ascii_uppercase();
+
+    let lat_sign = match &name[..1] {
+        "N" => 1,
+        "S" => -1,
+        _ => return None,
+    };
+    let lon_sign = match &name[3..4] {
+        "E" => 1,
+        "W" => -1,
+        _ => return None,
+    };
+
+    let lat: i32 = name[1..3].parse().ok()?;
+    let lon: i32 = name[4..7].parse().ok()?;
+
+    Some((lat_sign * lat, lon_sign * lon))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a 3 x 3 tile with its south-west corner at N46E007, stored row by row from the north
+    /// ```text
+    /// 47.0  100 200 300
+    /// 46.5  400 500 600
+    /// 46.0  700 800 900
+    ///       7.0 7.5 8.0
+    /// ```
+    /// # Arguments
+    /// * `void` - The indexes of the samples without data
+    /// # Returns
+    /// * `HgtTile` - The tile
+    fn tile(void: &[usize]) -> HgtTile {
+        let bytes: Vec<u8> = (0..9)
+            .map(|index| {
+                if void.contains(&index) {
+                    VOID_VALUE
+                } else {
+                    (index as i16 + 1) * 100
+                }
+            })
+            .flat_map(i16::to_be_bytes)
+            .collect();
+
+        HgtTile::from_bytes(46, 7, &bytes).unwrap()
+    }
+
+    /// Check that two altitudes are equal
+    /// # Arguments
+    /// * `elevation` - The altitude found
+    /// * `expected` - The altitude expected
+    fn assert_elevation(elevation: Option<f64>, expected: f64) {
+        let elevation = elevation.unwrap();
+
+        assert!((elevation - expected).abs() < 1e-9, "{}", elevation);
+    }
+
+    #[test]
+    fn big_endian_samples() {
+        let tile = tile(&[]);
+
+        assert_eq!(tile.size, 3);
+        assert_eq!(
+            tile.samples,
+            vec![100, 200, 300, 400, 500, 600, 700, 800, 900]
+        );
+
+        let negative = HgtTile::from_bytes(46, 7, &[0xff, 0xfe, 0, 1, 0, 2, 0, 3]).unwrap();
+        assert_eq!(negative.samples, vec![-2, 1, 2, 3]);
+    }
+
+    #[test]
+    fn invalid_sizes_are_rejected() {
+        assert!(HgtTile::from_bytes(46, 7, &[0, 1]).is_none());
+        assert!(HgtTile::from_bytes(46, 7, &[0; 7]).is_none());
+        assert!(HgtTile::from_bytes(46, 7, &[0; 10]).is_none());
+    }
+
+    #[test]
+    fn bilinear_interpolation() {
+        let tile = tile(&[]);
+
+        assert_elevation(tile.elevation(46.75, 7.25), 300.0);
+        assert_elevation(tile.elevation(46.5, 7.5), 500.0);
+        assert_elevation(tile.elevation(46.625, 7.5), 425.0);
+        assert_elevation(tile.elevation(46.5, 7.125), 425.0);
+    }
+
+    #[test]
+    fn points_on_the_edges() {
+        let tile = tile(&[]);
+
+        assert_elevation(tile.elevation(47.0, 7.0), 100.0);
+        assert_elevation(tile.elevation(47.0, 8.0), 300.0);
+        assert_elevation(tile.elevation(46.0, 7.0), 700.0);
+        assert_elevation(tile.elevation(46.0, 8.0), 900.0);
+        assert_elevation(tile.elevation(47.0, 7.75), 250.0);
+        assert_elevation(tile.elevation(46.0, 7.5), 800.0);
+        assert_elevation(tile.elevation(46.25, 8.0), 750.0);
+        assert_elevation(tile.elevation(46.75, 7.0), 250.0);
+
+        assert!(tile.elevation(45.99, 7.5).is_none());
+        assert!(tile.elevation(47.01, 7.5).is_none());
+        assert!(tile.elevation(46.5, 6.99).is_none());
+        assert!(tile.elevation(46.5, 8.01).is_none());
+    }
+
+    #[test]
+    fn void_samples_are_skipped() {
+        let tile = tile(&[0]);
+
+        // the three other samples around the point keep equal weights
+        assert_elevation(tile.elevation(46.75, 7.25), (200.0 + 400.0 + 500.0) / 3.0);
+        assert_elevation(tile.elevation(46.5, 7.5), 500.0);
+
+        // only the void sample has a weight on its own position
+        assert!(tile.elevation(47.0, 7.0).is_none());
+
+        let empty = HgtTile::from_bytes(46, 7, &[0x80, 0, 0x80, 0, 0x80, 0, 0x80, 0]).unwrap();
+        assert!(empty.elevation(46.5, 7.5).is_none());
+    }
+
+    #[test]
+    fn model_uses_the_tile_of_the_point() {
+        let model = ElevationModel {
+            tiles: HashMap::from([((46, 7), tile(&[]))]),
+        };
+
+        assert_elevation(model.elevation(46.75, 7.25), 300.0);
+        assert!(model.elevation(45.5, 7.25).is_none());
+        assert!(model.elevation(46.5, 8.5).is_none());
+    }
+
+    #[test]
+    fn tile_names() {
+        assert_eq!(tile_corner("N46E007.hgt"), Some((46, 7)));
+        assert_eq!(tile_corner("S12W077.hgt"), Some((-12, -77)));
+        assert_eq!(tile_corner("N00W001.hgt"), Some((0, -1)));
+        assert_eq!(tile_corner("s33e151.HGT"), Some((-33, 151)));
+        assert_eq!(tile_corner("X46E007.hgt"), None);
+        assert_eq!(tile_corner("N46X007.hgt"), None);
+        assert_eq!(tile_corner("N46E07.hgt"), None);
+        assert_eq!(tile_corner("N46"), None);
+    }
+}
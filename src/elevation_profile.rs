@@ -0,0 +1,113 @@
+use serde::{Deserialize, Serialize};
+
+/// Smallest distance in meters over which a gradient is measured
+/// The altitude models have a resolution of 30 to 90 meters, the gradients measured on shorter
+/// distances are mostly noise
+const GRADIENT_MIN_DISTANCE_M: f64 = 100.0;
+
+/// Elevation profile of a route and its climb statistics
+/// # Fields
+/// * `profile` - The distance from the start of the route in meters and the altitude in meters of
+///   each point of the route whose altitude is known
+/// * `ascent` - The total ascent in meters
+/// * `descent` - The total descent in meters
+/// * `max_altitude` - The highest altitude in meters
+/// * `min_altitude` - The lowest altitude in meters
+/// * `max_gradient` - The steepest gradient in percent, positive uphill and negative downhill
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ElevationProfile {
+    pub profile: Vec<(f64, f64)>,
+    pub ascent: f64,
+    pub descent: f64,
+    pub max_altitude: f64,
+    pub min_altitude: f64,
+    pub max_gradient: f64,
+}
+
+/// ElevationProfile implementation
+impl ElevationProfile {
+    /// Compute the statistics of an elevation profile
+    /// # Arguments
+    /// * `profile` - The distance from the start and the altitude of the points, in meters and in order
+    /// # Returns
+    /// * `Option<ElevationProfile>` - The profile and its statistics, None if the profile is empty
+    pub fn new(profile: Vec<(f64, f64)>) -> Option<ElevationProfile> {
+        if profile.is_empty() {
+            return None;
+        }
+
+        let mut ascent = 0.0;
+        let mut descent = 0.0;
+
+        for pair in profile.windows(2) {
+            let climb = pair[1].1 - pair[0].1;
+
+            if climb > 0.0 {
+                ascent += climb;
+            } else {
+                descent -= climb;
+            }
+        }
+
+        let max_altitude = profile
+            .iter()
+            .map(|(_, altitude)| *altitude)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let min_altitude = profile
+            .iter()
+            .map(|(_, altitude)| *altitude)
+            .fold(f64::INFINITY, f64::min);
+
+        let max_gradient = ElevationProfile::steepest_gradient(&profile);
+
+        Some(ElevationProfile {
+            profile,
+            ascent,
+            descent,
+            max_altitude,
+            min_altitude,
+            max_gradient,
+        })
+    }
+
+    /// Get the steepest gradient of a profile
+    /// Each gradient is measured from a point to the first point at least `GRADIENT_MIN_DISTANCE_M`
+    /// further, or over the whole profile if it is shorter
+    /// # Arguments
+    /// * `profile` - The distance from the start and the altitude of the points
+    /// # Returns
+    /// * `f64` - The gradient with the largest absolute value in percent, 0 if the profile has no length
+    fn steepest_gradient(profile: &[(f64, f64)]) -> f64 {
+        let gradient = |from: (f64, f64), to: (f64, f64)| {
+            let distance = to.0 - from.0;
+
+            if distance > 0.0 {
+                (to.1 - from.1) / distance * 100.0
+            } else {
+                0.0
+            }
+        };
+
+        let mut steepest: Option<f64> = None;
+        let mut end = 0;
+
+        for start in 0..profile.len() {
+            while end < profile.len() && profile[end].0 - profile[start].0 < GRADIENT_MIN_DISTANCE_M
+            {
+                end += 1;
+            }
+
+            if end == profile.len() {
+                break;
+            }
+
+            let value = gradient(profile[start], profile[end]);
+
+            if steepest.is_none_or(|steepest| value.abs() > steepest.abs()) {
+                steepest = Some(value);
+            }
+        }
+
+        steepest.unwrap_or_else(|| gradient(profile[0], profile[profile.len() - 1]))
+    }
+}
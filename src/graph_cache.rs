@@ -0,0 +1,163 @@
+use crate::osm_graph::OSMGraph;
+use log::{info, warn};
+use memmap2::Mmap;
+use serde::{Deserialize, Serialize};
+use sha1::{Digest, Sha1};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Version of the graph cache format, to increment when the serialized structures or the way
+/// the graph is built change
+pub const GRAPH_CACHE_VERSION: u32 = 12;
+
+/// Magic bytes at the beginning of a graph cache file
+const GRAPH_CACHE_MAGIC: [u8; 8] = *b"MTRGRAPH";
+
+/// Header of a graph cache file, written before the graph
+/// # Fields
+/// * `magic` - The magic bytes of the format
+/// * `version` - The version of the format
+/// * `pbf_hash` - The SHA-1 hash of the PBF file the graph was built from
+/// * `settings` - The build settings of the graph
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GraphCacheHeader {
+    pub magic: [u8; 8],
+    pub version: u32,
+    pub pbf_hash: String,
+    pub settings: String,
+}
+
+/// GraphCacheHeader implementation
+impl GraphCacheHeader {
+    /// Create the header of the current version
+    /// # Arguments
+    /// * `pbf_hash` - The SHA-1 hash of the PBF file
+    /// * `settings` - The build settings of the graph
+    pub fn new(pbf_hash: &str, settings: &str) -> Self {
+        GraphCacheHeader {
+            magic: GRAPH_CACHE_MAGIC,
+            version: GRAPH_CACHE_VERSION,
+            pbf_hash: pbf_hash.to_string(),
+            settings: settings.to_string(),
+        }
+    }
+}
+
+/// Compute the SHA-1 hash of a file
+/// # Arguments
+/// * `file_path` - The path of the file
+/// # Returns
+/// * `std::io::Result<String>` - The hash in hexadecimal
+pub fn file_hash(file_path: &Path) -> std::io::Result<String> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+    let mut hasher = Sha1::new();
+    let mut buffer = vec![0; 1 << 20];
+
+    loop {
+        let read = reader.read(&mut buffer)?;
+
+        if read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect())
+}
+
+/// Get the path of the cache file of a graph
+/// The settings are hashed so that each combination gets its own file
+/// # Arguments
+/// * `cache_dir` - The directory of the cache files
+/// * `pbf_hash` - The SHA-1 hash of the PBF file
+/// * `settings` - The build settings of the graph
+/// # Returns
+/// * `PathBuf` - The path of the cache file
+pub fn cache_file_path(cache_dir: &Path, pbf_hash: &str, settings: &str) -> PathBuf {
+    let settings_hash: String = Sha1::digest(settings.as_bytes())
+        .iter()
+        .take(4)
+        .map(|byte| format!("{:02x}", byte))
+        .collect();
+
+    cache_dir.join(format!(
+        "graph-v{}-{}-{}.bin",
+        GRAPH_CACHE_VERSION, pbf_hash, settings_hash
+    ))
+}
+
+/// Load a graph from a cache file
+/// The file is memory mapped and deserialized directly from the mapping
+/// # Arguments
+/// * `file_path` - The path of the cache file
+/// * `header` - The expected header of the file
+/// # Returns
+/// * `Option<OSMGraph>` - The graph if the file exists and matches the header, None otherwise
+pub fn load_graph(file_path: &Path, header: &GraphCacheHeader) -> Option<OSMGraph> {
+    let file = File::open(file_path).ok()?;
+
+    // Safety: the cache files are only written by `save_graph`, which writes to a temporary file
+    // and renames it, so a mapped file is never modified
+    let mmap = unsafe { Mmap::map(&file) }.ok()?;
+    let mut bytes: &[u8] = &mmap;
+
+    match bincode::deserialize_from::<_, GraphCacheHeader>(&mut bytes) {
+        Ok(file_header) if file_header == *header => {}
+        Ok(_) => {
+            warn!("Graph cache {:?} doesn't match the PBF file", file_path);
+            return None;
+        }
+        Err(error) => {
+            warn!("Invalid graph cache {:?}: {}", file_path, error);
+            return None;
+        }
+    }
+
+    match bincode::deserialize_from::<_, OSMGraph>(&mut bytes) {
+        Ok(graph) => Some(graph),
+        Err(error) => {
+            warn!("Invalid graph cache {:?}: {}", file_path, error);
+            None
+        }
+    }
+}
+
+/// Save a graph to a cache file
+/// # Arguments
+/// * `file_path` - The path of the cache file
+/// * `header` - The header of the file
+/// * `graph` - The graph to save
+/// # Returns
+/// * `Result<(), String>` - An error message if the graph couldn't be saved
+pub fn save_graph(
+    file_path: &Path,
+    header: &GraphCacheHeader,
+    graph: &OSMGraph,
+) -> Result<(), String> {
+    if let Some(cache_dir) = file_path.parent() {
+        fs::create_dir_all(cache_dir).map_err(|error| error.to_string())?;
+    }
+
+    let temp_path = file_path.with_extension("tmp");
+
+    {
+        let file = File::create(&temp_path).map_err(|error| error.to_string())?;
+        let mut writer = BufWriter::new(file);
+
+        bincode::serialize_into(&mut writer, header).map_err(|error| error.to_string())?;
+        bincode::serialize_into(&mut writer, graph).map_err(|error| error.to_string())?;
+        writer.flush().map_err(|error| error.to_string())?;
+    }
+
+    fs::rename(&temp_path, file_path).map_err(|error| error.to_string())?;
+
+    info!("Graph saved to {:?}", file_path);
+
+    Ok(())
+}
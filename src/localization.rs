@@ -0,0 +1,668 @@
+use crate::curve_warnings::CurveSeverity;
+use crate::maneuver::{Maneuver, ManeuverModifier, ManeuverType};
+use serde::{Deserialize, Serialize};
+
+/// Language of the instructions, an unknown language falls back to English
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", from = "String")]
+pub enum Language {
+    #[default]
+    En,
+    Fr,
+    De,
+    It,
+}
+
+/// Read a language from its code, such as `fr`
+impl From<String> for Language {
+    fn from(code: String) -> Self {
+        match code.trim().to_lowercase().as_str() {
+            "fr" => Language::Fr,
+            "de" => Language::De,
+            "it" => Language::It,
+            _ => Language::En,
+        }
+    }
+}
+
+/// Templates of the instructions in a language
+/// The placeholders `{direction}`, `{road}`, `{exit}` and `{waypoint}` are replaced by the
+/// direction, the road name, the ordinal of the exit and the number of the via point
+/// # Fields
+/// * `turn_onto` - Turn onto a named road
+/// * `turn` - Turn onto a road without name
+/// * `turn_without_direction` - Turn in an unknown direction
+/// * `uturn_onto` - Make a U-turn onto a named road
+/// * `uturn` - Make a U-turn onto a road without name
+/// * `continue_on` - Continue on a named road
+/// * `continue_ahead` - Continue on a road without name
+/// * `roundabout` - Leave a roundabout
+/// * `waypoint` - Arrive at a via point
+/// * `arrive` - Arrive at the destination
+/// * `curve` - Warning of a moderate curve
+/// * `sharp_curve` - Warning of a sharp curve
+/// * `hairpin` - Warning of a hairpin
+/// * `tightening` - Added to the warning of a curve that tightens
+/// * `slight_left` - The slight left direction
+/// * `left` - The left direction
+/// * `sharp_left` - The sharp left direction
+/// * `slight_right` - The slight right direction
+/// * `right` - The right direction
+/// * `sharp_right` - The sharp right direction
+struct Catalog {
+    turn_onto: &'static str,
+    turn: &'static str,
+    turn_without_direction: &'static str,
+    uturn_onto: &'static str,
+    uturn: &'static str,
+    continue_on: &'static str,
+    continue_ahead: &'static str,
+    roundabout: &'static str,
+    waypoint: &'static str,
+    arrive: &'static str,
+    curve: &'static str,
+    sharp_curve: &'static str,
+    hairpin: &'static str,
+    tightening: &'static str,
+    slight_left: &'static str,
+    left: &'static str,
+    sharp_left: &'static str,
+    slight_right: &'static str,
+    right: &'static str,
+    sharp_right: &'static str,
+}
+
+/// English templates
+const EN: Catalog = Catalog {
+    turn_onto: "Turn {direction} to road {road}",
+    turn: "Turn {direction}",
+    turn_without_direction: "Turn",
+    uturn_onto: "Make a U-turn onto {road}",
+    uturn: "Make a U-turn",
+    continue_on: "Continue on road {road}",
+    continue_ahead: "Continue",
+    roundabout: "At the roundabout, take the {exit} exit",
+    waypoint: "Arrive at via point {waypoint}",
+    arrive: "Arrive at destination",
+    curve: "Curve {direction} ahead",
+    sharp_curve: "Sharp curve {direction} ahead",
+    hairpin: "Hairpin {direction} ahead",
+    tightening: ", tightening",
+    slight_left: "slightly left",
+    left: "left",
+    sharp_left: "sharp left",
+    slight_right: "slightly right",
+    right: "right",
+    sharp_right: "sharp right",
+};
+
+/// French templates
+const FR: Catalog = Catalog {
+    turn_onto: "Tournez {direction} sur {road}",
+    turn: "Tournez {direction}",
+    turn_without_direction: "Tournez",
+    uturn_onto: "Faites demi-tour sur {road}",
+    uturn: "Faites demi-tour",
+    continue_on: "Continuez sur {road}",
+    continue_ahead: "Continuez",
+    roundabout: "Au rond-point, prenez la {exit} sortie",
+    waypoint: "Vous êtes arrivé à l'étape {waypoint}",
+    arrive: "Vous êtes arrivé à destination",
+    curve: "Virage {direction}",
+    sharp_curve: "Virage serré {direction}",
+    hairpin: "Épingle {direction}",
+    tightening: " qui se resserre",
+    slight_left: "légèrement à gauche",
+    left: "à gauche",
+    sharp_left: "franchement à gauche",
+    slight_right: "légèrement à droite",
+    right: "à droite",
+    sharp_right: "franchement à droite",
+};
+
+/// German templates
+const DE: Catalog = Catalog {
+    turn_onto: "Biegen Sie {direction} auf {road} ab",
+    turn: "Biegen Sie {direction} ab",
+    turn_without_direction: "Biegen Sie ab",
+    uturn_onto: "Wenden Sie und fahren Sie auf {road}",
+    uturn: "Wenden Sie",
+    continue_on: "Fahren Sie weiter auf {road}",
+    continue_ahead: "Fahren Sie weiter",
+    roundabout: "Nehmen Sie im Kreisverkehr die {exit} Ausfahrt",
+    waypoint: "Sie haben den Zwischenstopp {waypoint} erreicht",
+    arrive: "Sie haben Ihr Ziel erreicht",
+    curve: "Kurve {direction}",
+    sharp_curve: "Scharfe Kurve {direction}",
+    hairpin: "Haarnadelkurve {direction}",
+    tightening: ", die enger wird",
+    slight_left: "leicht links",
+    left: "links",
+    sharp_left: "scharf links",
+    slight_right: "leicht rechts",
+    right: "rechts",
+    sharp_right: "scharf rechts",
+};
+
+/// Italian templates
+const IT: Catalog = Catalog {
+    turn_onto: "Svolta {direction} in {road}",
+    turn: "Svolta {direction}",
+    turn_without_direction: "Svolta",
+    uturn_onto: "Fai inversione a U in {road}",
+    uturn: "Fai inversione a U",
+    continue_on: "Prosegui su {road}",
+    continue_ahead: "Prosegui",
+    roundabout: "Alla rotonda, prendi la {exit} uscita",
+    waypoint: "Sei arrivato alla tappa {waypoint}",
+    arrive: "Sei arrivato a destinazione",
+    curve: "Curva {direction}",
+    sharp_curve: "Curva stretta {direction}",
+    hairpin: "Tornante {direction}",
+    tightening: " che si stringe",
+    slight_left: "leggermente a sinistra",
+    left: "a sinistra",
+    sharp_left: "nettamente a sinistra",
+    slight_right: "leggermente a destra",
+    right: "a destra",
+    sharp_right: "nettamente a destra",
+};
+
+/// French road types taking the feminine article, as the first word of the road name
+const FR_FEMININE_ROADS: [&str; 8] = [
+    "rue",
+    "route",
+    "avenue",
+    "place",
+    "allée",
+    "impasse",
+    "ruelle",
+    "promenade",
+];
+
+/// French road types taking the masculine article, as the first word of the road name
+const FR_MASCULINE_ROADS: [&str; 8] = [
+    "chemin",
+    "boulevard",
+    "quai",
+    "passage",
+    "sentier",
+    "pont",
+    "col",
+    "tunnel",
+];
+
+/// German road types of feminine gender, as the end of the road name
+const DE_FEMININE_ROADS: [&str; 6] = [
+    "strasse",
+    "straße",
+    "gasse",
+    "allee",
+    "chaussee",
+    "promenade",
+];
+
+/// German road types of masculine gender, as the end of the road name
+const DE_MASCULINE_ROADS: [&str; 8] = [
+    "weg", "platz", "ring", "damm", "pfad", "steig", "graben", "pass",
+];
+
+/// Grammatical case of a road name in an instruction
+/// # Variants
+/// * `Destination` - The road is entered, such as "turn onto"
+/// * `Location` - The road is followed, such as "continue on"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RoadCase {
+    Destination,
+    Location,
+}
+
+/// Language implementation
+impl Language {
+    /// Get the templates of the language
+    /// # Returns
+    /// * `&Catalog` - The templates
+    fn catalog(self) -> &'static Catalog {
+        match self {
+            Language::En => &EN,
+            Language::Fr => &FR,
+            Language::De => &DE,
+            Language::It => &IT,
+        }
+    }
+
+    /// Write the ordinal of a roundabout exit, which is feminine in French and Italian
+    /// # Arguments
+    /// * `number` - The number of the exit, from 1
+    /// # Returns
+    /// * `String` - The ordinal, such as `2nd`, `2e`, `2.` or `2ª`
+    fn exit_ordinal(self, number: usize) -> String {
+        match self {
+            Language::En => {
+                let suffix = match (number % 10, number % 100) {
+                    (_, 11..=13) => "th",
+                    (1, _) => "st",
+                    (2, _) => "nd",
+                    (3, _) => "rd",
+                    _ => "th",
+                };
+
+                format!("{}{}", number, suffix)
+            }
+            Language::Fr if number == 1 => String::from("1re"),
+            Language::Fr => format!("{}e", number),
+            Language::De => format!("{}.", number),
+            Language::It => format!("{}ª", number),
+        }
+    }
+
+    /// Write a road name with the article it takes in an instruction
+    /// French names get the article of their first word, such as `la Rue de Lausanne`. German
+    /// names get the article of their gender in the accusative or dative case, such as
+    /// `die Bernstrasse` or `dem Grimselpass`. The other names are left unchanged
+    /// # Arguments
+    /// * `name` - The name of the road
+    /// * `case` - The grammatical case of the road in the instruction
+    /// # Returns
+    /// * `String` - The road name with its article
+    fn road(self, name: &str, case: RoadCase) -> String {
+        match self {
+            Language::Fr => {
+                let first_word = name.split([' ', '\'']).next().unwrap_or("").to_lowercase();
+
+                let article = if FR_FEMININE_ROADS.contains(&first_word.as_str()) {
+                    "la "
+                } else if FR_MASCULINE_ROADS.contains(&first_word.as_str()) {
+                    "le "
+                } else {
+                    return name.to_string();
+                };
+
+                if first_word.starts_with(['a', 'e', 'i', 'o', 'u', 'é']) {
+                    format!("l'{}", name)
+                } else {
+                    format!("{}{}", article, name)
+                }
+            }
+            Language::De => {
+                let last_word = name.split(' ').next_back().unwrap_or("").to_lowercase();
+
+                let feminine = DE_FEMININE_ROADS
+                    .iter()
+                    .any(|road| last_word.ends_with(road));
+                let masculine = DE_MASCULINE_ROADS
+                    .iter()
+                    .any(|road| last_word.ends_with(road));
+
+                let article = match (feminine, masculine, case) {
+                    (true, _, RoadCase::Destination) => "die",
+                    (true, _, RoadCase::Location) => "der",
+                    (_, true, RoadCase::Destination) => "den",
+                    (_, true, RoadCase::Location) => "dem",
+                    _ => return name.to_string(),
+                };
+
+                format!("{} {}", article, name)
+            }
+            Language::En | Language::It => name.to_string(),
+        }
+    }
+}
+
+/// Generate the text of the instruction of a maneuver
+/// The road is given by its name, or by its reference if it has no name
+/// # Arguments
+/// * `maneuver` - The maneuver
+/// * `language` - The language of the text
+/// # Returns
+/// * `String` - The instruction
+pub fn instruction_text(maneuver: &Maneuver, language: Language) -> String {
+    let catalog = language.catalog();
+
+    let direction = maneuver.modifier.and_then(|modifier| match modifier {
+        ManeuverModifier::SlightLeft => Some(catalog.slight_left),
+        ManeuverModifier::Left => Some(catalog.left),
+        ManeuverModifier::SharpLeft => Some(catalog.sharp_left),
+        ManeuverModifier::SlightRight => Some(catalog.slight_right),
+        ManeuverModifier::Right => Some(catalog.right),
+        ManeuverModifier::SharpRight => Some(catalog.sharp_right),
+        ManeuverModifier::Straight | ManeuverModifier::UTurn => None,
+    });
+    let road = maneuver.name.as_ref().or(maneuver.road_ref.as_ref());
+
+    // a turn straight on is worded as a continuation
+    let continuation = maneuver.maneuver_type == ManeuverType::Continue
+        || maneuver.modifier == Some(ManeuverModifier::Straight);
+
+    let template = match maneuver.maneuver_type {
+        _ if continuation && road.is_some() => catalog.continue_on,
+        _ if continuation => catalog.continue_ahead,
+        ManeuverType::Turn => match (maneuver.modifier, road) {
+            (Some(ManeuverModifier::UTurn), Some(_)) => catalog.uturn_onto,
+            (Some(ManeuverModifier::UTurn), None) => catalog.uturn,
+            (Some(_), Some(_)) => catalog.turn_onto,
+            (Some(_), None) => catalog.turn,
+            (None, _) => catalog.turn_without_direction,
+        },
+        ManeuverType::Continue => catalog.continue_ahead,
+        ManeuverType::Roundabout => catalog.roundabout,
+        ManeuverType::Waypoint => catalog.waypoint,
+        ManeuverType::Arrive => catalog.arrive,
+        ManeuverType::CurveWarning => match maneuver.severity {
+            Some(CurveSeverity::Hairpin) => catalog.hairpin,
+            Some(CurveSeverity::Sharp) => catalog.sharp_curve,
+            Some(CurveSeverity::Moderate) | None => catalog.curve,
+        },
+    };
+
+    let case = if continuation {
+        RoadCase::Location
+    } else {
+        RoadCase::Destination
+    };
+
+    // the tightening of a curve is added at the end of its warning
+    let tightening = if maneuver.tightening == Some(true) {
+        catalog.tightening
+    } else {
+        ""
+    };
+
+    [template, tightening]
+        .concat()
+        .replace("{direction}", direction.unwrap_or(""))
+        .replace(
+            "{road}",
+            &road.map_or(String::new(), |road| language.road(road, case)),
+        )
+        .replace("{exit}", &language.exit_ordinal(maneuver.exit.unwrap_or(1)))
+        .replace("{waypoint}", &maneuver.waypoint.unwrap_or(1).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::maneuver::Location;
+
+    /// Create a maneuver at (47.0, 7.0)
+    /// # Arguments
+    /// * `maneuver_type` - The type of the maneuver
+    /// * `modifier` - The direction of the maneuver
+    /// * `name` - The name of the road taken by the maneuver
+    /// # Returns
+    /// * `Maneuver` - The maneuver
+    fn maneuver(
+        maneuver_type: ManeuverType,
+        modifier: Option<ManeuverModifier>,
+        name: Option<&str>,
+    ) -> Maneuver {
+        let mut maneuver = Maneuver::new(
+            maneuver_type,
+            0.0,
+            0.0,
+            Location {
+                latitude: 47.0,
+                longitude: 7.0,
+            },
+        )
+        .with_modifier(modifier);
+        maneuver.name = name.map(String::from);
+
+        maneuver
+    }
+
+    /// Generate the texts of a maneuver in English, French, German and Italian
+    /// # Arguments
+    /// * `maneuver` - The maneuver
+    /// # Returns
+    /// * `[String; 4]` - The texts, in this order
+    fn texts(maneuver: &Maneuver) -> [String; 4] {
+        [Language::En, Language::Fr, Language::De, Language::It]
+            .map(|language| instruction_text(maneuver, language))
+    }
+
+    #[test]
+    fn catalogs() {
+        let turn = maneuver(
+            ManeuverType::Turn,
+            Some(ManeuverModifier::SharpLeft),
+            Some("Grand-Rue"),
+        );
+        assert_eq!(
+            texts(&turn),
+            [
+                "Turn sharp left to road Grand-Rue",
+                "Tournez franchement à gauche sur Grand-Rue",
+                "Biegen Sie scharf links auf Grand-Rue ab",
+                "Svolta nettamente a sinistra in Grand-Rue",
+            ]
+        );
+
+        let turn = maneuver(
+            ManeuverType::Turn,
+            Some(ManeuverModifier::SlightRight),
+            None,
+        );
+        assert_eq!(
+            texts(&turn),
+            [
+                "Turn slightly right",
+                "Tournez légèrement à droite",
+                "Biegen Sie leicht rechts ab",
+                "Svolta leggermente a destra",
+            ]
+        );
+
+        let uturn = maneuver(ManeuverType::Turn, Some(ManeuverModifier::UTurn), None);
+        assert_eq!(
+            texts(&uturn),
+            [
+                "Make a U-turn",
+                "Faites demi-tour",
+                "Wenden Sie",
+                "Fai inversione a U",
+            ]
+        );
+
+        // a turn straight on is worded as a continuation
+        let straight = maneuver(
+            ManeuverType::Turn,
+            Some(ManeuverModifier::Straight),
+            Some("Grand-Rue"),
+        );
+        assert_eq!(
+            texts(&straight),
+            [
+                "Continue on road Grand-Rue",
+                "Continuez sur Grand-Rue",
+                "Fahren Sie weiter auf Grand-Rue",
+                "Prosegui su Grand-Rue",
+            ]
+        );
+
+        let waypoint = maneuver(ManeuverType::Waypoint, None, None).with_waypoint(2);
+        assert_eq!(
+            texts(&waypoint),
+            [
+                "Arrive at via point 2",
+                "Vous êtes arrivé à l'étape 2",
+                "Sie haben den Zwischenstopp 2 erreicht",
+                "Sei arrivato alla tappa 2",
+            ]
+        );
+
+        let arrive = maneuver(ManeuverType::Arrive, None, None);
+        assert_eq!(
+            texts(&arrive),
+            [
+                "Arrive at destination",
+                "Vous êtes arrivé à destination",
+                "Sie haben Ihr Ziel erreicht",
+                "Sei arrivato a destinazione",
+            ]
+        );
+    }
+
+    #[test]
+    fn roads_without_name_are_given_by_their_reference() {
+        let mut turn = maneuver(ManeuverType::Turn, Some(ManeuverModifier::Right), None);
+        turn.road_ref = Some(String::from("A1"));
+
+        assert_eq!(
+            instruction_text(&turn, Language::En),
+            "Turn right to road A1"
+        );
+    }
+
+    #[test]
+    fn roundabout_ordinals() {
+        let ordinals = |language: Language, numbers: &[usize]| -> Vec<String> {
+            numbers
+                .iter()
+                .map(|number| language.exit_ordinal(*number))
+                .collect()
+        };
+
+        assert_eq!(
+            ordinals(
+                Language::En,
+                &[1, 2, 3, 4, 11, 12, 13, 21, 22, 23, 101, 111]
+            ),
+            [
+                "1st", "2nd", "3rd", "4th", "11th", "12th", "13th", "21st", "22nd", "23rd",
+                "101st", "111th"
+            ]
+        );
+        assert_eq!(ordinals(Language::Fr, &[1, 2, 11]), ["1re", "2e", "11e"]);
+        assert_eq!(ordinals(Language::De, &[1, 2, 11]), ["1.", "2.", "11."]);
+        assert_eq!(ordinals(Language::It, &[1, 2, 11]), ["1ª", "2ª", "11ª"]);
+
+        let roundabout = maneuver(ManeuverType::Roundabout, None, None).with_exit(1);
+        assert_eq!(
+            texts(&roundabout),
+            [
+                "At the roundabout, take the 1st exit",
+                "Au rond-point, prenez la 1re sortie",
+                "Nehmen Sie im Kreisverkehr die 1. Ausfahrt",
+                "Alla rotonda, prendi la 1ª uscita",
+            ]
+        );
+    }
+
+    #[test]
+    fn french_road_articles() {
+        let turn_onto = |name: &str| {
+            let turn = maneuver(ManeuverType::Turn, Some(ManeuverModifier::Left), Some(name));
+            instruction_text(&turn, Language::Fr)
+        };
+
+        assert_eq!(
+            turn_onto("Rue de Lausanne"),
+            "Tournez à gauche sur la Rue de Lausanne"
+        );
+        assert_eq!(
+            turn_onto("Chemin des Vignes"),
+            "Tournez à gauche sur le Chemin des Vignes"
+        );
+        assert_eq!(
+            turn_onto("Avenue de la Gare"),
+            "Tournez à gauche sur l'Avenue de la Gare"
+        );
+        assert_eq!(
+            turn_onto("Impasse du Lac"),
+            "Tournez à gauche sur l'Impasse du Lac"
+        );
+        assert_eq!(turn_onto("Grand-Rue"), "Tournez à gauche sur Grand-Rue");
+    }
+
+    #[test]
+    fn german_road_articles() {
+        let texts = |name: &str| {
+            let turn = maneuver(
+                ManeuverType::Turn,
+                Some(ManeuverModifier::Right),
+                Some(name),
+            );
+            let continuation = maneuver(ManeuverType::Continue, None, Some(name));
+
+            (
+                instruction_text(&turn, Language::De),
+                instruction_text(&continuation, Language::De),
+            )
+        };
+
+        assert_eq!(
+            texts("Bernstrasse"),
+            (
+                String::from("Biegen Sie rechts auf die Bernstrasse ab"),
+                String::from("Fahren Sie weiter auf der Bernstrasse")
+            )
+        );
+        assert_eq!(
+            texts("Grimselpass"),
+            (
+                String::from("Biegen Sie rechts auf den Grimselpass ab"),
+                String::from("Fahren Sie weiter auf dem Grimselpass")
+            )
+        );
+        assert_eq!(
+            texts("Seefeld"),
+            (
+                String::from("Biegen Sie rechts auf Seefeld ab"),
+                String::from("Fahren Sie weiter auf Seefeld")
+            )
+        );
+    }
+
+    #[test]
+    fn curve_warnings() {
+        let mut hairpin = maneuver(
+            ManeuverType::CurveWarning,
+            Some(ManeuverModifier::Left),
+            None,
+        );
+        hairpin.severity = Some(CurveSeverity::Hairpin);
+        hairpin.tightening = Some(false);
+        assert_eq!(
+            texts(&hairpin),
+            [
+                "Hairpin left ahead",
+                "Épingle à gauche",
+                "Haarnadelkurve links",
+                "Tornante a sinistra",
+            ]
+        );
+
+        let mut sharp = maneuver(
+            ManeuverType::CurveWarning,
+            Some(ManeuverModifier::Right),
+            None,
+        );
+        sharp.severity = Some(CurveSeverity::Sharp);
+        sharp.tightening = Some(true);
+        assert_eq!(
+            texts(&sharp),
+            [
+                "Sharp curve right ahead, tightening",
+                "Virage serré à droite qui se resserre",
+                "Scharfe Kurve rechts, die enger wird",
+                "Curva stretta a destra che si stringe",
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_language_falls_back_to_english() {
+        let language = |value: &str| serde_json::from_value::<Language>(value.into()).unwrap();
+
+        assert_eq!(language("fr"), Language::Fr);
+        assert_eq!(language("DE"), Language::De);
+        assert_eq!(language("it"), Language::It);
+        assert_eq!(language("en"), Language::En);
+        assert_eq!(language("es"), Language::En);
+        assert_eq!(language(""), Language::En);
+        assert_eq!(Language::default(), Language::En);
+
+        assert_eq!(serde_json::to_value(Language::Fr).unwrap(), "fr");
+    }
+}
@@ -1,17 +1,51 @@
 //mod graph;
+mod access;
+mod avoid;
+mod avoid_zone;
+mod conditions;
+mod contraction_hierarchies;
+mod curvature;
+mod curve_warnings;
+mod directions;
+mod elevation;
+mod elevation_profile;
+mod graph_cache;
+mod localization;
+mod maneuver;
+mod oneway;
 mod osm_graph;
 mod osm_reader;
+mod profile;
+mod road_filter;
 mod route_calculation;
+mod route_optimization;
+mod spatial_index;
+mod speed;
+#[cfg(test)]
+mod test_graph;
+mod turn_restrictions;
+mod turns;
 use actix_cors::Cors;
-use actix_web::{get, post, web, web::ServiceConfig, App, HttpResponse, HttpServer, Responder};
-use chrono::Utc;
+use actix_web::{
+    get, post, web, web::ServiceConfig, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use avoid::AvoidFeature;
+use avoid_zone::AvoidZone;
+use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use localization::Language;
 use log::{info, warn};
 use osm_graph::OSMGraph;
 use osm_reader::OSMReader;
 use osmpbfreader::objects::{Node, NodeId, Tags, Way, WayId};
+use profile::Profiles;
+use road_filter::RoadFilter;
+use route_calculation::bidirectional_dijkstra;
 use route_calculation::dijkstra;
 use route_calculation::generate_random_loop;
-use serde_json::{json, Map, Value};
+use route_calculation::{find_alternatives, find_path_through, Algorithm, RouteOptions};
+use route_optimization::{cost_matrix, optimize_order, order_cost, OrderConstraints};
+use serde_json::{Map, Value};
 use shuttle_actix_web::ShuttleActixWeb;
 use std::env;
 use std::fs::File;
@@ -21,6 +55,13 @@ use std::path::PathBuf;
 use std::sync::Arc;
 use tokio::io::AsyncReadExt;
 use tokio::io::AsyncWriteExt;
+use turns::TurnThresholds;
+
+/// Maximum number of waypoints of a route, including the start and the end
+const MAX_WAYPOINTS: usize = 25;
+
+/// Maximum number of alternative routes of a route
+const MAX_ALTERNATIVES: usize = 3;
 
 async fn start() {
     let current_dir = env::current_dir().expect("Failed to get current directory");
@@ -41,7 +82,7 @@ async fn start() {
     let start_node = {
         let graph = &graph; // Create a new scope to borrow graph immutably
         graph
-            .get_nearest_node(coords_poms_moi.0, coords_poms_moi.1)
+            .snap_to_edge(coords_poms_moi.0, coords_poms_moi.1)
             .unwrap()
     };
 
@@ -54,7 +95,7 @@ async fn start() {
     let end_node = {
         let graph = &graph; // Create a new scope to borrow graph immutably
         graph
-            .get_nearest_node(coords_neuchatel.0, coords_neuchatel.1)
+            .snap_to_edge(coords_neuchatel.0, coords_neuchatel.1)
             .unwrap()
     };
 
@@ -101,16 +142,20 @@ async fn start() {
 
     let timer = std::time::Instant::now();
 
-    let result = dijkstra(&graph, &start_node, &end_node);
+    let options = RouteOptions::default();
 
-    /*let duration = start_time.elapsed();
-
-    println!(
-        "Time elapsed in bidirectional_dijkstra_path_2() is: {:?}",
-        duration
-    );*/
+    let result = dijkstra(&graph, &start_node, &end_node, &options);
 
     println!("Time to calculate dijkstra: {:?}", timer.elapsed());
+
+    let timer = std::time::Instant::now();
+
+    let _result = bidirectional_dijkstra(&graph, &start_node, &end_node, &options);
+
+    println!(
+        "Time to calculate bidirectional dijkstra: {:?}",
+        timer.elapsed()
+    );
 }
 
 /// Parameters for the calculate-route endpoint
@@ -119,13 +164,149 @@ async fn start() {
 /// * from_lon: longitude of the starting point
 /// * to_lat: latitude of the ending point
 /// * to_lon: longitude of the ending point
-/// Example: http://localhost:8080/calculate-route/?from_lat=47.2715023&from_lon=6.9877472&to_lat=47.25&to_lon=7.0
+/// * algorithm: shortest path algorithm, "dijkstra", "astar", "bidirectional" or "ch" (optional, default "ch")
+/// * profile: routing profile of config/profiles.json, e.g. "fastest", "shortest", "curviest" or "balanced" (optional)
+/// * alternatives: number of alternative routes, between 0 and 3 (optional, default 0)
+/// * avoid: road features to avoid, comma separated among "motorway", "toll", "ferry", "unpaved" and "tunnel" (optional)
+/// * avoid_polygons: GeoJSON polygons to avoid, e.g. a closed pass (optional)
+/// * avoid_polygons_penalty: factor applied to the cost of the roads in the polygons, at least 1 (optional, the roads are excluded by default)
+/// * depart_at: local departure time in Europe/Zurich, e.g. "2024-01-15T08:30", used for the conditional restrictions (optional, default now)
+/// * lang: language of the instructions, "en", "fr", "de" or "it" (optional, default "en")
+/// Example: http://localhost:8080/calculate-route/?from_lat=47.2715023&from_lon=6.9877472&to_lat=47.25&to_lon=7.0&profile=curviest
 #[derive(serde::Deserialize, Debug)]
 struct CalculateRouteParams {
     from_lat: f64,
     from_lon: f64,
     to_lat: f64,
     to_lon: f64,
+    #[serde(default)]
+    algorithm: Algorithm,
+    profile: Option<String>,
+    #[serde(default)]
+    alternatives: usize,
+    avoid: Option<String>,
+    avoid_polygons: Option<String>,
+    avoid_polygons_penalty: Option<f64>,
+    depart_at: Option<String>,
+    #[serde(default)]
+    lang: Language,
+}
+
+/// Point of a route
+/// # Fields
+/// * lat: latitude of the point
+/// * lon: longitude of the point
+#[derive(serde::Deserialize, Debug, Clone, Copy)]
+struct Waypoint {
+    lat: f64,
+    lon: f64,
+}
+
+/// Waypoint implementation
+impl Waypoint {
+    /// Parse a point written as "latitude,longitude"
+    /// # Arguments
+    /// * value: the point
+    /// # Returns
+    /// * The point, None if the value is invalid
+    fn parse(value: &str) -> Option<Waypoint> {
+        let (lat, lon) = value.split_once(',')?;
+
+        Some(Waypoint {
+            lat: lat.trim().parse().ok()?,
+            lon: lon.trim().parse().ok()?,
+        })
+    }
+}
+
+/// Body of the calculate-route endpoint with waypoints
+/// # Fields
+/// * waypoints: the points of the route in order, from the start to the end
+/// * algorithm: shortest path algorithm (optional, default "ch")
+/// * profile: routing profile (optional)
+/// * alternatives: number of alternative routes between two waypoints (optional, default 0)
+/// * avoid: road features to avoid (optional)
+/// * avoid_polygons: GeoJSON polygons to avoid (optional)
+/// * avoid_polygons_penalty: factor applied to the cost of the roads in the polygons (optional, the roads are excluded by default)
+/// * depart_at: local departure time in Europe/Zurich (optional, default now)
+/// * lang: language of the instructions (optional, default "en")
+#[derive(serde::Deserialize, Debug)]
+struct CalculateRouteBody {
+    waypoints: Vec<Waypoint>,
+    #[serde(default)]
+    algorithm: Algorithm,
+    profile: Option<String>,
+    #[serde(default)]
+    alternatives: usize,
+    #[serde(default)]
+    avoid: Vec<AvoidFeature>,
+    avoid_polygons: Option<Value>,
+    avoid_polygons_penalty: Option<f64>,
+    depart_at: Option<String>,
+    #[serde(default)]
+    lang: Language,
+}
+
+/// Body of the optimize-route endpoint
+/// # Fields
+/// * stops: the points to visit
+/// * fixed_start: the route starts at the first stop (optional, default true)
+/// * fixed_end: the route ends at the last stop (optional, default false)
+/// * algorithm: shortest path algorithm (optional, default "ch")
+/// * profile: routing profile (optional)
+/// * avoid: road features to avoid (optional)
+/// * avoid_polygons: GeoJSON polygons to avoid (optional)
+/// * avoid_polygons_penalty: factor applied to the cost of the roads in the polygons (optional, the roads are excluded by default)
+/// * depart_at: local departure time in Europe/Zurich (optional, default now)
+/// * lang: language of the instructions (optional, default "en")
+#[derive(serde::Deserialize, Debug)]
+struct OptimizeRouteBody {
+    stops: Vec<Waypoint>,
+    #[serde(default = "default_true")]
+    fixed_start: bool,
+    #[serde(default)]
+    fixed_end: bool,
+    #[serde(default)]
+    algorithm: Algorithm,
+    profile: Option<String>,
+    #[serde(default)]
+    avoid: Vec<AvoidFeature>,
+    avoid_polygons: Option<Value>,
+    avoid_polygons_penalty: Option<f64>,
+    depart_at: Option<String>,
+    #[serde(default)]
+    lang: Language,
+}
+
+/// Time zone of the graph, in which the conditional restrictions such as `Nov-May` or
+/// `Sa,Su 10:00-18:00` are written
+const GRAPH_TIME_ZONE: Tz = chrono_tz::Europe::Zurich;
+
+/// Parse a departure time, as a local time of the graph time zone
+/// A time without offset is already local. A time with an offset and the current time are
+/// converted to the graph time zone, whatever the time zone of the server
+/// # Arguments
+/// * value: the time as "YYYY-MM-DDTHH:MM", "YYYY-MM-DDTHH:MM:SS" or RFC 3339 with an offset, None for now
+/// # Returns
+/// * The local date and time, or an error message if the value is invalid
+fn parse_depart_at(value: Option<&str>) -> Result<NaiveDateTime, String> {
+    let value = match value {
+        Some(value) => value.trim(),
+        None => return Ok(Utc::now().with_timezone(&GRAPH_TIME_ZONE).naive_local()),
+    };
+
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(date_time.with_timezone(&GRAPH_TIME_ZONE).naive_local());
+    }
+
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M"))
+        .map_err(|_| format!("Invalid departure time: {}", value))
+}
+
+/// Default value of the boolean parameters enabled by default
+fn default_true() -> bool {
+    true
 }
 
 /// Parameters for the calculate-loop endpoint
@@ -133,60 +314,329 @@ struct CalculateRouteParams {
 /// * from_lat: latitude of the starting point
 /// * from_lon: longitude of the starting point
 /// * distance: distance of the loop in meters
+/// * profile: routing profile of config/profiles.json (optional)
+/// * avoid: road features to avoid, comma separated (optional)
+/// * depart_at: local departure time in Europe/Zurich (optional, default now)
+/// * lang: language of the instructions (optional, default "en")
 /// Example: http://localhost:8080/calculate-loop/?from_lat=47.2715023&from_lon=6.9877472&distance=1000
 #[derive(serde::Deserialize, Debug)]
 struct CalculateLoopParams {
     from_lat: f64,
     from_lon: f64,
     distance: f64, // distance in meters
+    profile: Option<String>,
+    avoid: Option<String>,
+    depart_at: Option<String>,
+    #[serde(default)]
+    lang: Language,
 }
 
-/// Calculate a route between two points
+/// Calculate a route between two points, through optional via points
 /// # Parameters
 /// * from_lat: latitude of the starting point
 /// * from_lon: longitude of the starting point
 /// * to_lat: latitude of the ending point
 /// * to_lon: longitude of the ending point
+/// * via: via point as "latitude,longitude", repeated for each via point in order (optional)
+/// * algorithm: shortest path algorithm, "dijkstra", "astar", "bidirectional" or "ch" (optional, default "ch")
+/// * profile: routing profile (optional, the weights of the graph are used by default)
+/// * alternatives: number of alternative routes, without via points (optional, default 0)
+/// * avoid: road features to avoid, e.g. "motorway,toll" (optional)
+/// * avoid_polygons: GeoJSON polygons to avoid, as a Polygon, MultiPolygon, Feature or FeatureCollection (optional)
+/// * avoid_polygons_penalty: factor applied to the cost of the roads in the polygons, at least 1 (optional, the roads are excluded by default)
+/// * depart_at: local departure time in Europe/Zurich as "YYYY-MM-DDTHH:MM", the conditional restrictions such as winter closures are evaluated at this time (optional, default now)
+/// * lang: language of the instructions, "en", "fr", "de" or "it" (optional, default "en")
 /// # Returns
-/// * A JSON object containing the route
+/// * A JSON array containing the route, followed by the alternative routes
 /// # Example
-/// http://localhost:8080/calculate-route/?from_lat=47.2715023&from_lon=6.9877472&to_lat=47.25&to_lon=7.0
+/// http://localhost:8080/calculate-route/?from_lat=47.2715023&from_lon=6.9877472&to_lat=47.25&to_lon=7.0&via=47.26,6.99
 #[get("/calculate-route/")]
 async fn calculate_route(
+    req: HttpRequest,
     params: web::Query<CalculateRouteParams>,
     data: web::Data<AppState>,
 ) -> impl Responder {
-    let start_node = {
-        let graph = &data.graph;
-        graph
-            .get_nearest_node(params.from_lat, params.from_lon)
-            .unwrap()
+    let query = match web::Query::<Vec<(String, String)>>::from_query(req.query_string()) {
+        Ok(query) => query.into_inner(),
+        Err(_) => return HttpResponse::BadRequest().body("Invalid query"),
     };
 
-    let end_node = {
-        let graph = &data.graph;
-        graph
-            .get_nearest_node(params.to_lat, params.to_lon)
-            .unwrap()
+    let mut waypoints = vec![Waypoint {
+        lat: params.from_lat,
+        lon: params.from_lon,
+    }];
+
+    for (key, value) in &query {
+        if key == "via" {
+            match Waypoint::parse(value) {
+                Some(waypoint) => waypoints.push(waypoint),
+                None => return HttpResponse::BadRequest().body("Invalid via point"),
+            }
+        }
+    }
+
+    waypoints.push(Waypoint {
+        lat: params.to_lat,
+        lon: params.to_lon,
+    });
+
+    let avoid = match AvoidFeature::parse_list(params.avoid.as_deref().unwrap_or("")) {
+        Ok(avoid) => avoid,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+
+    let avoid_polygons = match params.avoid_polygons.as_deref().map(serde_json::from_str) {
+        Some(Ok(geojson)) => Some(geojson),
+        Some(Err(_)) => return HttpResponse::BadRequest().body("Invalid avoid polygons"),
+        None => None,
+    };
+
+    let options = match data.route_options(
+        params.profile.as_deref(),
+        avoid,
+        avoid_polygons.as_ref(),
+        params.avoid_polygons_penalty,
+        params.depart_at.as_deref(),
+    ) {
+        Ok(options) => options,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+
+    route_through_waypoints(
+        &data,
+        &waypoints,
+        params.algorithm,
+        &options,
+        None,
+        params.alternatives,
+        params.lang,
+    )
+}
+
+/// Calculate a route through waypoints
+/// # Body
+/// * waypoints: the points of the route in order, from the start to the end
+/// * algorithm: shortest path algorithm (optional, default "ch")
+/// * profile: routing profile (optional, the weights of the graph are used by default)
+/// * alternatives: number of alternative routes between two waypoints (optional, default 0)
+/// * avoid: road features to avoid, e.g. ["motorway", "toll"] (optional)
+/// * avoid_polygons: GeoJSON polygons to avoid, as a Polygon, MultiPolygon, Feature or FeatureCollection (optional)
+/// * avoid_polygons_penalty: factor applied to the cost of the roads in the polygons, at least 1 (optional, the roads are excluded by default)
+/// * depart_at: local departure time in Europe/Zurich as "YYYY-MM-DDTHH:MM", the conditional restrictions such as winter closures are evaluated at this time (optional, default now)
+/// * lang: language of the instructions, "en", "fr", "de" or "it" (optional, default "en")
+/// # Returns
+/// * A JSON array containing the route, followed by the alternative routes
+/// # Example
+/// POST http://localhost:8080/calculate-route/
+/// {"waypoints": [{"lat": 47.2715023, "lon": 6.9877472}, {"lat": 47.26, "lon": 6.99}, {"lat": 47.25, "lon": 7.0}]}
+#[post("/calculate-route/")]
+async fn calculate_route_waypoints(
+    body: web::Json<CalculateRouteBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let options = match data.route_options(
+        body.profile.as_deref(),
+        body.avoid.clone(),
+        body.avoid_polygons.as_ref(),
+        body.avoid_polygons_penalty,
+        body.depart_at.as_deref(),
+    ) {
+        Ok(options) => options,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+
+    route_through_waypoints(
+        &data,
+        &body.waypoints,
+        body.algorithm,
+        &options,
+        None,
+        body.alternatives,
+        body.lang,
+    )
+}
+
+/// Calculate a route visiting stops in the order with the lowest cost
+/// # Body
+/// * stops: the points to visit
+/// * fixed_start: the route starts at the first stop (optional, default true)
+/// * fixed_end: the route ends at the last stop (optional, default false)
+/// * algorithm: shortest path algorithm (optional, default "ch")
+/// * profile: routing profile (optional, the weights of the graph are used by default)
+/// * avoid: road features to avoid (optional)
+/// * avoid_polygons: GeoJSON polygons to avoid (optional)
+/// * avoid_polygons_penalty: factor applied to the cost of the roads in the polygons (optional, the roads are excluded by default)
+/// * depart_at: local departure time in Europe/Zurich (optional, default now)
+/// * lang: language of the instructions, "en", "fr", "de" or "it" (optional, default "en")
+/// # Returns
+/// * A JSON object containing the route and the visiting order of the stops in `order`
+/// # Example
+/// POST http://localhost:8080/optimize-route/
+/// {"stops": [{"lat": 47.2715023, "lon": 6.9877472}, {"lat": 47.25, "lon": 7.0}, {"lat": 47.26, "lon": 6.99}], "fixed_end": false}
+#[post("/optimize-route/")]
+async fn optimize_route(
+    body: web::Json<OptimizeRouteBody>,
+    data: web::Data<AppState>,
+) -> impl Responder {
+    let constraints = OrderConstraints {
+        fixed_start: body.fixed_start,
+        fixed_end: body.fixed_end,
+    };
+
+    let options = match data.route_options(
+        body.profile.as_deref(),
+        body.avoid.clone(),
+        body.avoid_polygons.as_ref(),
+        body.avoid_polygons_penalty,
+        body.depart_at.as_deref(),
+    ) {
+        Ok(options) => options,
+        Err(error) => return HttpResponse::BadRequest().body(error),
     };
 
-    let (result, edges) = dijkstra(&data.graph, &start_node, &end_node);
+    route_through_waypoints(
+        &data,
+        &body.stops,
+        body.algorithm,
+        &options,
+        Some(constraints),
+        0,
+        body.lang,
+    )
+}
+
+/// Route each leg between two consecutive waypoints and combine the legs into a single route
+/// # Arguments
+/// * data: state of the application
+/// * waypoints: the points of the route
+/// * algorithm: shortest path algorithm
+/// * options: options of the route
+/// * optimization: constraints of the visiting order to optimize, None to keep the order of the waypoints
+/// * alternatives: number of alternative routes, only available between two waypoints
+/// * language: language of the instructions
+/// # Returns
+/// * The route and its alternatives in a JSON array, or a bad request if the route can't be calculated
+fn route_through_waypoints(
+    data: &AppState,
+    waypoints: &[Waypoint],
+    algorithm: Algorithm,
+    options: &RouteOptions,
+    optimization: Option<OrderConstraints>,
+    alternatives: usize,
+    language: Language,
+) -> HttpResponse {
+    if waypoints.len() < 2 || waypoints.len() > MAX_WAYPOINTS {
+        return HttpResponse::BadRequest().body(format!(
+            "A route needs between 2 and {} waypoints",
+            MAX_WAYPOINTS
+        ));
+    }
+
+    if alternatives > MAX_ALTERNATIVES {
+        return HttpResponse::BadRequest().body(format!(
+            "At most {} alternative routes can be requested",
+            MAX_ALTERNATIVES
+        ));
+    }
+
+    if alternatives > 0 && waypoints.len() > 2 {
+        return HttpResponse::BadRequest()
+            .body("Alternative routes are only available between two points");
+    }
+
+    let mut snapped_points = Vec::new();
+
+    for waypoint in waypoints {
+        match data.graph.snap_to_edge(waypoint.lat, waypoint.lon) {
+            Some(snapped_point) => snapped_points.push(snapped_point),
+            None => return HttpResponse::BadRequest().body("No road found near the points"),
+        }
+    }
+
+    let order = match optimization {
+        Some(constraints) => {
+            let matrix = cost_matrix(&data.graph, &snapped_points, options);
+            let order = optimize_order(&matrix, constraints);
+
+            if !order_cost(&matrix, &order).is_finite() {
+                return HttpResponse::BadRequest().body("No route found through all the waypoints");
+            }
 
-    if result.is_none() {
-        return HttpResponse::BadRequest().body("No route found");
-    } else {
-        let result = result.unwrap();
+            snapped_points = order
+                .iter()
+                .map(|index| snapped_points[*index].clone())
+                .collect();
 
-        let test = data
-            .graph
-            .directions_instructions_and_path(&result, &edges.unwrap());
+            Some(order)
+        }
+        None => None,
+    };
 
-        let mut path = vec![];
+    let legs = match find_path_through(&data.graph, &snapped_points, algorithm, options) {
+        Ok(legs) => legs,
+        Err(index) => {
+            return HttpResponse::BadRequest().body(format!(
+                "No route found between waypoints {} and {}",
+                index,
+                index + 1
+            ))
+        }
+    };
 
-        path.push(test);
+    let leg_routes = legs
+        .iter()
+        .zip(snapped_points.windows(2))
+        .map(|((nodes, edges), points)| {
+            let mut leg =
+                data.graph
+                    .directions_instructions_and_path(nodes, edges, &data.turn_thresholds);
+
+            data.graph
+                .add_snapped_points(&mut leg, &points[0], &points[1]);
+
+            leg
+        })
+        .collect::<Vec<_>>();
+
+    let mut route = data.graph.combine_legs(leg_routes);
+    route.order = order;
+
+    let mut routes = vec![route];
+
+    if alternatives > 0 {
+        let (start_point, end_point) = (&snapped_points[0], &snapped_points[1]);
+
+        for alternative in find_alternatives(
+            &data.graph,
+            start_point,
+            end_point,
+            &legs[0].1,
+            algorithm,
+            options,
+            alternatives,
+        ) {
+            let mut leg = data.graph.directions_instructions_and_path(
+                &alternative.nodes,
+                &alternative.edges,
+                &data.turn_thresholds,
+            );
+
+            data.graph
+                .add_snapped_points(&mut leg, start_point, end_point);
+
+            let mut route = data.graph.combine_legs(vec![leg]);
+            route.stretch = Some(alternative.stretch);
+            route.overlap = Some(alternative.overlap);
+
+            routes.push(route);
+        }
+    }
 
-        HttpResponse::Ok().json(path)
+    for route in routes.iter_mut() {
+        maneuver::add_texts(&mut route.directions.instructions, language);
     }
+
+    HttpResponse::Ok().json(routes)
 }
 
 /// Calculate a loop starting from a point
@@ -194,6 +644,10 @@ async fn calculate_route(
 /// * from_lat: latitude of the starting point
 /// * from_lon: longitude of the starting point
 /// * distance: distance of the loop in meters
+/// * profile: routing profile (optional, the weights of the graph are used by default)
+/// * avoid: road features to avoid, e.g. "unpaved,ferry" (optional)
+/// * depart_at: local departure time in Europe/Zurich (optional, default now)
+/// * lang: language of the instructions, "en", "fr", "de" or "it" (optional, default "en")
 /// # Returns
 /// * A JSON object containing the route
 /// # Example
@@ -205,23 +659,40 @@ async fn calculate_loop(
 ) -> impl Responder {
     info!("Request: {:?}", params);
 
-    let start_node = {
-        let graph = &data.graph; // Create a new scope to borrow graph immutably
-        graph
-            .get_nearest_node(params.from_lat, params.from_lon)
-            .unwrap()
+    let start_point = match data.graph.snap_to_edge(params.from_lat, params.from_lon) {
+        Some(start_point) => start_point,
+        None => return HttpResponse::BadRequest().body("No road found near the point"),
+    };
+
+    let avoid = match AvoidFeature::parse_list(params.avoid.as_deref().unwrap_or("")) {
+        Ok(avoid) => avoid,
+        Err(error) => return HttpResponse::BadRequest().body(error),
     };
 
-    let result = generate_random_loop(params.distance, &data.graph, &start_node);
+    let options = match data.route_options(
+        params.profile.as_deref(),
+        avoid,
+        None,
+        None,
+        params.depart_at.as_deref(),
+    ) {
+        Ok(options) => options,
+        Err(error) => return HttpResponse::BadRequest().body(error),
+    };
+
+    let result = match generate_random_loop(params.distance, &data.graph, &start_point, &options) {
+        Some(result) => result,
+        None => return HttpResponse::BadRequest().body("No loop found around the point"),
+    };
 
     let mut path = vec![];
 
-    for i in 0..result.len() - 1 {
-        let node_ids = &result[i].0;
-        let edges_ids = &result[i].1;
-        let res = data
-            .graph
-            .directions_instructions_and_path(&node_ids, &edges_ids);
+    for (node_ids, edges) in &result {
+        let mut res =
+            data.graph
+                .directions_instructions_and_path(node_ids, edges, &data.turn_thresholds);
+
+        maneuver::add_texts(&mut res.instructions, params.lang);
 
         path.push(res);
     }
@@ -229,6 +700,62 @@ async fn calculate_loop(
     HttpResponse::Ok().json(path)
 }
 
+/// Load the road filter config file
+/// # Arguments
+/// * file_path: path of the JSON config file
+/// # Returns
+/// * The road filter of the file, or the default road filter if the file can't be read
+fn load_road_filter(file_path: &std::path::Path) -> RoadFilter {
+    match RoadFilter::from_file(file_path) {
+        Ok(road_filter) => {
+            info!("Road filter loaded from {:?}", file_path);
+            road_filter
+        }
+        Err(error) => {
+            warn!("Failed to load road filter {:?}: {}", file_path, error);
+            RoadFilter::default()
+        }
+    }
+}
+
+/// Load the routing profiles config file
+/// # Arguments
+/// * file_path: path of the JSON config file
+/// * graph: graph the profiles are used on
+/// # Returns
+/// * The profiles of the file, or no profile if the file can't be read
+fn load_profiles(file_path: &std::path::Path, graph: &OSMGraph) -> Profiles {
+    match Profiles::from_file(file_path) {
+        Ok(mut profiles) => {
+            profiles.update_min_cost_per_meter(graph);
+            info!("Profiles loaded from {:?}", file_path);
+            profiles
+        }
+        Err(error) => {
+            warn!("Failed to load profiles {:?}: {}", file_path, error);
+            Profiles::default()
+        }
+    }
+}
+
+/// Load the turn thresholds config file
+/// # Arguments
+/// * file_path: path of the JSON config file
+/// # Returns
+/// * The thresholds of the file, or the default thresholds if the file can't be read
+fn load_turn_thresholds(file_path: &std::path::Path) -> TurnThresholds {
+    match TurnThresholds::from_file(file_path) {
+        Ok(turn_thresholds) => {
+            info!("Turn thresholds loaded from {:?}", file_path);
+            turn_thresholds
+        }
+        Err(error) => {
+            warn!("Failed to load turn thresholds {:?}: {}", file_path, error);
+            TurnThresholds::default()
+        }
+    }
+}
+
 /// Main function
 /// # Returns
 /// * A ShuttleActixWeb object
@@ -272,15 +799,33 @@ async fn actix_web(
     info!("File saved !");
     println!("File saved !");
 
-    let mut osm_reader = OSMReader::new(file_path.to_str().unwrap().to_string());
-    let graph = osm_reader.build_graph().await;
+    let cache_dir = PathBuf::from("cache");
+
+    let road_filter = load_road_filter(&PathBuf::from("config").join("road_filter.json"));
+
+    let mut osm_reader =
+        OSMReader::with_road_filter(file_path.to_str().unwrap().to_string(), road_filter);
+
+    let elevation_dir = static_folder.join("srtm");
+    if elevation_dir.is_dir() {
+        osm_reader.set_elevation_dir(elevation_dir);
+    }
+
+    let mut graph = osm_reader.load_or_build_graph(&cache_dir).await;
 
     info!("Graph built");
     println!("Graph built");
 
+    graph.load_or_build_contraction_hierarchy(&cache_dir);
+
+    let profiles = load_profiles(&PathBuf::from("config").join("profiles.json"), &graph);
+    let turn_thresholds = load_turn_thresholds(&PathBuf::from("config").join("turns.json"));
+
     let app_state = AppState {
         app_name: String::from("OSM4Routing"),
-        graph: graph,
+        graph,
+        profiles,
+        turn_thresholds,
     };
 
     let app_data = web::Data::new(app_state);
@@ -298,6 +843,8 @@ async fn actix_web(
             web::scope("")
                 .service(calculate_loop)
                 .service(calculate_route)
+                .service(calculate_route_waypoints)
+                .service(optimize_route)
                 .app_data(app_data.clone())
                 .wrap(Arc::new(cors)),
         );
@@ -321,14 +868,32 @@ async fn main_actix() -> std::io::Result<()> {
     let file_path = current_dir.join("data").join("switzerland-latest.osm.pbf");
     //let file_path = current_dir.join("data").join("luxembourg-latest.osm.pbf");
 
-    let mut osm_reader = OSMReader::new(file_path.to_str().unwrap().to_string());
-    let graph = osm_reader.build_graph();
+    let cache_dir = current_dir.join("cache");
+
+    let road_filter = load_road_filter(&current_dir.join("config").join("road_filter.json"));
+
+    let mut osm_reader =
+        OSMReader::with_road_filter(file_path.to_str().unwrap().to_string(), road_filter);
+
+    let elevation_dir = current_dir.join("data").join("srtm");
+    if elevation_dir.is_dir() {
+        osm_reader.set_elevation_dir(elevation_dir);
+    }
+
+    let mut graph = osm_reader.load_or_build_graph(&cache_dir).await;
 
     println!("Graph built");
 
+    graph.load_or_build_contraction_hierarchy(&cache_dir);
+
+    let profiles = load_profiles(&current_dir.join("config").join("profiles.json"), &graph);
+    let turn_thresholds = load_turn_thresholds(&current_dir.join("config").join("turns.json"));
+
     let app_state = AppState {
         app_name: String::from("OSM4Routing"),
-        graph: graph.await,
+        graph,
+        profiles,
+        turn_thresholds,
     };
 
     let app_data = web::Data::new(app_state);
@@ -345,6 +910,8 @@ async fn main_actix() -> std::io::Result<()> {
             .wrap(cors)
             .app_data(app_data.clone())
             .service(calculate_route)
+            .service(calculate_route_waypoints)
+            .service(optimize_route)
     })
     .bind(("127.0.0.1", 4242))?
     .run()
@@ -355,8 +922,160 @@ async fn main_actix() -> std::io::Result<()> {
 /// # Fields
 /// * app_name: name of the application
 /// * graph: OSMGraph
+/// * profiles: routing profiles
+/// * turn_thresholds: thresholds classifying the turns of the instructions
 #[derive(Clone)]
 struct AppState {
     app_name: String,
     graph: OSMGraph,
+    profiles: Profiles,
+    turn_thresholds: TurnThresholds,
+}
+
+/// AppState implementation
+impl AppState {
+    /// Get the options of a route request
+    /// # Arguments
+    /// * profile: name of the routing profile, the weights of the graph are used if None
+    /// * avoid: road features to avoid
+    /// * avoid_polygons: GeoJSON polygons to avoid, None if there is no polygon
+    /// * avoid_polygons_penalty: factor applied to the cost of the edges in the polygons, None to exclude them
+    /// * depart_at: local departure time in Europe/Zurich, the current time if None
+    /// # Returns
+    /// * The options of the route, or an error message if the profile doesn't exist, the polygons or the departure time are invalid
+    fn route_options(
+        &self,
+        profile: Option<&str>,
+        avoid: Vec<AvoidFeature>,
+        avoid_polygons: Option<&Value>,
+        avoid_polygons_penalty: Option<f64>,
+        depart_at: Option<&str>,
+    ) -> Result<RouteOptions, String> {
+        let mut options = match profile {
+            Some(name) => match self.profiles.get(name) {
+                Some(profile) => RouteOptions::with_profile(profile.clone()),
+                None => return Err(format!("Unknown profile: {}", name)),
+            },
+            None => RouteOptions::default(),
+        };
+
+        options.avoid = avoid;
+        options.depart_at = Some(parse_depart_at(depart_at)?);
+
+        if let Some(geojson) = avoid_polygons {
+            // a lower factor would make the A* heuristic overestimate the costs
+            if avoid_polygons_penalty.is_some_and(|penalty| penalty.is_nan() || penalty < 1.0) {
+                return Err("The avoid polygons penalty must be at least 1".to_string());
+            }
+
+            let zones = AvoidZone::from_geojson(geojson)?;
+
+            options.avoid_zones(&self.graph, &zones, avoid_polygons_penalty);
+        }
+
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::grid;
+    use actix_web::http::StatusCode;
+    use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+    use serde_json::json;
+
+    /// Build the state of the application on the test grid
+    /// # Returns
+    /// * The state, without routing profile
+    fn test_state() -> AppState {
+        AppState {
+            app_name: String::from("test"),
+            graph: grid(),
+            profiles: Profiles::default(),
+            turn_thresholds: TurnThresholds::default(),
+        }
+    }
+
+    #[actix_web::test]
+    async fn malformed_avoid_polygons_are_rejected() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(test_state()))
+                .service(calculate_route)
+                .service(calculate_route_waypoints),
+        )
+        .await;
+
+        let route = "/calculate-route/?from_lat=47.0&from_lon=7.0025&to_lat=47.02&to_lon=7.0175";
+
+        let request = TestRequest::get().uri(route).to_request();
+        assert_eq!(call_service(&app, request).await.status(), StatusCode::OK);
+
+        let request = TestRequest::get()
+            .uri(&format!("{}&avoid_polygons=not-json", route))
+            .to_request();
+        assert_eq!(
+            call_service(&app, request).await.status(),
+            StatusCode::BAD_REQUEST
+        );
+
+        for avoid_polygons in [
+            json!({"type": "Point", "coordinates": [7.0, 47.0]}),
+            json!({"type": "Polygon", "coordinates": [[[7.0, 47.0], [7.1, 47.0]]]}),
+        ] {
+            let request = TestRequest::post()
+                .uri("/calculate-route/")
+                .set_json(json!({
+                    "waypoints": [{"lat": 47.0, "lon": 7.0025}, {"lat": 47.02, "lon": 7.0175}],
+                    "avoid_polygons": avoid_polygons,
+                }))
+                .to_request();
+
+            assert_eq!(
+                call_service(&app, request).await.status(),
+                StatusCode::BAD_REQUEST
+            );
+        }
+    }
+
+    #[actix_web::test]
+    async fn unknown_language_falls_back_to_english() {
+        let app = init_service(
+            App::new()
+                .app_data(web::Data::new(test_state()))
+                .service(calculate_route),
+        )
+        .await;
+
+        let route = "/calculate-route/?from_lat=47.0&from_lon=7.0025&to_lat=47.02&to_lon=7.0175";
+
+        for (lang, arrival) in [
+            ("fr", "Vous êtes arrivé à destination"),
+            ("DE", "Sie haben Ihr Ziel erreicht"),
+            ("es", "Arrive at destination"),
+        ] {
+            let request = TestRequest::get()
+                .uri(&format!("{}&lang={}", route, lang))
+                .to_request();
+            let response = call_service(&app, request).await;
+            assert_eq!(response.status(), StatusCode::OK, "{}", lang);
+
+            let body: Value = read_body_json(response).await;
+            let instructions = body[0]["instructions"].as_array().unwrap();
+            assert_eq!(instructions.last().unwrap()["text"], arrival, "{}", lang);
+        }
+    }
+
+    #[test]
+    fn depart_at_in_graph_time_zone() {
+        let local = |value: &str| parse_depart_at(Some(value)).unwrap().to_string();
+
+        assert_eq!(local("2024-01-15T08:30"), "2024-01-15 08:30:00");
+        // UTC+1 in winter and UTC+2 in summer
+        assert_eq!(local("2024-01-15T07:30:00Z"), "2024-01-15 08:30:00");
+        assert_eq!(local("2024-07-15T07:30:00Z"), "2024-07-15 09:30:00");
+        assert_eq!(local("2024-07-15T09:30:00+02:00"), "2024-07-15 09:30:00");
+        assert!(parse_depart_at(Some("tomorrow")).is_err());
+    }
 }
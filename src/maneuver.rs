@@ -0,0 +1,187 @@
+use crate::curve_warnings::{CurveDirection, CurveSeverity, CurveWarning};
+use crate::localization::{self, Language};
+use osmpbfreader::objects::Way;
+use serde::{Deserialize, Serialize};
+
+/// Type of a maneuver
+/// # Variants
+/// * `Turn` - Turn onto another road
+/// * `Continue` - Continue on a road of the same name, or straight on onto another road
+/// * `Roundabout` - Leave a roundabout, the exit is given by `Maneuver::exit`
+/// * `Waypoint` - Arrive at a via point, its number is given by `Maneuver::waypoint`
+/// * `Arrive` - Arrive at the destination
+/// * `CurveWarning` - Warning of an upcoming curve, graded by `Maneuver::severity`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManeuverType {
+    Turn,
+    Continue,
+    Roundabout,
+    Waypoint,
+    Arrive,
+    CurveWarning,
+}
+
+/// Direction of a maneuver, see `TurnThresholds::classify`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ManeuverModifier {
+    Straight,
+    SlightLeft,
+    Left,
+    SharpLeft,
+    SlightRight,
+    Right,
+    SharpRight,
+    UTurn,
+}
+
+/// Coordinates of a maneuver
+/// # Fields
+/// * `latitude` - The latitude of the maneuver
+/// * `longitude` - The longitude of the maneuver
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+/// Maneuver of a route, the text of its instruction is generated by `localization::instruction_text`
+/// # Fields
+/// * `maneuver_type` - The type of the maneuver, serialized as `type`
+/// * `modifier` - The direction of the maneuver, None if it has no direction
+/// * `name` - The name of the road taken by the maneuver
+/// * `road_ref` - The reference of the road taken by the maneuver, such as `A1`, serialized as `ref`
+/// * `exit` - The number of the exit taken in a roundabout
+/// * `waypoint` - The number of the via point reached, from 1
+/// * `severity` - The severity of the curve of a curve warning
+/// * `tightening` - True if the curve of a curve warning tightens
+/// * `bearing_before` - The bearing of the route before the maneuver, in degrees clockwise from the north
+/// * `bearing_after` - The bearing of the route after the maneuver, in degrees clockwise from the north
+/// * `distance` - The distance from the previous maneuver in meters
+/// * `duration` - The travel time from the previous maneuver in seconds
+/// * `location` - The coordinates of the maneuver
+/// * `text` - The text of the instruction, see `add_texts`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Maneuver {
+    #[serde(rename = "type")]
+    pub maneuver_type: ManeuverType,
+    pub modifier: Option<ManeuverModifier>,
+    pub name: Option<String>,
+    #[serde(rename = "ref")]
+    pub road_ref: Option<String>,
+    pub exit: Option<usize>,
+    pub waypoint: Option<usize>,
+    pub severity: Option<CurveSeverity>,
+    pub tightening: Option<bool>,
+    pub bearing_before: Option<f64>,
+    pub bearing_after: Option<f64>,
+    pub distance: f64,
+    pub duration: f64,
+    pub location: Location,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+/// Maneuver implementation
+impl Maneuver {
+    /// Create a maneuver without road, direction or bearings
+    /// # Arguments
+    /// * `maneuver_type` - The type of the maneuver
+    /// * `distance` - The distance from the previous maneuver in meters
+    /// * `duration` - The travel time from the previous maneuver in seconds
+    /// * `location` - The coordinates of the maneuver
+    pub fn new(
+        maneuver_type: ManeuverType,
+        distance: f64,
+        duration: f64,
+        location: Location,
+    ) -> Self {
+        Maneuver {
+            maneuver_type,
+            modifier: None,
+            name: None,
+            road_ref: None,
+            exit: None,
+            waypoint: None,
+            severity: None,
+            tightening: None,
+            bearing_before: None,
+            bearing_after: None,
+            distance,
+            duration,
+            location,
+            text: None,
+        }
+    }
+
+    /// Set the road taken by the maneuver from the `name` and `ref` tags of its way
+    /// # Arguments
+    /// * `way` - The way taken by the maneuver
+    pub fn on_way(mut self, way: &Way) -> Self {
+        self.name = way.tags.get("name").map(|name| name.to_string());
+        self.road_ref = way.tags.get("ref").map(|road_ref| road_ref.to_string());
+        self
+    }
+
+    /// Set the direction of the maneuver
+    /// # Arguments
+    /// * `modifier` - The direction, None if it has no direction
+    pub fn with_modifier(mut self, modifier: Option<ManeuverModifier>) -> Self {
+        self.modifier = modifier;
+        self
+    }
+
+    /// Set the number of the exit taken in a roundabout
+    /// # Arguments
+    /// * `exit` - The number of the exit, from 1
+    pub fn with_exit(mut self, exit: usize) -> Self {
+        self.exit = Some(exit);
+        self
+    }
+
+    /// Set the number of the via point reached
+    /// # Arguments
+    /// * `waypoint` - The number of the via point, from 1
+    pub fn with_waypoint(mut self, waypoint: usize) -> Self {
+        self.waypoint = Some(waypoint);
+        self
+    }
+
+    /// Set the direction, the severity and the tightening of the curve of a curve warning
+    /// # Arguments
+    /// * `warning` - The warning of the curve
+    pub fn with_curve(mut self, warning: &CurveWarning) -> Self {
+        self.modifier = Some(match warning.direction {
+            CurveDirection::Left => ManeuverModifier::Left,
+            CurveDirection::Right => ManeuverModifier::Right,
+        });
+        self.severity = Some(warning.severity);
+        self.tightening = Some(warning.tightening);
+        self
+    }
+
+    /// Set the bearings of the route around the maneuver
+    /// # Arguments
+    /// * `bearing_before` - The bearing before the maneuver in degrees, None if it is unknown
+    /// * `bearing_after` - The bearing after the maneuver in degrees, None if it is unknown
+    pub fn with_bearings(
+        mut self,
+        bearing_before: Option<f64>,
+        bearing_after: Option<f64>,
+    ) -> Self {
+        self.bearing_before = bearing_before;
+        self.bearing_after = bearing_after;
+        self
+    }
+}
+
+/// Add the text of its instruction to each maneuver of a route
+/// # Arguments
+/// * `maneuvers` - The maneuvers of the route
+/// * `language` - The language of the texts
+pub fn add_texts(maneuvers: &mut [Maneuver], language: Language) {
+    for maneuver in maneuvers.iter_mut() {
+        maneuver.text = Some(localization::instruction_text(maneuver, language));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::conditions::{split_conditional, TimeCondition};
+use osmpbfreader::objects::Tags;
+
+/// Highway classes that are one-way without `oneway` tag
+const IMPLIED_ONEWAY_HIGHWAYS: [&str; 2] = ["motorway", "motorway_link"];
+
+/// Junctions that are one-way without `oneway` tag
+const IMPLIED_ONEWAY_JUNCTIONS: [&str; 2] = ["roundabout", "circular"];
+
+/// Directions in which a way can be followed
+/// # Fields
+/// * `forward` - The way can be followed in the direction of its nodes
+/// * `backward` - The way can be followed in the opposite direction of its nodes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Directions {
+    pub forward: bool,
+    pub backward: bool,
+}
+
+/// Directions implementation
+impl Directions {
+    /// Both directions
+    pub const BOTH: Directions = Directions {
+        forward: true,
+        backward: true,
+    };
+
+    /// Direction of the nodes only
+    pub const FORWARD: Directions = Directions {
+        forward: true,
+        backward: false,
+    };
+
+    /// Opposite direction of the nodes only
+    pub const BACKWARD: Directions = Directions {
+        forward: false,
+        backward: true,
+    };
+
+    /// No direction
+    pub const NONE: Directions = Directions {
+        forward: false,
+        backward: false,
+    };
+
+    /// Parse the value of a oneway tag
+    /// `oneway=reversible` ways change their direction during the day without fixed schedule, so
+    /// they can't be followed. `oneway=alternating` ways are used in both directions in turn
+    /// # Arguments
+    /// * `value` - The value of the tag
+    /// # Returns
+    /// * `Option<Directions>` - The directions of the way, None if the value is unknown
+    pub fn parse(value: &str) -> Option<Directions> {
+        match value.trim() {
+            "yes" | "1" | "true" => Some(Directions::FORWARD),
+            "-1" | "reverse" => Some(Directions::BACKWARD),
+            "no" | "0" | "false" | "alternating" => Some(Directions::BOTH),
+            "reversible" => Some(Directions::NONE),
+            _ => None,
+        }
+    }
+
+    /// Get the directions in which a motorcycle can follow a way
+    /// `oneway:motorcycle` takes precedence over `oneway`. Without explicit value, motorways,
+    /// motorway links and roundabouts are one-way. The conditional one-way restrictions are
+    /// given by `Directions::conditions_of_way`
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `Directions` - The directions of the way
+    pub fn of_way(tags: &Tags) -> Directions {
+        let tag = |key: &str| tags.get(key).map(|value| value.as_str());
+
+        let explicit = tag("oneway:motorcycle")
+            .and_then(Directions::parse)
+            .or_else(|| tag("oneway").and_then(Directions::parse));
+
+        explicit.unwrap_or_else(|| {
+            let implied = IMPLIED_ONEWAY_HIGHWAYS.contains(&tag("highway").unwrap_or(""))
+                || IMPLIED_ONEWAY_JUNCTIONS.contains(&tag("junction").unwrap_or(""));
+
+            if implied {
+                Directions::FORWARD
+            } else {
+                Directions::BOTH
+            }
+        })
+    }
+
+    /// Get the conditional one-way restrictions of a way, from `oneway:motorcycle:conditional`
+    /// or `oneway:conditional`, such as `-1 @ (Mo-Fr 16:00-19:00)`
+    /// The conditions that can't be parsed are ignored
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `Vec<(Directions, TimeCondition)>` - The directions of the way while each condition is met
+    pub fn conditions_of_way(tags: &Tags) -> Vec<(Directions, TimeCondition)> {
+        let conditional = tags
+            .get("oneway:motorcycle:conditional")
+            .or(tags.get("oneway:conditional"))
+            .map_or("", |value| value.as_str());
+
+        split_conditional(conditional)
+            .into_iter()
+            .filter_map(|(value, condition)| {
+                Some((Directions::parse(value)?, TimeCondition::parse(condition)?))
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::tags;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn parse_forward_values() {
+        for value in ["yes", "1", "true", " yes "] {
+            assert_eq!(
+                Directions::parse(value),
+                Some(Directions::FORWARD),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parse_backward_values() {
+        for value in ["-1", "reverse"] {
+            assert_eq!(
+                Directions::parse(value),
+                Some(Directions::BACKWARD),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parse_two_way_values() {
+        for value in ["no", "0", "false"] {
+            assert_eq!(
+                Directions::parse(value),
+                Some(Directions::BOTH),
+                "{}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn parse_reversible_and_alternating() {
+        assert_eq!(Directions::parse("reversible"), Some(Directions::NONE));
+        assert_eq!(Directions::parse("alternating"), Some(Directions::BOTH));
+        assert_eq!(Directions::parse("sometimes"), None);
+    }
+
+    #[test]
+    fn implied_oneway_roads() {
+        assert_eq!(
+            Directions::of_way(&tags(&[("highway", "motorway")])),
+            Directions::FORWARD
+        );
+        assert_eq!(
+            Directions::of_way(&tags(&[("highway", "motorway_link")])),
+            Directions::FORWARD
+        );
+        assert_eq!(
+            Directions::of_way(&tags(&[("highway", "primary"), ("junction", "roundabout")])),
+            Directions::FORWARD
+        );
+        assert_eq!(
+            Directions::of_way(&tags(&[("highway", "primary")])),
+            Directions::BOTH
+        );
+    }
+
+    #[test]
+    fn explicit_value_overrides_implied_oneway() {
+        assert_eq!(
+            Directions::of_way(&tags(&[("highway", "motorway"), ("oneway", "no")])),
+            Directions::BOTH
+        );
+        assert_eq!(
+            Directions::of_way(&tags(&[("highway", "secondary"), ("oneway", "-1")])),
+            Directions::BACKWARD
+        );
+    }
+
+    #[test]
+    fn motorcycle_oneway_overrides_oneway() {
+        assert_eq!(
+            Directions::of_way(&tags(&[
+                ("highway", "residential"),
+                ("oneway", "yes"),
+                ("oneway:motorcycle", "no"),
+            ])),
+            Directions::BOTH
+        );
+        assert_eq!(
+            Directions::of_way(&tags(&[
+                ("highway", "residential"),
+                ("oneway:motorcycle", "yes"),
+            ])),
+            Directions::FORWARD
+        );
+        // an unknown motorcycle value falls back to the oneway tag
+        assert_eq!(
+            Directions::of_way(&tags(&[
+                ("highway", "residential"),
+                ("oneway", "-1"),
+                ("oneway:motorcycle", "unknown"),
+            ])),
+            Directions::BACKWARD
+        );
+    }
+
+    #[test]
+    fn conditional_oneway_at_a_given_time() {
+        let conditions = Directions::conditions_of_way(&tags(&[
+            ("highway", "secondary"),
+            ("oneway:conditional", "-1 @ (Mo-Fr 16:00-19:00)"),
+        ]));
+
+        assert_eq!(conditions.len(), 1);
+        assert_eq!(conditions[0].0, Directions::BACKWARD);
+
+        // 2024-01-15 is a Monday and 2024-01-13 a Saturday
+        let monday_evening = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .and_then(|date| date.and_hms_opt(17, 30, 0))
+            .unwrap();
+        let monday_morning = NaiveDate::from_ymd_opt(2024, 1, 15)
+            .and_then(|date| date.and_hms_opt(8, 0, 0))
+            .unwrap();
+        let saturday_evening = NaiveDate::from_ymd_opt(2024, 1, 13)
+            .and_then(|date| date.and_hms_opt(17, 30, 0))
+            .unwrap();
+
+        assert!(conditions[0].1.matches(&monday_evening));
+        assert!(!conditions[0].1.matches(&monday_morning));
+        assert!(!conditions[0].1.matches(&saturday_evening));
+    }
+
+    #[test]
+    fn motorcycle_conditional_overrides_conditional() {
+        let conditions = Directions::conditions_of_way(&tags(&[
+            ("oneway:conditional", "-1 @ (Mo-Fr 16:00-19:00)"),
+            ("oneway:motorcycle:conditional", "yes @ (Sa,Su)"),
+        ]));
+
+        assert_eq!(conditions.len(), 1);
+        assert_eq!(conditions[0].0, Directions::FORWARD);
+    }
+}
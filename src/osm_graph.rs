@@ -1,3 +1,14 @@
+use crate::access::EdgeAccess;
+use crate::contraction_hierarchies::ContractionHierarchy;
+use crate::curve_warnings::{self, CurveWarning};
+use crate::directions::{Directions, LegSummary, Route, SnappedLocation};
+use crate::elevation::ElevationModel;
+use crate::elevation_profile::ElevationProfile;
+use crate::maneuver::{Location, Maneuver, ManeuverModifier, ManeuverType};
+use crate::road_filter::RoadFilter;
+use crate::spatial_index::EdgeIndex;
+use crate::turn_restrictions::TurnRestrictions;
+use crate::turns::{self, TurnThresholds};
 use osmpbfreader::objects::{Node, NodeId, Way, WayId};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
@@ -5,6 +16,7 @@ use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap};
 use std::fs::File;
 use std::io::Write;
+use std::path::Path;
 
 /// OSMGraph struct that contains the nodes, ways and edges of the graph
 /// # Attributes
@@ -12,14 +24,38 @@ use std::io::Write;
 /// * `ways` - The ways of the graph
 /// * `edges` - The edges of the graph
 /// * `edges_from_node` - A hashmap that contains the edges that start from a node
+/// * `edges_to_node` - A hashmap that contains the edges that end to a node
 /// * `empty_edges` - An empty vector of edges
-#[derive(Debug, Clone)]
+/// * `edge_index` - The spatial index of the segments of the edges, built by `build_spatial_index`
+/// * `min_weight_per_meter` - The smallest weight per meter of the edges, used by the A* heuristic
+/// * `turn_restrictions` - The turn restrictions between the ways of the graph
+/// * `elevations` - The altitude of the nodes in meters, empty if no elevation model was used
+/// * `metadata` - The settings used to build the graph
+/// * `contraction_hierarchy` - The contraction hierarchy of the edges, if it has been built. It is
+///   stored in its own cache file and is not serialized with the graph
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OSMGraph {
     pub nodes: HashMap<NodeId, Node>,
     pub ways: HashMap<WayId, Way>,
     pub edges: Vec<Edge>,
     pub edges_from_node: HashMap<NodeId, Vec<Edge>>,
+    pub edges_to_node: HashMap<NodeId, Vec<Edge>>,
     pub empty_edges: Vec<Edge>,
+    pub edge_index: EdgeIndex,
+    pub min_weight_per_meter: f64,
+    pub turn_restrictions: TurnRestrictions,
+    pub elevations: HashMap<NodeId, f64>,
+    pub metadata: GraphMetadata,
+    #[serde(skip)]
+    pub contraction_hierarchy: Option<ContractionHierarchy>,
+}
+
+/// Settings used to build a graph
+/// # Fields
+/// * `road_filter` - The filter of the ways kept in the graph
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GraphMetadata {
+    pub road_filter: RoadFilter,
 }
 
 /// OSMGraph implementation
@@ -33,7 +69,14 @@ impl OSMGraph {
             ways: HashMap::new(),
             edges: Vec::new(),
             edges_from_node: HashMap::new(),
+            edges_to_node: HashMap::new(),
             empty_edges: Vec::new(),
+            edge_index: EdgeIndex::new(),
+            min_weight_per_meter: 0.0,
+            turn_restrictions: TurnRestrictions::default(),
+            elevations: HashMap::new(),
+            metadata: GraphMetadata::default(),
+            contraction_hierarchy: None,
         }
     }
 
@@ -109,6 +152,140 @@ impl OSMGraph {
         &self.nodes
     }
 
+    /// Get the altitude of a node
+    /// # Arguments
+    /// * `id` - The id of the node
+    /// # Returns
+    /// * `Option<f64>` - The altitude of the node in meters, None if it is unknown
+    pub fn get_elevation(&self, id: NodeId) -> Option<f64> {
+        self.elevations.get(&id).copied()
+    }
+
+    /// Give an altitude to the nodes of the graph covered by an elevation model
+    /// # Arguments
+    /// * `elevation_model` - The elevation model
+    pub fn update_elevations(&mut self, elevation_model: &ElevationModel) {
+        self.elevations = self
+            .nodes
+            .iter()
+            .filter_map(|(id, node)| {
+                Some((*id, elevation_model.elevation(node.lat(), node.lon())?))
+            })
+            .collect();
+    }
+
+    /// Get the elevation profile of a line of nodes
+    /// The repeated nodes are only counted once and the nodes without altitude are left out
+    /// # Arguments
+    /// * `nodes_ids` - The nodes of the line, in order
+    /// # Returns
+    /// * `Vec<(f64, f64)>` - The distance from the first node and the altitude of each node, in meters
+    pub fn elevation_profile(&self, nodes_ids: &[NodeId]) -> Vec<(f64, f64)> {
+        let mut profile = Vec::new();
+        let mut distance = 0.0;
+        let mut prev_node: Option<&Node> = None;
+
+        for node_id in nodes_ids {
+            let node = match self.nodes.get(node_id) {
+                Some(node) => node,
+                None => continue,
+            };
+
+            if let Some(prev_node) = prev_node {
+                if prev_node.id == node.id {
+                    continue;
+                }
+
+                distance += OSMGraph::haversine_distance(
+                    prev_node.lat(),
+                    prev_node.lon(),
+                    node.lat(),
+                    node.lon(),
+                );
+            }
+
+            if let Some(altitude) = self.get_elevation(node.id) {
+                profile.push((distance, altitude));
+            }
+
+            prev_node = Some(node);
+        }
+
+        profile
+    }
+
+    /// Get the bearings at the start and at the end of an edge, from its first and last segments
+    /// # Arguments
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `Option<(f64, f64)>` - The bearings in degrees at the start and at the end of the edge,
+    ///   None if the edge has no length
+    pub fn edge_bearings(&self, edge: &Edge) -> Option<(f64, f64)> {
+        let nodes_ids = if edge.nodes_ids.len() >= 2 {
+            edge.nodes_ids.clone()
+        } else {
+            vec![edge.from, edge.to]
+        };
+
+        let nodes: Vec<&Node> = nodes_ids
+            .iter()
+            .filter_map(|node_id| self.nodes.get(node_id))
+            .collect();
+
+        let bearing =
+            |from: &Node, to: &Node| OSMGraph::bearing(from.lat(), from.lon(), to.lat(), to.lon());
+
+        // the first and last nodes at another position than the ends of the edge
+        let first = nodes.first()?;
+        let last = nodes.last()?;
+        let after_first = nodes
+            .iter()
+            .find(|node| node.lat() != first.lat() || node.lon() != first.lon())?;
+        let before_last = nodes
+            .iter()
+            .rev()
+            .find(|node| node.lat() != last.lat() || node.lon() != last.lon())?;
+
+        Some((bearing(first, after_first), bearing(before_last, last)))
+    }
+
+    /// Detect the curves along the edges of a route that deserve a warning
+    /// # Arguments
+    /// * `edges` - The edges of the route, in order
+    /// # Returns
+    /// * `Vec<CurveWarning>` - The warnings of the curves, their distances start at the first node
+    ///   of the route
+    pub fn route_curve_warnings(&self, edges: &[Edge]) -> Vec<CurveWarning> {
+        let mut nodes: Vec<&Node> = Vec::new();
+        let mut junctions: Vec<bool> = Vec::new();
+        let mut prev_way_id: Option<WayId> = None;
+
+        for edge in edges {
+            for node_id in &edge.nodes_ids {
+                let node = match self.nodes.get(node_id) {
+                    Some(node) => node,
+                    None => continue,
+                };
+
+                // the last node of an edge is the first node of the next one
+                if nodes.last().map(|last| last.id) == Some(node.id) {
+                    if prev_way_id != Some(edge.way_id) {
+                        if let Some(junction) = junctions.last_mut() {
+                            *junction = true;
+                        }
+                    }
+                } else {
+                    nodes.push(node);
+                    junctions.push(false);
+                }
+
+                prev_way_id = Some(edge.way_id);
+            }
+        }
+
+        curve_warnings::curve_warnings(&nodes, &junctions)
+    }
+
     /// Calculate the distance between two nodes using the haversine formula
     /// # Arguments
     /// * `from_lat` - The latitude of the first node
@@ -148,26 +325,373 @@ impl OSMGraph {
         distance * 1000.0 // convert to meters
     }
 
-    /// Get the nearest node from a given latitude and longitude
+    /// Calculate the initial bearing of the great circle going from a point to another
+    /// # Arguments
+    /// * `from_lat` - The latitude of the first point
+    /// * `from_lon` - The longitude of the first point
+    /// * `to_lat` - The latitude of the second point
+    /// * `to_lon` - The longitude of the second point
+    /// # Returns
+    /// * `f64` - The bearing in degrees clockwise from the north, between 0 and 360
+    pub fn bearing(from_lat: f64, from_lon: f64, to_lat: f64, to_lon: f64) -> f64 {
+        let from_lat = from_lat.to_radians();
+        let to_lat = to_lat.to_radians();
+        let delta_lon = (to_lon - from_lon).to_radians();
+
+        let y = delta_lon.sin() * to_lat.cos();
+        let x = from_lat.cos() * to_lat.sin() - from_lat.sin() * to_lat.cos() * delta_lon.cos();
+
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+
+    /// Build the spatial index of the edges of the graph
+    /// Must be called once all the nodes and edges have been added
+    pub fn build_spatial_index(&mut self) {
+        self.edge_index = EdgeIndex::from_graph(self);
+    }
+
+    /// Update the smallest weight per meter of the edges
+    /// Must be called once all the edges have been added
+    pub fn update_min_weight_per_meter(&mut self) {
+        self.min_weight_per_meter = self
+            .edges
+            .iter()
+            .filter(|edge| edge.distance_m > 0.0)
+            .map(|edge| edge.weight / edge.distance_m)
+            .fold(f64::INFINITY, f64::min);
+
+        if !self.min_weight_per_meter.is_finite() {
+            self.min_weight_per_meter = 0.0;
+        }
+    }
+
+    /// Load the contraction hierarchy of the graph from the cache directory, or build and store it
+    /// Must be called once the graph is complete, the hierarchy refers to the edges by index
+    /// # Arguments
+    /// * `cache_dir` - The directory where the hierarchies are stored
+    pub fn load_or_build_contraction_hierarchy(&mut self, cache_dir: &Path) {
+        self.contraction_hierarchy = Some(ContractionHierarchy::load_or_build(self, cache_dir));
+    }
+
+    /// Snap a coordinate onto the nearest edge of the graph
     /// # Arguments
     /// * `lat` - The latitude
     /// * `lon` - The longitude
     /// # Returns
-    /// * `Option<NodeId>` - The id of the nearest node if it exists, None otherwise
-    pub fn get_nearest_node(&self, lat: f64, lon: f64) -> Option<NodeId> {
-        let mut min_distance = std::f64::MAX;
-        let mut nearest_node_id = None;
+    /// * `Option<SnappedPoint>` - The projection of the coordinate onto the nearest edge, None if the graph has no edge
+    pub fn snap_to_edge(&self, lat: f64, lon: f64) -> Option<SnappedPoint> {
+        let projection = self.edge_index.nearest(lat, lon)?;
+        let edge = &self.edges[projection.segment.edge_index];
+
+        let mut offset_m = 0.0;
+
+        for i in 0..projection.segment.segment_index {
+            offset_m += self.get_segment_length(edge.nodes_ids[i], edge.nodes_ids[i + 1]);
+        }
+
+        offset_m += projection.fraction
+            * self.get_segment_length(
+                edge.nodes_ids[projection.segment.segment_index],
+                edge.nodes_ids[projection.segment.segment_index + 1],
+            );
+
+        Some(SnappedPoint {
+            edge_index: projection.segment.edge_index,
+            segment_index: projection.segment.segment_index,
+            lat: projection.lat,
+            lon: projection.lon,
+            offset_m,
+            distance_to_road_m: projection.distance_m,
+        })
+    }
+
+    /// Get the length of the segment between two nodes
+    /// # Arguments
+    /// * `from` - The id of the first node
+    /// * `to` - The id of the second node
+    /// # Returns
+    /// * `f64` - The length of the segment in meters
+    pub fn get_segment_length(&self, from: NodeId, to: NodeId) -> f64 {
+        let from = self.nodes.get(&from).unwrap();
+        let to = self.nodes.get(&to).unwrap();
+
+        OSMGraph::haversine_distance(from.lat(), from.lon(), to.lat(), to.lon())
+    }
+
+    /// Get the length of the geometry of an edge
+    /// # Arguments
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `f64` - The length of the edge in meters
+    pub fn get_edge_length(&self, edge: &Edge) -> f64 {
+        edge.nodes_ids
+            .windows(2)
+            .map(|nodes| self.get_segment_length(nodes[0], nodes[1]))
+            .sum()
+    }
+
+    /// Get the edge going in the opposite direction along the same way
+    /// # Arguments
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `Option<&Edge>` - The reverse edge if the way is not one-way, None otherwise
+    pub fn get_reverse_edge(&self, edge: &Edge) -> Option<&Edge> {
+        self.get_edges_from_node_fast(&edge.to)
+            .iter()
+            .find(|reverse| {
+                reverse.to == edge.from
+                    && reverse.way_id == edge.way_id
+                    && reverse.nodes_ids.len() == edge.nodes_ids.len()
+                    && reverse.nodes_ids.iter().eq(edge.nodes_ids.iter().rev())
+            })
+    }
+
+    /// Get the positions of a snapped point on the edges that can be travelled through it
+    /// The first position is on the snapped edge, the second one on its reverse edge if it exists
+    /// # Arguments
+    /// * `snapped_point` - The snapped point
+    /// # Returns
+    /// * `Vec<EdgePosition>` - The positions of the point on the edges
+    pub fn get_edge_positions(&self, snapped_point: &SnappedPoint) -> Vec<EdgePosition> {
+        let edge = &self.edges[snapped_point.edge_index];
+        let length_m = self.get_edge_length(edge);
+
+        let mut positions = vec![EdgePosition {
+            edge: edge.clone(),
+            segment_index: snapped_point.segment_index,
+            offset_m: snapped_point.offset_m,
+            length_m,
+        }];
+
+        if let Some(reverse) = self.get_reverse_edge(edge) {
+            positions.push(EdgePosition {
+                edge: reverse.clone(),
+                segment_index: edge.nodes_ids.len() - 2 - snapped_point.segment_index,
+                offset_m: length_m - snapped_point.offset_m,
+                length_m,
+            });
+        }
+
+        positions
+    }
+
+    /// Cut an edge between two positions
+    /// The distance of the new edge is the length of its geometry, the weight, the time and the
+    /// curvature score are proportional to the part of the edge that is kept
+    /// # Arguments
+    /// * `position` - Any position on the edge to cut, giving the edge and its length
+    /// * `start` - The segment index and offset in meters where the new edge starts, None to start at the beginning of the edge
+    /// * `end` - The segment index and offset in meters where the new edge ends, None to end at the end of the edge
+    /// # Returns
+    /// * `Edge` - The part of the edge between the two positions. `from` and `to` are the nodes surrounding it
+    pub fn partial_edge(
+        &self,
+        position: &EdgePosition,
+        start: Option<(usize, f64)>,
+        end: Option<(usize, f64)>,
+    ) -> Edge {
+        let edge = &position.edge;
+        let last_segment = edge.nodes_ids.len() - 2;
+
+        let (from_index, start_offset) =
+            start.map_or((0, 0.0), |(segment, offset)| (segment, offset));
+        let (to_index, end_offset) = end.map_or(
+            (last_segment + 1, position.length_m),
+            |(segment, offset)| (segment + 1, offset),
+        );
+
+        // keep the nodes strictly after the start position and strictly before the end position
+        let first_kept = if start.is_some() { from_index + 1 } else { 0 };
+        let last_kept = if end.is_some() {
+            to_index - 1
+        } else {
+            to_index
+        };
+
+        let nodes_ids = if first_kept <= last_kept {
+            edge.nodes_ids[first_kept..=last_kept].to_vec()
+        } else {
+            Vec::new()
+        };
+
+        let distance_m = (end_offset - start_offset).max(0.0);
+        let fraction = if position.length_m > 0.0 {
+            distance_m / position.length_m
+        } else {
+            0.0
+        };
+
+        Edge::new(
+            edge.nodes_ids[from_index],
+            edge.nodes_ids[to_index],
+            distance_m,
+            edge.weight * fraction,
+            edge.time * fraction,
+            nodes_ids,
+            edge.way_id,
+        )
+        .with_curvature(edge.curvature * fraction)
+        .with_access(edge.access.clone())
+    }
+
+    /// Add the snapped start and end points to a route built by `directions_instructions_and_path`
+    /// The snapped points are added at the beginning and at the end of the path, and their
+    /// distances to the road are reported. The distances of the elevation profile are shifted to
+    /// start at the start point
+    /// # Arguments
+    /// * `directions` - The path and directions of the route
+    /// * `start` - The snapped start point
+    /// * `end` - The snapped end point
+    pub fn add_snapped_points(
+        &self,
+        directions: &mut Directions,
+        start: &SnappedPoint,
+        end: &SnappedPoint,
+    ) {
+        // the elevation profile starts at the first node of the path, before the start point
+        let offset = directions.path.first().map_or(0.0, |first_point| {
+            OSMGraph::haversine_distance(
+                start.lat,
+                start.lon,
+                first_point.latitude,
+                first_point.longitude,
+            )
+        });
+
+        if let Some(elevation) = directions.elevation.as_mut() {
+            for point in elevation.profile.iter_mut() {
+                point.0 += offset;
+            }
+        }
+
+        directions.path.insert(
+            0,
+            Location {
+                latitude: start.lat,
+                longitude: start.lon,
+            },
+        );
+        directions.path.push(Location {
+            latitude: end.lat,
+            longitude: end.lon,
+        });
+
+        directions.snapped_start = Some(SnappedLocation::new(start));
+        directions.snapped_end = Some(SnappedLocation::new(end));
+    }
+
+    /// Combine the legs of a route through waypoints into a single route
+    /// Each leg is a route built by `directions_instructions_and_path` with its snapped points.
+    /// An arrival instruction is added at the end of each leg, and each leg is summarized in `legs`.
+    /// The elevation profiles of the legs are joined, and the statistics of the elevation profile
+    /// are computed again
+    /// # Arguments
+    /// * `legs` - The path and directions of the legs of the route, in order
+    /// # Returns
+    /// * `Route` - The route
+    pub fn combine_legs(&self, legs: Vec<Directions>) -> Route {
+        let leg_count = legs.len();
+        let snapped_start = legs.first().and_then(|leg| leg.snapped_start);
+        let snapped_end = legs.last().and_then(|leg| leg.snapped_end);
 
-        for (node_id, node) in &self.nodes {
-            let distance = OSMGraph::haversine_distance(lat, lon, node.lat(), node.lon());
+        let mut path: Vec<Location> = Vec::new();
+        let mut instructions: Vec<Maneuver> = Vec::new();
+        let mut summaries: Vec<LegSummary> = Vec::new();
 
-            if distance < min_distance {
-                min_distance = distance;
-                nearest_node_id = Some(*node_id);
+        let mut total_distance = 0.0;
+        let mut total_time = 0.0;
+        let mut total_curvature = 0.0;
+        let mut elevation_profile: Vec<(f64, f64)> = Vec::new();
+
+        for (index, leg) in legs.into_iter().enumerate() {
+            // the first point of a leg is the last point of the previous leg
+            let skip = if path.is_empty() { 0 } else { 1 };
+            path.extend(leg.path.into_iter().skip(skip));
+
+            // distance and time between the last instruction of the leg and its end
+            let instructed_distance: f64 = leg
+                .instructions
+                .iter()
+                .map(|instruction| instruction.distance)
+                .sum();
+            let instructed_time: f64 = leg
+                .instructions
+                .iter()
+                .map(|instruction| instruction.duration)
+                .sum();
+
+            instructions.extend(leg.instructions);
+
+            let location = leg.snapped_end.map_or(
+                Location {
+                    latitude: 0.0,
+                    longitude: 0.0,
+                },
+                |end| Location {
+                    latitude: end.latitude,
+                    longitude: end.longitude,
+                },
+            );
+
+            let arrival = if index == leg_count - 1 {
+                Maneuver::new(
+                    ManeuverType::Arrive,
+                    (leg.total_distance - instructed_distance).max(0.0),
+                    (leg.total_time - instructed_time).max(0.0),
+                    location,
+                )
+            } else {
+                Maneuver::new(
+                    ManeuverType::Waypoint,
+                    (leg.total_distance - instructed_distance).max(0.0),
+                    (leg.total_time - instructed_time).max(0.0),
+                    location,
+                )
+                .with_waypoint(index + 1)
+            };
+
+            instructions.push(arrival);
+
+            summaries.push(LegSummary {
+                start: leg.snapped_start,
+                end: leg.snapped_end,
+                distance: leg.total_distance,
+                time: leg.total_time,
+                curvature: leg.total_curvature,
+            });
+
+            if let Some(elevation) = leg.elevation {
+                elevation_profile.extend(
+                    elevation
+                        .profile
+                        .into_iter()
+                        .map(|(point_distance, altitude)| {
+                            (total_distance + point_distance, altitude)
+                        }),
+                );
             }
+
+            total_distance += leg.total_distance;
+            total_time += leg.total_time;
+            total_curvature += leg.total_curvature;
         }
 
-        nearest_node_id
+        Route {
+            directions: Directions {
+                path,
+                instructions,
+                total_distance,
+                total_time,
+                total_curvature,
+                elevation: ElevationProfile::new(elevation_profile),
+                snapped_start,
+                snapped_end,
+            },
+            legs: summaries,
+            order: None,
+            stretch: None,
+            overlap: None,
+        }
     }
 
     /// Reconstruction of the path from the visited nodes
@@ -190,19 +714,28 @@ impl OSMGraph {
     }
 
     /// Reconstruction of the path and directions instructions from the visited nodes and edges
+    /// The instructions are maneuvers without text, see `maneuver::add_texts`. The elevation
+    /// profile and the climb statistics of the path are given in `elevation`, which is None if
+    /// the altitude of the nodes is unknown. The hairpins and sharp curves along the path are
+    /// curve warnings between the maneuvers at the junctions
     /// # Arguments
     /// * `visited_nodes` - The visited nodes
     /// * `visited_edges` - The visited edges
+    /// * `turn_thresholds` - The thresholds classifying the turns at the junctions
     /// # Returns
-    /// * `serde_json::Value` - The path and directions instructions in json format
+    /// * `Directions` - The path and directions instructions, without snapped points
     pub fn directions_instructions_and_path(
         &self,
         visited_nodes: &Vec<NodeId>,
         visited_edges: &Vec<Edge>,
-    ) -> serde_json::Value {
+        turn_thresholds: &TurnThresholds,
+    ) -> Directions {
         let mut path = Vec::new();
-        let mut instructions: Vec<(String, f64, f64, f64)> = Vec::new();
+        let mut path_nodes: Vec<NodeId> = Vec::new();
+        let mut instructions: Vec<Maneuver> = Vec::new();
         let mut total_distance = 0.0;
+        let mut total_time = 0.0;
+        let mut total_curvature = 0.0;
 
         let mut prev_edge = visited_edges.first().unwrap();
 
@@ -210,22 +743,45 @@ impl OSMGraph {
         let mut is_prev_roundabout = false;
 
         let mut distance_since_last_instruction = 0.0;
+        let mut time_since_last_instruction = 0.0;
 
         // iter over visited edges and add the nodes coordinates to the path
         for i in 0..visited_edges.len() {
             for node_id in &visited_edges[i].nodes_ids {
                 let node = self.nodes.get(node_id).unwrap();
-                path.push(json!({"latitude": node.lat(), "longitude": node.lon()}));
+                path.push(Location {
+                    latitude: node.lat(),
+                    longitude: node.lon(),
+                });
+                path_nodes.push(*node_id);
             }
             total_distance += visited_edges[i].distance_m;
-
-            distance_since_last_instruction += visited_edges[i].distance_m;
+            total_time += visited_edges[i].time;
+            total_curvature += visited_edges[i].curvature;
 
             // Find way
             let way = self.ways.get(&visited_edges[i].way_id).unwrap();
 
             // get edge node lat and lon
             let edge_first_node = self.nodes.get(&visited_edges[i].from).unwrap();
+            let location = Location {
+                latitude: edge_first_node.lat(),
+                longitude: edge_first_node.lon(),
+            };
+
+            // bearings of the route before and after the first node of the edge
+            let bearing_before = self.edge_bearings(prev_edge).map(|(_, end)| end);
+            let bearing_after = self
+                .edge_bearings(&visited_edges[i])
+                .map(|(start, _)| start);
+
+            // direction of the route at the first node of the edge
+            let modifier = match (bearing_before, bearing_after) {
+                (Some(before), Some(after)) => {
+                    Some(turn_thresholds.classify(turns::turn_angle(before, after)))
+                }
+                _ => None,
+            };
 
             // print if way is intersection or roundabout
             if (way.tags.contains_key("highway") && way.tags["highway"] == "motorway_link")
@@ -240,16 +796,20 @@ impl OSMGraph {
                 is_prev_roundabout = true;
             } else {
                 if (is_prev_roundabout) {
-                    let instruction = format!("Roundabout exit: {}", roundabout_exit_counter);
-
-                    instructions.push((
-                        instruction,
-                        distance_since_last_instruction,
-                        edge_first_node.lat(),
-                        edge_first_node.lon(),
-                    ));
+                    instructions.push(
+                        Maneuver::new(
+                            ManeuverType::Roundabout,
+                            distance_since_last_instruction,
+                            time_since_last_instruction,
+                            location,
+                        )
+                        .on_way(way)
+                        .with_exit(roundabout_exit_counter)
+                        .with_bearings(bearing_before, bearing_after),
+                    );
 
                     distance_since_last_instruction = 0.0;
+                    time_since_last_instruction = 0.0;
 
                     roundabout_exit_counter = 0;
                     is_prev_roundabout = false;
@@ -261,73 +821,69 @@ impl OSMGraph {
                         if prev_way.tags.contains_key("name")
                             && prev_way.tags["name"] == way.tags["name"]
                         {
-                            let instruction = format!("Continue on road {}", way.tags["name"]);
-                            instructions.push((
-                                instruction,
-                                distance_since_last_instruction,
-                                edge_first_node.lat(),
-                                edge_first_node.lon(),
-                            ));
+                            instructions.push(
+                                Maneuver::new(
+                                    ManeuverType::Continue,
+                                    distance_since_last_instruction,
+                                    time_since_last_instruction,
+                                    location,
+                                )
+                                .on_way(way)
+                                .with_modifier(modifier)
+                                .with_bearings(bearing_before, bearing_after),
+                            );
                             distance_since_last_instruction = 0.0;
+                            time_since_last_instruction = 0.0;
                         } else {
-                            let prev_edge_last_node = self.nodes.get(&prev_edge.to).unwrap();
-                            let prev_edge_first_node = self.nodes.get(&prev_edge.from).unwrap();
-                            let current_edge_first_node =
-                                self.nodes.get(&visited_edges[i].from).unwrap();
-                            let current_edge_last_node =
-                                self.nodes.get(&visited_edges[i].to).unwrap();
-
-                            let prev_edge_angle = (prev_edge_last_node.lat()
-                                - prev_edge_first_node.lat())
-                            .atan2(prev_edge_last_node.lon() - prev_edge_first_node.lon())
-                            .to_degrees();
-
-                            let current_edge_angle = (current_edge_last_node.lat()
-                                - current_edge_first_node.lat())
-                            .atan2(current_edge_last_node.lon() - current_edge_first_node.lon())
-                            .to_degrees();
-
-                            let angle = current_edge_angle - prev_edge_angle;
-
-                            let mut instruction = String::new();
-
-                            // check the angle and determine if it is a left or right turn
-                            if angle > 0.0 && angle < 180.0 {
-                                //println!("Turn left to road {}", way.tags["name"]);
-                                instruction = format!("Turn left to road {}", way.tags["name"]);
-                            } else if angle < 0.0 && angle > -180.0 {
-                                //println!("Turn right to road {}", way.tags["name"]);
-                                instruction = format!("Turn right to road {}", way.tags["name"]);
-                            } else if angle > 180.0 && angle < 360.0 {
-                                //println!("Turn right to road {}", way.tags["name"]);
-                                instruction = format!("Turn right to road {}", way.tags["name"]);
-                            } else if angle < -180.0 && angle > -360.0 {
-                                //println!("Turn left to road {}", way.tags["name"]);
-                                instruction = format!("Turn left to road {}", way.tags["name"]);
+                            // straight on onto another road is a continuation, not a turn
+                            let maneuver_type = if modifier == Some(ManeuverModifier::Straight) {
+                                ManeuverType::Continue
                             } else {
-                                //println!("Turn");
-                                instruction = format!("Turn");
-                            }
-                            instructions.push((
-                                instruction,
-                                distance_since_last_instruction,
-                                edge_first_node.lat(),
-                                edge_first_node.lon(),
-                            ));
+                                ManeuverType::Turn
+                            };
+
+                            instructions.push(
+                                Maneuver::new(
+                                    maneuver_type,
+                                    distance_since_last_instruction,
+                                    time_since_last_instruction,
+                                    location,
+                                )
+                                .on_way(way)
+                                .with_modifier(modifier)
+                                .with_bearings(bearing_before, bearing_after),
+                            );
                             distance_since_last_instruction = 0.0;
+                            time_since_last_instruction = 0.0;
                         }
                     }
                 }
             }
 
+            // the maneuvers at the first node of the edge are before it
+            distance_since_last_instruction += visited_edges[i].distance_m;
+            time_since_last_instruction += visited_edges[i].time;
+
             prev_edge = &visited_edges[i];
         }
 
-        // add the last node to the path
-        let json_obj =
-            json!({ "path": path, "instructions": instructions, "total_distance": total_distance });
-
-        json_obj
+        let instructions = curve_warnings::add_to_instructions(
+            instructions,
+            &self.route_curve_warnings(visited_edges),
+            total_distance,
+            total_time,
+        );
+
+        Directions {
+            path,
+            instructions,
+            total_distance,
+            total_time,
+            total_curvature,
+            elevation: ElevationProfile::new(self.elevation_profile(&path_nodes)),
+            snapped_start: None,
+            snapped_end: None,
+        }
     }
 
     /// Combine two paths
@@ -347,33 +903,6 @@ impl OSMGraph {
         combined_path
     }
 
-    /// Get the edges that start from a node or contain it
-    /// # Arguments
-    /// * `node_id` - The id of the node
-    /// # Returns
-    /// * `Vec<&Edge>` - A vector of references to the edges that start from the node or contain it
-    pub fn get_edges_from_node_or_containing(&self, node_id: NodeId) -> Vec<&Edge> {
-        let mut edges = Vec::new();
-
-        for edge in &self.edges {
-            if edge.from == node_id {
-                edges.push(edge);
-            }
-        }
-
-        if (edges.len() == 0) {
-            for edge in &self.edges {
-                for n_id in &edge.nodes_ids {
-                    if node_id == *n_id {
-                        edges.push(edge);
-                    }
-                }
-            }
-        }
-
-        edges
-    }
-
     /// Get the edges that start from a node
     /// # Arguments
     /// * `node_id` - The id of the node
@@ -411,51 +940,70 @@ impl OSMGraph {
         edges.unwrap_or(&self.empty_edges)
     }
 
+    /// Add an edge and the node where it ends to the graph
+    /// # Arguments
+    /// * `node_id` - The id of the node where the edge ends
+    /// * `edge` - The edge to add
+    pub fn add_edge_to_node(&mut self, node_id: NodeId, edge: Edge) {
+        let edges = self.edges_to_node.entry(node_id).or_default();
+        edges.push(edge);
+    }
+
     /// Get the edges that end to a node
     /// # Arguments
     /// * `node_id` - The id of the node
     /// # Returns
-    /// * `Vec<&Edge>` - A vector of references to the edges that end to the node
-    pub fn get_edges_to_node(&self, node_id: NodeId) -> Vec<&Edge> {
-        let mut edges = Vec::new();
+    /// * `&Vec<Edge>` - A reference to the vector of edges that end to the node
+    pub fn get_edges_to_node_fast(&self, node_id: &NodeId) -> &Vec<Edge> {
+        let edges = self.edges_to_node.get(node_id);
 
-        for edge in &self.edges {
-            if edge.to == node_id {
-                edges.push(edge);
-            }
-        }
+        edges.unwrap_or(&self.empty_edges)
+    }
 
-        edges
+    /// Get the edges that end to a node
+    /// # Arguments
+    /// * `node_id` - The id of the node
+    /// # Returns
+    /// * `Vec<&Edge>` - A vector of references to the edges that end to the node
+    pub fn get_edges_to_node(&self, node_id: NodeId) -> Vec<&Edge> {
+        self.get_edges_to_node_fast(&node_id).iter().collect()
     }
 
-    /// calculate the radius of the circle that contains the given three points
+    /// Calculate the radius of the circle that contains the given three points
+    /// The points are projected around the second node, so that the radius is in meters
     /// # Arguments
     /// * `node_1` - The first node
     /// * `node_2` - The second node
     /// * `node_3` - The third node
     /// # Returns
-    /// * `f64` - The radius of the circle
+    /// * `f64` - The radius of the circle in meters, infinite if the points are aligned
     pub fn circle_radius(node_1: &Node, node_2: &Node, node_3: &Node) -> f64 {
-        let lat_1 = node_1.lat();
-        let lon_1 = node_1.lon();
-
-        let lat_2 = node_2.lat();
-        let lon_2 = node_2.lon();
+        let meters_per_degree = 6371.0 * 1000.0 * std::f64::consts::PI / 180.0;
+        let cos_lat = node_2.lat().to_radians().cos();
 
-        let lat_3 = node_3.lat();
-        let lon_3 = node_3.lon();
+        // local equirectangular projection centered on the second node
+        let project = |node: &Node| {
+            (
+                (node.lon() - node_2.lon()) * cos_lat * meters_per_degree,
+                (node.lat() - node_2.lat()) * meters_per_degree,
+            )
+        };
 
-        let a = (lat_1 - lat_2) * (lat_1 - lat_2) + (lon_1 - lon_2) * (lon_1 - lon_2);
-        let b = (lat_2 - lat_3) * (lat_2 - lat_3) + (lon_2 - lon_3) * (lon_2 - lon_3);
-        let c = (lat_3 - lat_1) * (lat_3 - lat_1) + (lon_3 - lon_1) * (lon_3 - lon_1);
+        let (x_1, y_1) = project(node_1);
+        let (x_3, y_3) = project(node_3);
 
-        let s = 0.5 * (a + b + c);
+        let a = (x_1 * x_1 + y_1 * y_1).sqrt();
+        let b = (x_3 * x_3 + y_3 * y_3).sqrt();
+        let c = ((x_3 - x_1) * (x_3 - x_1) + (y_3 - y_1) * (y_3 - y_1)).sqrt();
 
-        let area = (s * (s - a) * (s - b) * (s - c)).sqrt();
+        // twice the area of the triangle
+        let cross = (x_1 * y_3 - y_1 * x_3).abs();
 
-        let radius = a * b * c / (4.0 * area);
+        if cross == 0.0 {
+            return f64::INFINITY;
+        }
 
-        radius.sqrt()
+        a * b * c / (2.0 * cross)
     }
 }
 
@@ -465,10 +1013,12 @@ impl OSMGraph {
 /// * `to` - The id of the node where the edge ends
 /// * `distance_m` - The distance of the edge in meters
 /// * `weight` - The weight of the edge
-/// * `time` - The time needed to traverse the edge
+/// * `time` - The time needed to traverse the edge in seconds
 /// * `nodes_ids` - The ids of the nodes that the edge contains
 /// * `way_id` - The id of the way that the edge belongs to
-#[derive(Debug, Clone)]
+/// * `curvature` - The curvature score of the edge in meters, see `curvature::curvature_score`
+/// * `access` - The access of motorcycles to the edge, with its conditional restrictions
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Edge {
     pub from: NodeId,
     pub to: NodeId,
@@ -477,6 +1027,8 @@ pub struct Edge {
     pub time: f64,
     pub nodes_ids: Vec<NodeId>,
     pub way_id: WayId,
+    pub curvature: f64,
+    pub access: EdgeAccess,
 }
 
 /// Edge implementation
@@ -487,7 +1039,7 @@ impl Edge {
     /// * `to` - The id of the node where the edge ends
     /// * `distance_m` - The distance of the edge in meters
     /// * `weight` - The weight of the edge
-    /// * `time` - The time needed to traverse the edge
+    /// * `time` - The time needed to traverse the edge in seconds
     /// * `nodes_ids` - The ids of the nodes that the edge contains
     /// * `way_id` - The id of the way that the edge belongs to
     pub fn new(
@@ -507,8 +1059,84 @@ impl Edge {
             time,
             nodes_ids,
             way_id,
+            curvature: 0.0,
+            access: EdgeAccess::default(),
         }
     }
+
+    /// Get the key identifying the edge, its end nodes and its way
+    /// # Returns
+    /// * `(NodeId, NodeId, WayId)` - The key of the edge
+    pub fn key(&self) -> (NodeId, NodeId, WayId) {
+        (self.from, self.to, self.way_id)
+    }
+
+    /// Set the curvature score of the edge
+    /// # Arguments
+    /// * `curvature` - The curvature score of the edge in meters
+    /// # Returns
+    /// * `Edge` - The edge with the curvature score
+    pub fn with_curvature(mut self, curvature: f64) -> Self {
+        self.curvature = curvature;
+        self
+    }
+
+    /// Set the access of motorcycles to the edge
+    /// # Arguments
+    /// * `access` - The access of the edge
+    /// # Returns
+    /// * `Edge` - The edge with the access
+    pub fn with_access(mut self, access: EdgeAccess) -> Self {
+        self.access = access;
+        self
+    }
+}
+
+/// Coordinate snapped onto an edge of the graph
+/// # Fields
+/// * `edge_index` - The index of the edge in `OSMGraph::edges`
+/// * `segment_index` - The index of the segment of the edge containing the point
+/// * `lat` - The latitude of the point on the edge
+/// * `lon` - The longitude of the point on the edge
+/// * `offset_m` - The distance along the edge from its first node to the point in meters
+/// * `distance_to_road_m` - The distance between the input coordinate and the point in meters
+#[derive(Debug, Clone)]
+pub struct SnappedPoint {
+    pub edge_index: usize,
+    pub segment_index: usize,
+    pub lat: f64,
+    pub lon: f64,
+    pub offset_m: f64,
+    pub distance_to_road_m: f64,
+}
+
+/// Position on a directed edge
+/// # Fields
+/// * `edge` - The edge
+/// * `segment_index` - The index of the segment of the edge containing the position
+/// * `offset_m` - The distance along the edge from its first node to the position in meters
+/// * `length_m` - The length of the geometry of the edge in meters
+#[derive(Debug, Clone)]
+pub struct EdgePosition {
+    pub edge: Edge,
+    pub segment_index: usize,
+    pub offset_m: f64,
+    pub length_m: f64,
+}
+
+/// EdgePosition implementation
+impl EdgePosition {
+    /// Check if two positions are on the same directed edge
+    /// # Arguments
+    /// * `other` - The other position
+    /// # Returns
+    /// * `bool` - True if both positions are on the same edge
+    pub fn is_same_edge(&self, other: &EdgePosition) -> bool {
+        self.edge.from == other.edge.from
+            && self.edge.to == other.edge.to
+            && self.edge.way_id == other.edge.way_id
+            && self.edge.nodes_ids == other.edge.nodes_ids
+    }
 }
 
 /// State of the graph
@@ -551,3 +1179,93 @@ impl Ord for State {
         self.partial_cmp(&other).unwrap_or(Ordering::Equal)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::{add_road, node};
+
+    /// Build a junction reached from the south by the way 1, left by the way 2 at a given bearing,
+    /// and get the maneuver at the junction
+    /// # Arguments
+    /// * `bearing` - The bearing of the way 2 in degrees clockwise from the north
+    /// * `turn_thresholds` - The thresholds classifying the turns
+    /// # Returns
+    /// * `Maneuver` - The maneuver onto the way 2
+    fn maneuver_at_junction(bearing: f64, turn_thresholds: &TurnThresholds) -> Maneuver {
+        let mut graph = OSMGraph::new();
+        let lon_scale = 47.0_f64.to_radians().cos();
+
+        graph.add_node(&node(1, 46.99, 7.0));
+        graph.add_node(&node(2, 47.0, 7.0));
+        graph.add_node(&node(
+            3,
+            47.0 + 0.01 * bearing.to_radians().cos(),
+            7.0 + 0.01 * bearing.to_radians().sin() / lon_scale,
+        ));
+
+        add_road(&mut graph, 1, &[1, 2]);
+        add_road(&mut graph, 2, &[2, 3]);
+
+        let edges: Vec<Edge> = [(1, 1), (2, 2)]
+            .iter()
+            .map(|(way_id, from)| {
+                graph
+                    .get_edges()
+                    .iter()
+                    .find(|edge| edge.way_id == WayId(*way_id) && edge.from == NodeId(*from))
+                    .unwrap()
+                    .clone()
+            })
+            .collect();
+
+        let directions = graph.directions_instructions_and_path(
+            &vec![NodeId(1), NodeId(2), NodeId(3)],
+            &edges,
+            turn_thresholds,
+        );
+
+        assert_eq!(directions.instructions.len(), 1);
+
+        directions.instructions[0].clone()
+    }
+
+    #[test]
+    fn junction_modifiers() {
+        let thresholds = TurnThresholds::default();
+
+        let cases = [
+            (5.0, ManeuverType::Continue, ManeuverModifier::Straight),
+            (-10.0, ManeuverType::Continue, ManeuverModifier::Straight),
+            (40.0, ManeuverType::Turn, ManeuverModifier::SlightRight),
+            (-40.0, ManeuverType::Turn, ManeuverModifier::SlightLeft),
+            (90.0, ManeuverType::Turn, ManeuverModifier::Right),
+            (-90.0, ManeuverType::Turn, ManeuverModifier::Left),
+            (150.0, ManeuverType::Turn, ManeuverModifier::SharpRight),
+            (-150.0, ManeuverType::Turn, ManeuverModifier::SharpLeft),
+            (176.0, ManeuverType::Turn, ManeuverModifier::UTurn),
+        ];
+
+        for (bearing, maneuver_type, modifier) in cases {
+            let maneuver = maneuver_at_junction(bearing, &thresholds);
+
+            assert_eq!(maneuver.maneuver_type, maneuver_type, "{}", bearing);
+            assert_eq!(maneuver.modifier, Some(modifier), "{}", bearing);
+            assert_eq!(maneuver.name.as_deref(), Some("road 2"));
+            assert_eq!(maneuver.bearing_before.map(f64::round), Some(0.0));
+        }
+    }
+
+    #[test]
+    fn junction_modifiers_follow_the_thresholds() {
+        let thresholds = TurnThresholds {
+            straight: 45.0,
+            ..TurnThresholds::default()
+        };
+
+        let maneuver = maneuver_at_junction(40.0, &thresholds);
+
+        assert_eq!(maneuver.maneuver_type, ManeuverType::Continue);
+        assert_eq!(maneuver.modifier, Some(ManeuverModifier::Straight));
+    }
+}
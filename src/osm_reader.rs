@@ -1,47 +1,136 @@
 extern crate osmpbfreader;
+use crate::access::EdgeAccess;
+use crate::curvature;
+use crate::elevation::ElevationModel;
+use crate::graph_cache::{self, GraphCacheHeader};
+use crate::oneway::Directions;
 use crate::osm_graph::Edge;
 use crate::osm_graph::OSMGraph;
-use log::info;
-use osmpbfreader::objects::{NodeId, Way};
+use crate::road_filter::RoadFilter;
+use crate::speed;
+use crate::turn_restrictions::{RestrictionVia, TurnRestriction};
+use log::{info, warn};
+use osmpbfreader::objects::{Node, NodeId, OsmObj, Way};
 use osmpbfreader::OsmPbfReader;
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 
 /// OSMReader is used to read an PBF file containing OSM data and build a graph from it
 /// # Arguments
 /// * `file_path` - The path of the PBF file
+/// * `road_filter` - The filter of the ways kept in the graph
+/// * `elevation_dir` - The directory of the SRTM tiles giving the altitude of the nodes, None to build the graph without altitudes
 pub struct OSMReader {
     file_path: String,
+    road_filter: RoadFilter,
+    elevation_dir: Option<PathBuf>,
 }
 
 /// OSMReader implementation
 impl OSMReader {
-    /// Create a new OSMReader
+    /// Create a new OSMReader with the default road filter
     /// # Arguments
     /// * `file_path` - The path of the PBF file
     pub fn new(file_path: String) -> Self {
+        OSMReader::with_road_filter(file_path, RoadFilter::default())
+    }
+
+    /// Create a new OSMReader keeping the ways accepted by a road filter
+    /// # Arguments
+    /// * `file_path` - The path of the PBF file
+    /// * `road_filter` - The filter of the ways kept in the graph
+    pub fn with_road_filter(file_path: String, road_filter: RoadFilter) -> Self {
         OSMReader {
-            file_path: file_path,
+            file_path,
+            road_filter,
+            elevation_dir: None,
+        }
+    }
+
+    /// Set the directory of the SRTM `.hgt` tiles used to give an altitude to the nodes
+    /// # Arguments
+    /// * `elevation_dir` - The directory of the tiles
+    pub fn set_elevation_dir(&mut self, elevation_dir: PathBuf) {
+        self.elevation_dir = Some(elevation_dir);
+    }
+
+    /// Get the build settings of the graph, a graph can only be reused with the same settings
+    /// # Returns
+    /// * `String` - The build settings
+    pub fn settings(&self) -> String {
+        let mut settings = format!("road_filter={}", self.road_filter.settings());
+
+        // the tiles are keyed by name and size, to rebuild the graph when they change
+        if let Some(elevation_dir) = &self.elevation_dir {
+            let tiles: Vec<String> = ElevationModel::tile_files(elevation_dir)
+                .iter()
+                .map(|(_, file_path)| {
+                    let size = file_path.metadata().map_or(0, |metadata| metadata.len());
+                    format!(
+                        "{}:{}",
+                        file_path.file_name().unwrap_or_default().to_string_lossy(),
+                        size
+                    )
+                })
+                .collect();
+
+            settings.push_str(&format!(";elevation={}", tiles.join(",")));
+        }
+
+        settings
+    }
+
+    /// Load the graph of the PBF file from the cache directory, or build it and store it
+    /// The cache file is keyed by the hash of the PBF file and by the build settings
+    /// # Arguments
+    /// * `cache_dir` - The directory of the cache files
+    /// # Returns
+    /// * `OSMGraph` - The graph of the PBF file
+    pub async fn load_or_build_graph(&mut self, cache_dir: &Path) -> OSMGraph {
+        let start_time = std::time::Instant::now();
+
+        let pbf_hash = match graph_cache::file_hash(Path::new(&self.file_path)) {
+            Ok(pbf_hash) => pbf_hash,
+            Err(error) => {
+                warn!("Unable to hash {}: {}", self.file_path, error);
+                return self.build_graph().await;
+            }
+        };
+
+        let settings = self.settings();
+        let header = GraphCacheHeader::new(&pbf_hash, &settings);
+        let cache_file = graph_cache::cache_file_path(cache_dir, &pbf_hash, &settings);
+
+        if let Some(graph) = graph_cache::load_graph(&cache_file, &header) {
+            info!(
+                "Graph loaded from {:?} in {} ms",
+                cache_file,
+                start_time.elapsed().as_millis()
+            );
+            println!(
+                "Graph loaded from {:?} in {} ms",
+                cache_file,
+                start_time.elapsed().as_millis()
+            );
+
+            return graph;
+        }
+
+        let graph = self.build_graph().await;
+
+        if let Err(error) = graph_cache::save_graph(&cache_file, &header, &graph) {
+            warn!("Failed to save graph: {}", error);
         }
+
+        graph
     }
 
     /// Build a graph from the PBF file
     /// # Returns
     /// * `OSMGraph` - The graph built from the PBF file
     pub async fn build_graph(&mut self) -> OSMGraph {
-        let highway_to_keep = vec![
-            "primary",
-            "secondary",
-            "tertiary",
-            "unclassified",
-            "residential",
-            "primary_link",
-            "secondary_link",
-            "tertiary_link",
-            "living_street",
-        ];
-
         let file = File::open(&self.file_path).expect("Unable to open file");
         let reader = BufReader::new(file);
         let mut pbf_reader = OsmPbfReader::new(reader);
@@ -49,16 +138,13 @@ impl OSMReader {
         let start_time = std::time::Instant::now();
 
         let mut osm_graph = OSMGraph::new();
+        osm_graph.metadata.road_filter = self.road_filter.clone();
 
         info!("Start reading file...");
         println!("Start reading file...");
 
         let objs = pbf_reader
-            .get_objs_and_deps(|obj| {
-                obj.is_way()
-                    && obj.tags().contains_key("highway")
-                    && highway_to_keep.contains(&obj.tags()["highway"].as_str())
-            })
+            .get_objs_and_deps(|obj| obj.is_way() && self.road_filter.accepts(obj.tags()))
             .unwrap();
 
         info!("Read file in {} seconds", start_time.elapsed().as_secs());
@@ -90,208 +176,66 @@ impl OSMReader {
         }
 
         for way in &ways {
-            let mut source = NodeId(0);
-            let mut one_way = way.tags.contains_key("oneway") && way.tags["oneway"] == "yes";
+            OSMReader::add_way_edges(&mut osm_graph, way, &link_counter);
+        }
 
-            let roundabout =
-                way.tags.contains_key("junction") && way.tags["junction"] == "roundabout";
+        info!("Build graph in {} seconds", start_time.elapsed().as_secs());
+        println!("Build graph in {} seconds", start_time.elapsed().as_secs());
 
-            if roundabout {
-                one_way = true;
-            }
+        let start_time = std::time::Instant::now();
 
-            for node in &way.nodes {
-                if node == &way.nodes[0] {
-                    source = *node;
-                }
+        OSMReader::read_turn_restrictions(&mut pbf_reader, &mut osm_graph);
+
+        info!(
+            "Read {} turn restrictions in {} seconds",
+            osm_graph.turn_restrictions.len(),
+            start_time.elapsed().as_secs()
+        );
+        println!(
+            "Read {} turn restrictions in {} seconds",
+            osm_graph.turn_restrictions.len(),
+            start_time.elapsed().as_secs()
+        );
+
+        if let Some(elevation_dir) = &self.elevation_dir {
+            let start_time = std::time::Instant::now();
+
+            let points: Vec<(f64, f64)> = osm_graph
+                .get_nodes()
+                .values()
+                .map(|node| (node.lat(), node.lon()))
+                .collect();
+            let elevation_model = ElevationModel::load(elevation_dir, points);
+
+            osm_graph.update_elevations(&elevation_model);
+
+            info!(
+                "Read altitudes of {} nodes from {} tiles in {} seconds",
+                osm_graph.elevations.len(),
+                elevation_model.tiles.len(),
+                start_time.elapsed().as_secs()
+            );
+            println!(
+                "Read altitudes of {} nodes from {} tiles in {} seconds",
+                osm_graph.elevations.len(),
+                elevation_model.tiles.len(),
+                start_time.elapsed().as_secs()
+            );
+        }
 
-                if node != &way.nodes[0] && node != &way.nodes[way.nodes.len() - 1] {
-                    if link_counter[node] > 1 {
-                        let index = way.nodes.iter().position(|x| *x == *node).unwrap();
-
-                        let mut way1 = way.clone();
-
-                        let source_index = way.nodes.iter().position(|x| *x == source).unwrap();
-                        way1.nodes = way.nodes[source_index..index + 1].to_vec();
-
-                        let mut weight = 1.0;
-                        let mut distance = 0.0;
-
-                        for i in 0..way1.nodes.len() - 1 {
-                            let node1 = osm_graph.get_node(way1.nodes[i]).unwrap();
-                            let node2 = osm_graph.get_node(way1.nodes[i + 1]).unwrap();
-
-                            let distance1 = OSMGraph::haversine_distance(
-                                node1.lat(),
-                                node1.lon(),
-                                node2.lat(),
-                                node2.lon(),
-                            );
-
-                            distance += distance1;
-                        }
-
-                        let mut CURVY = 0.0;
-                        let mut MIDDLE = 0.0;
-                        let mut STRAIGHT = 0.0;
-
-                        for i in 0..way1.nodes.len() - 2 {
-                            let node1 = osm_graph.get_node(way1.nodes[i]).unwrap();
-                            let node2 = osm_graph.get_node(way1.nodes[i + 1]).unwrap();
-                            let node3 = osm_graph.get_node(way1.nodes[i + 2]).unwrap();
-
-                            let radius = OSMGraph::circle_radius(node1, node2, node3);
-
-                            // attributes a weight to the edge based on the radius : small curve, big curve, straight line
-                            if (radius < 100.0) {
-                                weight += 0.0; // * total_distance;
-                                CURVY += 1.0;
-                            } else if (radius < 200.0) {
-                                weight += 0.0; // * total_distance;
-                                MIDDLE += 1.0;
-                            } else {
-                                weight += 0.0; //* total_distance;
-                                STRAIGHT += 1.0;
-                            }
-                        }
-
-                        // calculate weight based on the number of curves (CURVY, MIDDLE, STRAIGHT) and the distance
-                        if (CURVY > MIDDLE && CURVY > STRAIGHT) {
-                            weight *= 0.5;
-                        } else if (MIDDLE > CURVY && MIDDLE > STRAIGHT) {
-                            weight *= 1.0;
-                        } else {
-                            weight *= 1.5;
-                        }
-
-                        weight *= distance;
-
-                        let edge = Edge::new(
-                            source,
-                            *node,
-                            distance,
-                            weight,
-                            0.0,
-                            way1.nodes.clone(),
-                            way1.id,
-                        );
-
-                        osm_graph.add_edge(edge.clone());
-
-                        osm_graph.add_edge_from_node(source, edge.clone());
-
-                        let nodes = way1
-                            .nodes
-                            .clone()
-                            .into_iter()
-                            .rev()
-                            .collect::<Vec<NodeId>>();
-
-                        if (!one_way) {
-                            let edge =
-                                Edge::new(*node, source, distance, weight, 0.0, nodes, way1.id);
-                            osm_graph.add_edge(edge.clone());
-                            osm_graph.add_edge_from_node(*node, edge.clone());
-                        }
-
-                        source = *node;
-                    }
-                }
+        let start_time = std::time::Instant::now();
 
-                if node == &way.nodes[way.nodes.len() - 1] {
-                    let source_index = way.nodes.iter().position(|x| *x == source).unwrap();
-
-                    let mut weight = 1.0;
-                    let mut distance = 0.0;
-
-                    for i in 0..way.nodes.len() - 1 {
-                        let node1 = osm_graph.get_node(way.nodes[i]).unwrap();
-                        let node2 = osm_graph.get_node(way.nodes[i + 1]).unwrap();
-
-                        let distance1 = OSMGraph::haversine_distance(
-                            node1.lat(),
-                            node1.lon(),
-                            node2.lat(),
-                            node2.lon(),
-                        );
-
-                        distance += distance1;
-                    }
-
-                    let mut CURVY = 0.0;
-                    let mut MIDDLE = 0.0;
-                    let mut STRAIGHT = 0.0;
-
-                    // for each three nodes, calculate radius of the circle that passes through them
-                    for i in 0..way.nodes.len() - 2 {
-                        let node1 = osm_graph.get_node(way.nodes[i]).unwrap();
-                        let node2 = osm_graph.get_node(way.nodes[i + 1]).unwrap();
-                        let node3 = osm_graph.get_node(way.nodes[i + 2]).unwrap();
-
-                        let radius = OSMGraph::circle_radius(node1, node2, node3);
-
-                        // attributes a weight to the edge based on the radius : small curve, big curve, straight line
-                        if (radius < 100.0) {
-                            weight += 0.0; // * total_distance;
-                            CURVY += 1.0;
-                        } else if (radius < 200.0) {
-                            weight += 0.0; // * total_distance;
-                            MIDDLE += 1.0;
-                        } else {
-                            weight += 0.0; // * total_distance;
-                            STRAIGHT += 1.0;
-                        }
-                    }
-
-                    // Define weights for the scores (adjust these based on your preferences)
-                    let w_curvy = 1.0;
-                    let w_middle = 0.5;
-                    let w_straight = 0.2;
-
-                    // Calculate the overall score
-                    let overall_score = w_curvy * CURVY + w_middle * MIDDLE - w_straight * STRAIGHT;
-
-                    // calculate weight based on the number of curves (CURVY, MIDDLE, STRAIGHT) and the distance
-                    if (CURVY > MIDDLE && CURVY > STRAIGHT) {
-                        weight *= 0.5;
-                    } else if (MIDDLE > CURVY && MIDDLE > STRAIGHT) {
-                        weight *= 1.0;
-                    } else {
-                        weight *= 1.5;
-                    }
-
-                    weight *= distance;
-
-                    let edge = Edge::new(
-                        source,
-                        *node,
-                        distance,
-                        weight,
-                        0.0,
-                        way.nodes[source_index..way.nodes.len()].to_vec(),
-                        way.id,
-                    );
-
-                    osm_graph.add_edge(edge.clone());
-                    osm_graph.add_edge_from_node(source, edge.clone());
-
-                    let nodes = way.nodes[source_index..way.nodes.len()]
-                        .to_vec()
-                        .into_iter()
-                        .rev()
-                        .collect::<Vec<NodeId>>();
-
-                    if (!one_way) {
-                        let edge = Edge::new(*node, source, distance, weight, 0.0, nodes, way.id);
-                        osm_graph.add_edge(edge.clone());
-                        osm_graph.add_edge_from_node(*node, edge.clone());
-                    }
-                }
-            }
-        }
+        osm_graph.build_spatial_index();
+        osm_graph.update_min_weight_per_meter();
 
-        info!("Build graph in {} seconds", start_time.elapsed().as_secs());
-        println!("Build graph in {} seconds", start_time.elapsed().as_secs());
+        info!(
+            "Build spatial index in {} ms",
+            start_time.elapsed().as_millis()
+        );
+        println!(
+            "Build spatial index in {} ms",
+            start_time.elapsed().as_millis()
+        );
 
         info!("Nodes count: {}", osm_graph.get_node_count());
         println!("Nodes count: {}", osm_graph.get_node_count());
@@ -302,4 +246,263 @@ impl OSMReader {
 
         osm_graph
     }
+
+    /// Read the turn restriction relations of the PBF file, in a second pass over the file
+    /// The restrictions on ways or nodes missing from the graph are ignored
+    /// # Arguments
+    /// * `pbf_reader` - The reader of the PBF file
+    /// * `osm_graph` - The graph to add the restrictions to
+    fn read_turn_restrictions(
+        pbf_reader: &mut OsmPbfReader<BufReader<File>>,
+        osm_graph: &mut OSMGraph,
+    ) {
+        if let Err(error) = pbf_reader.rewind() {
+            warn!("Unable to read the turn restrictions: {}", error);
+            return;
+        }
+
+        for obj in pbf_reader.par_iter() {
+            let relation = match obj {
+                Ok(OsmObj::Relation(relation)) => relation,
+                Ok(_) => continue,
+                Err(error) => {
+                    warn!("Unable to read the turn restrictions: {}", error);
+                    return;
+                }
+            };
+
+            for restriction in TurnRestriction::from_relation(&relation) {
+                let via_in_graph = match &restriction.via {
+                    RestrictionVia::Node(node_id) => osm_graph.nodes.contains_key(node_id),
+                    RestrictionVia::Ways(way_ids) => way_ids
+                        .iter()
+                        .all(|way_id| osm_graph.ways.contains_key(way_id)),
+                };
+
+                if via_in_graph
+                    && osm_graph.ways.contains_key(&restriction.from)
+                    && osm_graph.ways.contains_key(&restriction.to)
+                {
+                    osm_graph.turn_restrictions.add(restriction);
+                }
+            }
+        }
+    }
+
+    /// Add the edges of a way to the graph, split into sections at the nodes shared with other
+    /// ways
+    /// The directions and the access of the edges follow the tags of the way
+    /// # Arguments
+    /// * `osm_graph` - The graph to add the edges to, containing the nodes of the way
+    /// * `way` - The way
+    /// * `link_counter` - The number of ways of the graph through each node
+    fn add_way_edges(osm_graph: &mut OSMGraph, way: &Way, link_counter: &HashMap<NodeId, i64>) {
+        let directions = Directions::of_way(&way.tags);
+        let direction_conditions = Directions::conditions_of_way(&way.tags);
+        let access = EdgeAccess::of_way(&way.tags);
+
+        // access in each direction, None if motorcycles can never follow the way in it
+        let direction_access = |allowed: bool, forward: bool| {
+            let conditions: Vec<_> = direction_conditions
+                .iter()
+                .map(|(condition_directions, condition)| {
+                    let open = if forward {
+                        condition_directions.forward
+                    } else {
+                        condition_directions.backward
+                    };
+
+                    (open, condition.clone())
+                })
+                .collect();
+
+            Some(access.in_direction(allowed, &conditions))
+                .filter(|edge_access| !edge_access.is_never_allowed())
+        };
+
+        let forward_access = direction_access(directions.forward, true);
+        let backward_access = direction_access(directions.backward, false);
+
+        if forward_access.is_none() && backward_access.is_none() {
+            return;
+        }
+
+        // split the way into sections at the nodes shared with other ways
+        let mut source_index = 0;
+
+        for index in 1..way.nodes.len() {
+            if index == way.nodes.len() - 1 || link_counter[&way.nodes[index]] > 1 {
+                OSMReader::add_section(
+                    osm_graph,
+                    way,
+                    &way.nodes[source_index..index + 1],
+                    forward_access.as_ref(),
+                    backward_access.as_ref(),
+                );
+
+                source_index = index;
+            }
+        }
+    }
+
+    /// Add the edges of a section of a way between two intersections to the graph
+    /// The distance, the curvature score and the travel time are computed on the nodes of the section
+    /// # Arguments
+    /// * `osm_graph` - The graph to add the edges to
+    /// * `way` - The way of the section
+    /// * `nodes` - The nodes of the section, from the first to the last intersection
+    /// * `forward_access` - The access of motorcycles in the direction of the nodes, None if there is no edge in this direction
+    /// * `backward_access` - The access of motorcycles in the opposite direction, None if there is no edge in this direction
+    fn add_section(
+        osm_graph: &mut OSMGraph,
+        way: &Way,
+        nodes: &[NodeId],
+        forward_access: Option<&EdgeAccess>,
+        backward_access: Option<&EdgeAccess>,
+    ) {
+        let section_nodes: Vec<&Node> = nodes
+            .iter()
+            .map(|node_id| osm_graph.get_node(*node_id).unwrap())
+            .collect();
+
+        let distance: f64 = section_nodes
+            .windows(2)
+            .map(|segment| {
+                OSMGraph::haversine_distance(
+                    segment[0].lat(),
+                    segment[0].lon(),
+                    segment[1].lat(),
+                    segment[1].lon(),
+                )
+            })
+            .sum();
+
+        let curvature = curvature::curvature_score(&section_nodes);
+        let weight = curvature::edge_weight(curvature, distance);
+
+        let source = nodes[0];
+        let target = nodes[nodes.len() - 1];
+
+        if let Some(access) = forward_access {
+            let time = speed::travel_time(distance, speed::way_speed(&way.tags, true));
+            let edge = Edge::new(
+                source,
+                target,
+                distance,
+                weight,
+                time,
+                nodes.to_vec(),
+                way.id,
+            )
+            .with_curvature(curvature)
+            .with_access(access.clone());
+
+            osm_graph.add_edge(edge.clone());
+            osm_graph.add_edge_from_node(source, edge.clone());
+            osm_graph.add_edge_to_node(target, edge);
+        }
+
+        if let Some(access) = backward_access {
+            let time = speed::travel_time(distance, speed::way_speed(&way.tags, false));
+            let edge = Edge::new(
+                target,
+                source,
+                distance,
+                weight,
+                time,
+                nodes.iter().rev().cloned().collect(),
+                way.id,
+            )
+            .with_curvature(curvature)
+            .with_access(access.clone());
+
+            osm_graph.add_edge(edge.clone());
+            osm_graph.add_edge_from_node(target, edge.clone());
+            osm_graph.add_edge_to_node(source, edge);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::{node, tags};
+    use osmpbfreader::objects::WayId;
+
+    /// Build a graph from a single way of three nodes going east, as the reader does
+    /// # Arguments
+    /// * `way_tags` - The tags of the way
+    /// # Returns
+    /// * `Vec<(NodeId, NodeId)>` - The source and target nodes of the edges of the graph
+    fn edges_of_way(way_tags: &[(&str, &str)]) -> Vec<(NodeId, NodeId)> {
+        let mut osm_graph = OSMGraph::new();
+        let way = Way {
+            id: WayId(1),
+            tags: tags(way_tags),
+            nodes: vec![NodeId(1), NodeId(2), NodeId(3)],
+        };
+        let mut link_counter = HashMap::new();
+
+        for (id, lon) in [(1, 7.0), (2, 7.01), (3, 7.02)] {
+            osm_graph.add_node(&node(id, 47.0, lon));
+            link_counter.insert(NodeId(id), 1);
+        }
+
+        osm_graph.add_way(&way);
+        OSMReader::add_way_edges(&mut osm_graph, &way, &link_counter);
+
+        osm_graph
+            .get_edges()
+            .iter()
+            .map(|edge| (edge.from, edge.to))
+            .collect()
+    }
+
+    #[test]
+    fn two_way_road() {
+        assert_eq!(
+            edges_of_way(&[("highway", "primary")]),
+            vec![(NodeId(1), NodeId(3)), (NodeId(3), NodeId(1))]
+        );
+    }
+
+    #[test]
+    fn reverse_oneway_has_only_a_backward_edge() {
+        assert_eq!(
+            edges_of_way(&[("highway", "primary"), ("oneway", "-1")]),
+            vec![(NodeId(3), NodeId(1))]
+        );
+    }
+
+    #[test]
+    fn reversible_way_has_no_edge() {
+        assert_eq!(
+            edges_of_way(&[("highway", "primary"), ("oneway", "reversible")]),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn motorway_is_oneway_by_default() {
+        assert_eq!(
+            edges_of_way(&[("highway", "motorway")]),
+            vec![(NodeId(1), NodeId(3))]
+        );
+        assert_eq!(
+            edges_of_way(&[("highway", "motorway"), ("oneway", "no")]),
+            vec![(NodeId(1), NodeId(3)), (NodeId(3), NodeId(1))]
+        );
+    }
+
+    #[test]
+    fn motorcycle_oneway_overrides_oneway() {
+        assert_eq!(
+            edges_of_way(&[
+                ("highway", "primary"),
+                ("oneway", "yes"),
+                ("oneway:motorcycle", "no")
+            ]),
+            vec![(NodeId(1), NodeId(3)), (NodeId(3), NodeId(1))]
+        );
+    }
 }
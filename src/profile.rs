@@ -0,0 +1,173 @@
+use crate::curvature;
+use crate::osm_graph::{Edge, OSMGraph};
+use crate::road_filter::TagFilter;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Factor applied to the cost of the edges whose way matches a tag filter
+/// # Fields
+/// * `filter` - The tag filter
+/// * `factor` - The factor applied to the cost, above 1 to avoid the ways and below 1 to prefer them
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagFactor {
+    #[serde(flatten)]
+    pub filter: TagFilter,
+    pub factor: f64,
+}
+
+/// Routing profile, computing the cost of an edge from its distance, time, curvature and tags
+/// The cost is `(distance * distance_m + time * time_s) / (1 + curvature * curviness) * tag factors`,
+/// where the curviness of the edge goes from 0 on a straight road to 2 on hairpins
+/// # Fields
+/// * `distance` - The cost of a meter
+/// * `time` - The cost of a second
+/// * `curvature` - How much curvy roads are preferred, 0 to ignore the curvature
+/// * `tag_factors` - The factors applied to the ways matching a tag filter
+/// * `min_cost_per_meter` - The smallest cost per meter of the edges of the graph
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Profile {
+    #[serde(default)]
+    pub distance: f64,
+    #[serde(default)]
+    pub time: f64,
+    #[serde(default)]
+    pub curvature: f64,
+    #[serde(default)]
+    pub tag_factors: Vec<TagFactor>,
+    #[serde(skip)]
+    pub min_cost_per_meter: f64,
+}
+
+/// Profile implementation
+impl Profile {
+    /// Check that the profile gives a positive cost to every edge
+    /// # Returns
+    /// * `Result<(), String>` - An error message if a factor is negative or every cost is zero
+    pub fn validate(&self) -> Result<(), String> {
+        if self.distance < 0.0 || self.time < 0.0 {
+            return Err("distance and time must be positive".to_string());
+        }
+
+        if self.distance == 0.0 && self.time == 0.0 {
+            return Err("distance or time must be above 0".to_string());
+        }
+
+        if self.curvature < 0.0 {
+            return Err("curvature must be positive".to_string());
+        }
+
+        if self
+            .tag_factors
+            .iter()
+            .any(|tag_factor| tag_factor.factor <= 0.0)
+        {
+            return Err("tag factors must be above 0".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Get the curvature factor of an edge
+    /// # Arguments
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `f64` - The factor applied to the cost, 1 on a straight road and lower on curvy roads
+    pub fn curvature_factor(&self, edge: &Edge) -> f64 {
+        1.0 / (1.0 + self.curvature * curvature::curviness(edge.curvature, edge.distance_m))
+    }
+
+    /// Get the cost of an edge
+    /// # Arguments
+    /// * `graph` - The graph of the edge
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `f64` - The cost of the edge
+    pub fn edge_cost(&self, graph: &OSMGraph, edge: &Edge) -> f64 {
+        let mut cost =
+            (self.distance * edge.distance_m + self.time * edge.time) * self.curvature_factor(edge);
+
+        if let Some(way) = graph.ways.get(&edge.way_id) {
+            for tag_factor in &self.tag_factors {
+                if tag_factor.filter.matches(&way.tags) {
+                    cost *= tag_factor.factor;
+                }
+            }
+        }
+
+        cost
+    }
+
+    /// Update the smallest cost per meter of the edges of a graph, used by the A* heuristic
+    /// # Arguments
+    /// * `graph` - The graph
+    pub fn update_min_cost_per_meter(&mut self, graph: &OSMGraph) {
+        self.min_cost_per_meter = graph
+            .edges
+            .iter()
+            .filter(|edge| edge.distance_m > 0.0)
+            .map(|edge| self.edge_cost(graph, edge) / edge.distance_m)
+            .fold(f64::INFINITY, f64::min);
+
+        if !self.min_cost_per_meter.is_finite() {
+            self.min_cost_per_meter = 0.0;
+        }
+    }
+}
+
+/// Routing profiles by name, loaded from a JSON config file
+/// # Example
+/// ```json
+/// {
+///     "fastest": {"time": 1.0},
+///     "curviest": {"distance": 1.0, "curvature": 2.0}
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Profiles {
+    pub profiles: HashMap<String, Profile>,
+}
+
+/// Profiles implementation
+impl Profiles {
+    /// Load the profiles from a JSON config file
+    /// # Arguments
+    /// * `file_path` - The path of the config file
+    /// # Returns
+    /// * `Result<Profiles, String>` - The profiles, or an error message if the file is missing or a profile is invalid
+    pub fn from_file(file_path: &Path) -> Result<Self, String> {
+        let file = File::open(file_path).map_err(|error| error.to_string())?;
+
+        let profiles: Profiles =
+            serde_json::from_reader(BufReader::new(file)).map_err(|error| error.to_string())?;
+
+        for (name, profile) in &profiles.profiles {
+            profile
+                .validate()
+                .map_err(|error| format!("profile {}: {}", name, error))?;
+        }
+
+        Ok(profiles)
+    }
+
+    /// Get a profile by name
+    /// # Arguments
+    /// * `name` - The name of the profile
+    /// # Returns
+    /// * `Option<&Profile>` - The profile, None if there is no profile with this name
+    pub fn get(&self, name: &str) -> Option<&Profile> {
+        self.profiles.get(name)
+    }
+
+    /// Update the smallest cost per meter of every profile for a graph
+    /// # Arguments
+    /// * `graph` - The graph
+    pub fn update_min_cost_per_meter(&mut self, graph: &OSMGraph) {
+        for profile in self.profiles.values_mut() {
+            profile.update_min_cost_per_meter(graph);
+        }
+    }
+}
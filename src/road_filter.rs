@@ -0,0 +1,132 @@
+use osmpbfreader::objects::Tags;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Filter on the value of a tag
+/// # Fields
+/// * `key` - The key of the tag
+/// * `values` - The accepted values of the tag, any value if empty
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TagFilter {
+    pub key: String,
+    #[serde(default)]
+    pub values: Vec<String>,
+}
+
+/// TagFilter implementation
+impl TagFilter {
+    /// Check if tags match the filter
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `bool` - True if the tag is present with one of the values
+    pub fn matches(&self, tags: &Tags) -> bool {
+        match tags.get(self.key.as_str()) {
+            Some(value) => self.values.is_empty() || self.values.iter().any(|v| v == value),
+            None => false,
+        }
+    }
+}
+
+/// Filter of the ways kept in the graph, loaded from a JSON config file
+/// # Fields
+/// * `highways` - The highway classes kept in the graph
+/// * `ferries` - Keep the ferry routes carrying motor vehicles, see `RoadFilter::is_vehicle_ferry`
+/// * `require_tags` - Tags that every kept way must match
+/// * `exclude_tags` - Tags that remove a way from the graph if it matches any of them
+/// # Example
+/// ```json
+/// {
+///     "highways": ["primary", "secondary", "motorway", "track"],
+///     "ferries": true,
+///     "require_tags": [],
+///     "exclude_tags": [{"key": "area", "values": ["yes"]}]
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RoadFilter {
+    pub highways: Vec<String>,
+    #[serde(default)]
+    pub ferries: bool,
+    #[serde(default)]
+    pub require_tags: Vec<TagFilter>,
+    #[serde(default)]
+    pub exclude_tags: Vec<TagFilter>,
+}
+
+/// Default filter, keeping the roads between `primary` and `living_street`
+impl Default for RoadFilter {
+    fn default() -> Self {
+        RoadFilter {
+            highways: [
+                "primary",
+                "secondary",
+                "tertiary",
+                "unclassified",
+                "residential",
+                "primary_link",
+                "secondary_link",
+                "tertiary_link",
+                "living_street",
+            ]
+            .iter()
+            .map(|highway| highway.to_string())
+            .collect(),
+            ferries: false,
+            require_tags: Vec::new(),
+            exclude_tags: Vec::new(),
+        }
+    }
+}
+
+/// RoadFilter implementation
+impl RoadFilter {
+    /// Load a filter from a JSON config file
+    /// # Arguments
+    /// * `file_path` - The path of the config file
+    /// # Returns
+    /// * `Result<RoadFilter, String>` - The filter, or an error message if the file is missing or invalid
+    pub fn from_file(file_path: &Path) -> Result<Self, String> {
+        let file = File::open(file_path).map_err(|error| error.to_string())?;
+
+        serde_json::from_reader(BufReader::new(file)).map_err(|error| error.to_string())
+    }
+
+    /// Check if a way is kept in the graph
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `bool` - True if the way has a kept highway class or is a kept ferry route, matches the required tags and none of the excluded tags
+    pub fn accepts(&self, tags: &Tags) -> bool {
+        let kept = match tags.get("highway") {
+            Some(highway) => self.highways.iter().any(|h| h == highway),
+            None => self.ferries && RoadFilter::is_vehicle_ferry(tags),
+        };
+
+        kept && self.require_tags.iter().all(|filter| filter.matches(tags))
+            && !self.exclude_tags.iter().any(|filter| filter.matches(tags))
+    }
+
+    /// Check if a way is a ferry route carrying motor vehicles
+    /// The ferries without `motorcycle`, `motor_vehicle` or `motorcar` tag only carry pedestrians
+    /// and bicycles, like most of the boats on the Swiss lakes
+    /// # Arguments
+    /// * `tags` - The tags of the way
+    /// # Returns
+    /// * `bool` - True if the way is `route=ferry` and one of these tags is `yes`
+    pub fn is_vehicle_ferry(tags: &Tags) -> bool {
+        let is_yes = |key: &str| tags.get(key).is_some_and(|value| value == "yes");
+
+        tags.get("route").is_some_and(|route| route == "ferry")
+            && (is_yes("motorcycle") || is_yes("motor_vehicle") || is_yes("motorcar"))
+    }
+
+    /// Get a description of the filter, used to key the graph cache
+    /// # Returns
+    /// * `String` - The filter in JSON format
+    pub fn settings(&self) -> String {
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
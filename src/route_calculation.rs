@@ -1,125 +1,1118 @@
+use crate::access::{Access, AccessPhase};
+use crate::avoid::{AvoidFeature, AVOID_PENALTY};
+use crate::avoid_zone::AvoidZone;
 use crate::osm_graph::Edge;
 use crate::osm_graph::OSMGraph;
+use crate::osm_graph::SnappedPoint;
 use crate::osm_graph::State;
+use crate::profile::Profile;
+use chrono::NaiveDateTime;
 use log::{info, warn};
 use osmpbfreader::objects::{Node, NodeId, Way, WayId};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
+use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
-/// Dijkstra algorithm to find the shortest path between two nodes
+/// Nodes ids and edges of the path between two points
+pub type RouteLeg = (Vec<NodeId>, Vec<Edge>);
+
+/// Factor applied to the cost of the edges of the routes already found when searching alternatives
+const ALTERNATIVE_PENALTY: f64 = 1.5;
+
+/// Maximum ratio between the cost of an alternative route and the cost of the best route
+const ALTERNATIVE_MAX_STRETCH: f64 = 1.4;
+
+/// Maximum share of the distance of an alternative route on the edges of another route
+const ALTERNATIVE_MAX_OVERLAP: f64 = 0.7;
+
+/// Number of searches done for each alternative route requested
+const ALTERNATIVE_SEARCHES_PER_ROUTE: usize = 3;
+
+/// Options of a route request, shared by the search algorithms
+/// # Fields
+/// * `profile` - The routing profile giving the cost of the edges, `Edge::weight` is used if None
+/// * `penalties` - Factors applied to the cost of edges, by edge key
+/// * `avoid` - The road features to avoid
+/// * `depart_at` - The local departure time, used to evaluate the conditional restrictions. If
+///   None, the conditional restrictions are applied at all times
+#[derive(Debug, Clone, Default)]
+pub struct RouteOptions {
+    pub profile: Option<Profile>,
+    pub penalties: HashMap<(NodeId, NodeId, WayId), f64>,
+    pub avoid: Vec<AvoidFeature>,
+    pub depart_at: Option<NaiveDateTime>,
+}
+
+/// RouteOptions implementation
+impl RouteOptions {
+    /// Create the options of a route using a profile
+    /// # Arguments
+    /// * `profile` - The routing profile
+    pub fn with_profile(profile: Profile) -> Self {
+        RouteOptions {
+            profile: Some(profile),
+            ..Default::default()
+        }
+    }
+
+    /// Get the cost of an edge
+    /// # Arguments
+    /// * `graph` - The graph of the edge
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `f64` - The cost of the edge with the profile, or its weight without profile, times its penalties
+    pub fn edge_cost(&self, graph: &OSMGraph, edge: &Edge) -> f64 {
+        let mut cost = match &self.profile {
+            Some(profile) => profile.edge_cost(graph, edge),
+            None => edge.weight,
+        };
+
+        if !self.avoid.is_empty() {
+            if let Some(way) = graph.ways.get(&edge.way_id) {
+                if self.avoid.iter().any(|feature| feature.matches(&way.tags)) {
+                    cost *= AVOID_PENALTY;
+                }
+            }
+        }
+
+        if self.penalties.is_empty() {
+            return cost;
+        }
+
+        cost * self.penalties.get(&edge.key()).unwrap_or(&1.0)
+    }
+
+    /// Get the access of motorcycles to an edge at the departure time
+    /// # Arguments
+    /// * `edge` - The edge
+    /// # Returns
+    /// * `Access` - The access of the edge
+    pub fn edge_access(&self, edge: &Edge) -> Access {
+        edge.access.at(self.depart_at.as_ref())
+    }
+
+    /// Get the smallest cost per meter of the edges of the graph, used by the A* heuristic
+    /// # Arguments
+    /// * `graph` - The graph
+    /// # Returns
+    /// * `f64` - The smallest cost per meter
+    pub fn min_cost_per_meter(&self, graph: &OSMGraph) -> f64 {
+        match &self.profile {
+            Some(profile) => profile.min_cost_per_meter,
+            None => graph.min_weight_per_meter,
+        }
+    }
+
+    /// Check if the costs are the weights of the edges, which the contraction hierarchy is built on
+    /// # Returns
+    /// * `bool` - True if no profile, no penalty and no avoided feature are used
+    pub fn uses_edge_weights(&self) -> bool {
+        self.profile.is_none() && self.penalties.is_empty() && self.avoid.is_empty()
+    }
+
+    /// Penalize the edges crossing zones
+    /// The segments near each zone are found with the edge index of the graph. The snapped start
+    /// and end of a route are parts of edges that are not penalized, so a route can start or end
+    /// inside a zone
+    /// # Arguments
+    /// * `graph` - The graph of the edges
+    /// * `zones` - The zones to avoid
+    /// * `penalty` - The factor applied to the cost of the edges, None to exclude them
+    pub fn avoid_zones(&mut self, graph: &OSMGraph, zones: &[AvoidZone], penalty: Option<f64>) {
+        let factor = penalty.unwrap_or(f64::INFINITY);
+
+        for zone in zones {
+            let (min_lat, min_lon, max_lat, max_lon) = zone.bbox();
+
+            for segment in graph
+                .edge_index
+                .segments_in_bbox(min_lat, min_lon, max_lat, max_lon)
+            {
+                let key = graph.edges[segment.edge_index].key();
+
+                if self
+                    .penalties
+                    .get(&key)
+                    .is_some_and(|current| *current >= factor)
+                {
+                    continue;
+                }
+
+                if zone.intersects_segment(segment.from, segment.to) {
+                    self.penalties.insert(key, factor);
+                }
+            }
+        }
+    }
+
+    /// Get the cost of a path
+    /// # Arguments
+    /// * `graph` - The graph of the path
+    /// * `edges` - The edges of the path
+    /// # Returns
+    /// * `f64` - The sum of the costs of the edges
+    pub fn path_cost(&self, graph: &OSMGraph, edges: &[Edge]) -> f64 {
+        edges.iter().map(|edge| self.edge_cost(graph, edge)).sum()
+    }
+}
+
+/// Alternative to the best route between two points
+/// # Fields
+/// * `nodes` - The nodes ids of the path
+/// * `edges` - The edges of the path
+/// * `stretch` - The ratio between the cost of the route and the cost of the best route
+/// * `overlap` - The share of the distance of the route on the edges of the best route
+#[derive(Debug, Clone)]
+pub struct AlternativeRoute {
+    pub nodes: Vec<NodeId>,
+    pub edges: Vec<Edge>,
+    pub stretch: f64,
+    pub overlap: f64,
+}
+
+/// Get the share of the distance of a path on the edges of another path
+/// # Arguments
+/// * `edges` - The edges of the path
+/// * `other` - The edges of the other path
+/// # Returns
+/// * `f64` - The overlap, between 0 and 1
+pub fn path_overlap(edges: &[Edge], other: &[Edge]) -> f64 {
+    let other_keys: HashSet<(NodeId, NodeId, WayId)> =
+        other.iter().map(|edge| edge.key()).collect();
+
+    let distance: f64 = edges.iter().map(|edge| edge.distance_m).sum();
+    let shared: f64 = edges
+        .iter()
+        .filter(|edge| other_keys.contains(&edge.key()))
+        .map(|edge| edge.distance_m)
+        .sum();
+
+    if distance > 0.0 {
+        shared / distance
+    } else {
+        1.0
+    }
+}
+
+/// Find alternatives to the best route between two points by penalizing the edges of the routes
+/// already found and searching again
+/// An alternative is kept if its cost is at most `ALTERNATIVE_MAX_STRETCH` times the best cost and
+/// if it overlaps each route already kept by at most `ALTERNATIVE_MAX_OVERLAP`
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `best_edges` - The edges of the best route
+/// * `algorithm` - The algorithm to use
+/// * `options` - The options of the route
+/// * `count` - The number of alternatives wanted
+/// # Returns
+/// * `Vec<AlternativeRoute>` - The alternatives found, at most `count`
+pub fn find_alternatives(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    best_edges: &[Edge],
+    algorithm: Algorithm,
+    options: &RouteOptions,
+    count: usize,
+) -> Vec<AlternativeRoute> {
+    let best_cost = options.path_cost(graph, best_edges);
+
+    let mut alternatives: Vec<AlternativeRoute> = Vec::new();
+    let mut penalized_options = options.clone();
+    let mut penalized_edges: Vec<Edge> = best_edges.to_vec();
+
+    for _ in 0..count * ALTERNATIVE_SEARCHES_PER_ROUTE {
+        if alternatives.len() >= count {
+            break;
+        }
+
+        for edge in &penalized_edges {
+            *penalized_options.penalties.entry(edge.key()).or_insert(1.0) *= ALTERNATIVE_PENALTY;
+        }
+
+        let (nodes, edges) = match find_path(graph, start, end, algorithm, &penalized_options) {
+            (Some(nodes), Some(edges)) => (nodes, edges),
+            _ => break,
+        };
+
+        let stretch = if best_cost > 0.0 {
+            options.path_cost(graph, &edges) / best_cost
+        } else {
+            1.0
+        };
+        let overlap = path_overlap(&edges, best_edges);
+
+        let is_different = overlap <= ALTERNATIVE_MAX_OVERLAP
+            && alternatives.iter().all(|alternative| {
+                path_overlap(&edges, &alternative.edges) <= ALTERNATIVE_MAX_OVERLAP
+            });
+
+        penalized_edges = edges.clone();
+
+        if stretch <= ALTERNATIVE_MAX_STRETCH && is_different {
+            alternatives.push(AlternativeRoute {
+                nodes,
+                edges,
+                stretch,
+                overlap,
+            });
+        }
+    }
+
+    alternatives
+}
+
+/// Start or end of a search on a node of the graph, reached through a part of an edge
+/// # Fields
+/// * `node_id` - The node where the search starts or ends
+/// * `cost` - The cost of the part of the edge between the snapped point and the node
+/// * `edge` - The part of the edge between the snapped point and the node
+#[derive(Debug, Clone)]
+pub struct SearchEndpoint {
+    pub node_id: NodeId,
+    pub cost: f64,
+    pub edge: Edge,
+}
+
+/// Key of a state of the edge-based searches: the node, the way used to reach it, the
+/// indexes of the via-way turn restrictions in progress and the access phase of the route
+type SearchStateKey = (NodeId, WayId, Vec<usize>, AccessPhase);
+
+/// Get the key of the state of a search after following an edge
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `options` - The options of the route
+/// * `key` - The key of the state before the edge, None at the start of the route
+/// * `edge` - The edge
+/// # Returns
+/// * `Option<SearchStateKey>` - The key of the state at the end of the edge, None if the turn
+///   to the edge is forbidden or if the edge can't be used at this point of the route
+fn next_state(
+    graph: &OSMGraph,
+    options: &RouteOptions,
+    key: Option<&SearchStateKey>,
+    edge: &Edge,
+) -> Option<SearchStateKey> {
+    match key {
+        Some((node_id, way_id, active, phase)) => Some((
+            edge.to,
+            edge.way_id,
+            graph
+                .turn_restrictions
+                .turn(*node_id, *way_id, active, edge.way_id)?,
+            phase.next(options.edge_access(edge))?,
+        )),
+        None => Some((
+            edge.to,
+            edge.way_id,
+            Vec::new(),
+            AccessPhase::Start.next(options.edge_access(edge))?,
+        )),
+    }
+}
+
+/// States of an edge-based search
+/// # Fields
+/// * `keys` - The key of each state
+/// * `indexes` - The index of the state of each key
+/// * `distances` - The cost from the start to each state
+/// * `prev_states` - The previous state of each state, None for the start states
+/// * `prev_edges` - The edge used to reach each state
+/// * `visited` - True for the states whose outgoing edges have been explored
+#[derive(Debug, Default)]
+struct SearchStates {
+    keys: Vec<SearchStateKey>,
+    indexes: HashMap<SearchStateKey, usize>,
+    distances: Vec<f64>,
+    prev_states: Vec<Option<usize>>,
+    prev_edges: Vec<Edge>,
+    visited: Vec<bool>,
+}
+
+/// SearchStates implementation
+impl SearchStates {
+    /// Reach a state, keeping the cheapest way to reach it
+    /// # Arguments
+    /// * `key` - The key of the state
+    /// * `distance` - The cost from the start to the state
+    /// * `prev_state` - The index of the previous state, None for a start state
+    /// * `edge` - The edge used to reach the state
+    /// # Returns
+    /// * `Option<usize>` - The index of the state if its cost has been lowered, None otherwise
+    fn relax(
+        &mut self,
+        key: SearchStateKey,
+        distance: f64,
+        prev_state: Option<usize>,
+        edge: Edge,
+    ) -> Option<usize> {
+        match self.indexes.get(&key) {
+            Some(index) => {
+                if self.visited[*index] || distance >= self.distances[*index] {
+                    return None;
+                }
+
+                self.distances[*index] = distance;
+                self.prev_states[*index] = prev_state;
+                self.prev_edges[*index] = edge;
+
+                Some(*index)
+            }
+            None => {
+                let index = self.keys.len();
+
+                self.indexes.insert(key.clone(), index);
+                self.keys.push(key);
+                self.distances.push(distance);
+                self.prev_states.push(prev_state);
+                self.prev_edges.push(edge);
+                self.visited.push(false);
+
+                Some(index)
+            }
+        }
+    }
+
+    /// Mark a state as visited
+    /// # Arguments
+    /// * `index` - The index of the state
+    /// # Returns
+    /// * `bool` - True if the state was not visited yet
+    fn visit(&mut self, index: usize) -> bool {
+        !std::mem::replace(&mut self.visited[index], true)
+    }
+
+    /// Get the path from the start to a state
+    /// # Arguments
+    /// * `index` - The index of the state
+    /// # Returns
+    /// * `RouteLeg` - The nodes ids and the edges of the path, starting with the start edge
+    fn path_to(&self, index: usize) -> RouteLeg {
+        let mut path: Vec<NodeId> = Vec::new();
+        let mut edge_path: Vec<Edge> = Vec::new();
+        let mut current_state = Some(index);
+
+        // the start states have no previous state
+        while let Some(state) = current_state {
+            path.push(self.keys[state].0);
+            edge_path.push(self.prev_edges[state].clone());
+            current_state = self.prev_states[state];
+        }
+
+        path.reverse();
+        edge_path.reverse();
+
+        (path, edge_path)
+    }
+}
+
+/// Entry of the heaps used by the edge-based searches
+/// # Fields
+/// * `index` - The index of the state
+/// * `distance` - The cost of the state, plus the heuristic for A*
+#[derive(Debug, Clone, Copy)]
+struct QueueEntry {
+    index: usize,
+    distance: f64,
+}
+
+impl Eq for QueueEntry {}
+
+impl PartialEq for QueueEntry {
+    fn eq(&self, other: &Self) -> bool {
+        self.distance == other.distance
+    }
+}
+
+impl PartialOrd for QueueEntry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueueEntry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+/// Get the nodes where a search can start from a snapped point
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `options` - The options of the route
+/// # Returns
+/// * `Vec<SearchEndpoint>` - The ends of the edges going away from the point
+pub fn get_sources(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    options: &RouteOptions,
+) -> Vec<SearchEndpoint> {
+    graph
+        .get_edge_positions(start)
+        .iter()
+        .map(|position| {
+            let edge = graph.partial_edge(
+                position,
+                Some((position.segment_index, position.offset_m)),
+                None,
+            );
+
+            SearchEndpoint {
+                node_id: position.edge.to,
+                cost: options.edge_cost(graph, &edge),
+                edge,
+            }
+        })
+        .collect()
+}
+
+/// Get the nodes where a search can end to reach a snapped point
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `end` - The snapped end point
+/// * `options` - The options of the route
+/// # Returns
+/// * `Vec<SearchEndpoint>` - The beginnings of the edges going to the point
+pub fn get_targets(
+    graph: &OSMGraph,
+    end: &SnappedPoint,
+    options: &RouteOptions,
+) -> Vec<SearchEndpoint> {
+    graph
+        .get_edge_positions(end)
+        .iter()
+        .map(|position| {
+            let edge = graph.partial_edge(
+                position,
+                None,
+                Some((position.segment_index, position.offset_m)),
+            );
+
+            SearchEndpoint {
+                node_id: position.edge.from,
+                cost: options.edge_cost(graph, &edge),
+                edge,
+            }
+        })
+        .collect()
+}
+
+/// Get the route staying on a single edge when both snapped points are on it, in the right order
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `options` - The options of the route
+/// # Returns
+/// * `Option<(Edge, f64)>` - The cheapest part of an edge going from the start to the end and its cost, None if there is none
+pub fn get_direct_edge(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    options: &RouteOptions,
+) -> Option<(Edge, f64)> {
+    let mut direct_edge: Option<(Edge, f64)> = None;
+
+    for start_position in graph.get_edge_positions(start) {
+        for end_position in graph.get_edge_positions(end) {
+            if start_position.is_same_edge(&end_position)
+                && start_position.offset_m <= end_position.offset_m
+            {
+                let edge = graph.partial_edge(
+                    &start_position,
+                    Some((start_position.segment_index, start_position.offset_m)),
+                    Some((end_position.segment_index, end_position.offset_m)),
+                );
+
+                let cost = options.edge_cost(graph, &edge);
+
+                if direct_edge
+                    .as_ref()
+                    .is_none_or(|(_, direct_cost)| cost < *direct_cost)
+                {
+                    direct_edge = Some((edge, cost));
+                }
+            }
+        }
+    }
+
+    direct_edge
+}
+
+/// Shortest path algorithm used to calculate a route
+/// # Variants
+/// * `Dijkstra` - Dijkstra algorithm, exploring the graph in every direction
+/// * `AStar` - A* algorithm, guided towards the end point by the haversine distance
+/// * `Bidirectional` - Dijkstra algorithm searching from both ends until the searches meet
+/// * `ContractionHierarchies` - Query in the contraction hierarchy of the graph, falls back to
+///   `Bidirectional` if the hierarchy has not been built or if the route uses a profile
+///
+/// `Dijkstra` and `AStar` follow the turn restrictions of the graph and only use destination-only
+/// edges at the start and at the end of the route. The routes of the other algorithms are
+/// calculated again with `AStar` if they don't
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Algorithm {
+    Dijkstra,
+    AStar,
+    Bidirectional,
+    #[default]
+    #[serde(rename = "ch")]
+    ContractionHierarchies,
+}
+
+/// Find the shortest path between two points snapped onto edges with the given algorithm
 /// Returns a tuple of two vectors, the first one contains the nodes ids of the path
 /// and the second one contains the edges of the path
 /// If no path is found, returns None
 /// # Arguments
 /// * `graph` - The graph to search in
-/// * `start_node` - The start node
-/// * `end_node` - The end node
-pub fn dijkstra(
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `algorithm` - The algorithm to use
+/// * `options` - The options of the route
+pub fn find_path(
     graph: &OSMGraph,
-    start_node: &NodeId,
-    end_node: &NodeId,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    algorithm: Algorithm,
+    options: &RouteOptions,
 ) -> (Option<Vec<NodeId>>, Option<Vec<Edge>>) {
-    let mut distances: HashMap<NodeId, f64> = HashMap::new();
+    let (nodes, edges) = match algorithm {
+        Algorithm::Dijkstra => return dijkstra(graph, start, end, options),
+        Algorithm::AStar => return a_star(graph, start, end, options),
+        Algorithm::Bidirectional => bidirectional_dijkstra(graph, start, end, options),
+        Algorithm::ContractionHierarchies => match &graph.contraction_hierarchy {
+            Some(contraction_hierarchy) if options.uses_edge_weights() => {
+                contraction_hierarchy.query(graph, start, end)
+            }
+            _ => bidirectional_dijkstra(graph, start, end, options),
+        },
+    };
 
-    for node_id in graph.get_nodes().keys() {
-        distances.insert(*node_id, f64::INFINITY);
+    // the node-based searches ignore the turn restrictions and the destination-only edges, a
+    // route with a forbidden turn or crossing a destination-only area is calculated again with
+    // the edge-based A* search
+    match &edges {
+        Some(path_edges)
+            if !graph.turn_restrictions.allows_path(path_edges)
+                || !AccessPhase::allows_path(
+                    path_edges.iter().map(|edge| options.edge_access(edge)),
+                ) =>
+        {
+            a_star(graph, start, end, options)
+        }
+        _ => (nodes, edges),
     }
+}
+
+/// Find the shortest path through waypoints, routing each leg between two consecutive waypoints
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `waypoints` - The snapped waypoints, from the start to the end
+/// * `algorithm` - The algorithm to use
+/// * `options` - The options of the route
+/// # Returns
+/// * `Result<Vec<RouteLeg>, usize>` - The nodes ids and the edges of each leg, or the
+///   index of the first leg without path
+pub fn find_path_through(
+    graph: &OSMGraph,
+    waypoints: &[SnappedPoint],
+    algorithm: Algorithm,
+    options: &RouteOptions,
+) -> Result<Vec<RouteLeg>, usize> {
+    let mut legs = Vec::new();
+
+    for (index, leg) in waypoints.windows(2).enumerate() {
+        match find_path(graph, &leg[0], &leg[1], algorithm, options) {
+            (Some(nodes), Some(edges)) => legs.push((nodes, edges)),
+            _ => return Err(index),
+        }
+    }
+
+    Ok(legs)
+}
+
+/// Dijkstra algorithm to find the shortest path between two points snapped onto edges
+/// Returns a tuple of two vectors, the first one contains the nodes ids of the path
+/// and the second one contains the edges of the path. The first and last edges are the
+/// parts of the snapped edges between the points and the graph nodes
+/// If no path is found, returns None
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `options` - The options of the route
+pub fn dijkstra(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    options: &RouteOptions,
+) -> (Option<Vec<NodeId>>, Option<Vec<Edge>>) {
+    search(graph, start, end, options, |_| 0.0)
+}
+
+/// A* algorithm to find the shortest path between two points snapped onto edges
+/// The heuristic is the haversine distance to the end point multiplied by the smallest cost
+/// per meter of the graph, which never overestimates the remaining cost
+/// Returns the same result as `dijkstra`, usually exploring far fewer nodes
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `options` - The options of the route
+pub fn a_star(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    options: &RouteOptions,
+) -> (Option<Vec<NodeId>>, Option<Vec<Edge>>) {
+    let cost_per_meter = options.min_cost_per_meter(graph);
 
-    let mut visited: HashSet<NodeId> = HashSet::new();
+    search(graph, start, end, options, |node_id| {
+        let node = graph.get_node(node_id).unwrap();
+
+        cost_per_meter * OSMGraph::haversine_distance(node.lat(), node.lon(), end.lat, end.lon)
+    })
+}
+
+/// Best-first search shared by `dijkstra` and `a_star`
+/// The search is edge-based: a node reached by different ways, during different via-way
+/// turn restrictions or in different access phases gives different search states, so that the
+/// turn restrictions and the destination-only edges of the graph can be checked at each turn.
+/// The heap is ordered by the cost from the start plus the heuristic, the search stops when no
+/// state left in the heap can improve the best route found
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `options` - The options of the route
+/// * `heuristic` - Lower bound of the cost between a node and the end point
+fn search<H: Fn(NodeId) -> f64>(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    options: &RouteOptions,
+    heuristic: H,
+) -> (Option<Vec<NodeId>>, Option<Vec<Edge>>) {
+    let mut states = SearchStates::default();
     let mut heap = BinaryHeap::new();
-    let mut start_edge_end_node = NodeId(-1);
 
-    let start_edge: Vec<&Edge> = graph.get_edges_from_node_or_containing(*start_node);
-    let start_node_edge: &Edge = &start_edge[0];
+    for source in get_sources(graph, start, options) {
+        let state = match next_state(graph, options, None, &source.edge) {
+            Some(state) => state,
+            None => continue,
+        };
 
-    distances.insert(start_node_edge.from, 0.0);
-    heap.push(State::new(start_node_edge.from, 0.0));
+        if let Some(index) = states.relax(state, source.cost, None, source.edge) {
+            heap.push(QueueEntry {
+                index,
+                distance: source.cost + heuristic(source.node_id),
+            });
+        }
+    }
 
-    let mut prev_nodes: HashMap<NodeId, NodeId> = HashMap::new(); // TODO : prev edge ?
-    let mut prev_edges: HashMap<NodeId, Edge> = HashMap::new();
+    let targets = get_targets(graph, end, options);
 
-    let mut end_edges: Vec<Edge> = Vec::new();
-    for edge in graph.get_edges() {
-        for node_id_ in &edge.nodes_ids {
-            if node_id_ == end_node {
-                end_edges.push(edge.clone());
+    // route staying on the snapped edge, used if no route through the graph is shorter
+    let direct_edge = get_direct_edge(graph, start, end, options);
+    let mut best_distance = direct_edge
+        .as_ref()
+        .map_or(f64::INFINITY, |(_, cost)| *cost);
+    let mut best_target: Option<(usize, &SearchEndpoint)> = None;
+
+    while let Some(QueueEntry {
+        index,
+        distance: estimate,
+    }) = heap.pop()
+    {
+        if estimate >= best_distance {
+            break;
+        }
+
+        if !states.visit(index) {
+            continue;
+        }
+
+        let key = states.keys[index].clone();
+        let node_id = key.0;
+        let distance = states.distances[index];
+
+        for target in &targets {
+            if target.node_id == node_id
+                && distance + target.cost < best_distance
+                && next_state(graph, options, Some(&key), &target.edge).is_some()
+            {
+                best_distance = distance + target.cost;
+                best_target = Some((index, target));
             }
         }
+
+        for edge in graph.get_edges_from_node_fast(&node_id) {
+            let state = match next_state(graph, options, Some(&key), edge) {
+                Some(state) => state,
+                None => continue,
+            };
+
+            let new_dist = distance + options.edge_cost(graph, edge);
+
+            if let Some(next_index) = states.relax(state, new_dist, Some(index), edge.clone()) {
+                heap.push(QueueEntry {
+                    index: next_index,
+                    distance: new_dist + heuristic(edge.to),
+                });
+            }
+        }
+    }
+
+    if let Some((index, target)) = best_target {
+        let (path, mut edge_path) = states.path_to(index);
+
+        edge_path.push(target.edge.clone());
+
+        return (Some(path), Some(edge_path));
+    }
+
+    if let Some((edge, _)) = direct_edge {
+        return (Some(vec![edge.from, edge.to]), Some(vec![edge]));
+    }
+
+    warn!("No path found");
+
+    (None, None) // No path found
+}
+
+/// Dijkstra algorithm computing the cost of the shortest paths from a point to several points
+/// An end point is settled once no node left in the heap can improve its cost. The search stops
+/// when every end point is settled or when the heap is empty
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `ends` - The snapped end points
+/// * `options` - The options of the route
+/// # Returns
+/// * `Vec<f64>` - The cost of the shortest path to each end point, infinite if there is none
+pub fn dijkstra_to_many(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    ends: &[SnappedPoint],
+    options: &RouteOptions,
+) -> Vec<f64> {
+    let mut states = SearchStates::default();
+    let mut heap = BinaryHeap::new();
+
+    for source in get_sources(graph, start, options) {
+        let state = match next_state(graph, options, None, &source.edge) {
+            Some(state) => state,
+            None => continue,
+        };
+
+        if let Some(index) = states.relax(state, source.cost, None, source.edge) {
+            heap.push(QueueEntry {
+                index,
+                distance: source.cost,
+            });
+        }
     }
 
-    let end_node_edge: &NodeId = &end_edges[0].to;
+    // end points reached from each node, with the end of the route from the node to the point
+    let mut targets: HashMap<NodeId, Vec<(usize, SearchEndpoint)>> = HashMap::new();
+    let mut costs: Vec<f64> = Vec::with_capacity(ends.len());
+
+    // end points reached, by increasing cost, and the number of end points not settled yet
+    let mut reached = BinaryHeap::new();
+    let mut settled = vec![false; ends.len()];
+    let mut unsettled = ends.len();
 
-    while let Some(State { node_id, distance }) = heap.pop() {
-        if node_id == *end_node || node_id == *end_node_edge {
-            println!("Intersection point found: {:?}", node_id);
+    for (index, end) in ends.iter().enumerate() {
+        for target in get_targets(graph, end, options) {
+            targets
+                .entry(target.node_id)
+                .or_default()
+                .push((index, target));
+        }
+
+        // route staying on the snapped edge, used if no route through the graph is shorter
+        let cost =
+            get_direct_edge(graph, start, end, options).map_or(f64::INFINITY, |(_, cost)| cost);
 
-            let mut edge_path: Vec<Edge> = Vec::new();
-            let mut path: Vec<NodeId> = Vec::new();
-            let mut current_node = node_id;
+        if cost.is_finite() {
+            reached.push(QueueEntry {
+                index,
+                distance: cost,
+            });
+        }
+
+        costs.push(cost);
+    }
 
-            while current_node != start_node_edge.from {
-                path.push(current_node);
-                edge_path.push(prev_edges[&current_node].clone());
-                current_node = prev_nodes[&current_node];
+    while let Some(QueueEntry { index, distance }) = heap.pop() {
+        // the costs of the nodes left in the heap are at least `distance`
+        while let Some(end) = reached.peek().copied() {
+            if end.distance > distance {
+                break;
             }
 
-            path.push(*start_node);
+            reached.pop();
 
-            path.reverse();
-            edge_path.reverse();
+            if !settled[end.index] {
+                settled[end.index] = true;
+                unsettled -= 1;
+            }
+        }
 
-            return (Some(path), Some(edge_path));
+        if unsettled == 0 {
+            break;
         }
 
-        if !visited.contains(&node_id) {
-            visited.insert(node_id);
-        } else {
+        if !states.visit(index) {
             continue;
         }
 
-        if let Some(current_dist) = distances.get(&node_id) {
-            if distance > *current_dist {
-                continue;
+        let key = states.keys[index].clone();
+        let node_id = key.0;
+
+        if let Some(node_targets) = targets.get(&node_id) {
+            for (end_index, target) in node_targets {
+                if distance + target.cost < costs[*end_index]
+                    && next_state(graph, options, Some(&key), &target.edge).is_some()
+                {
+                    costs[*end_index] = distance + target.cost;
+                    reached.push(QueueEntry {
+                        index: *end_index,
+                        distance: costs[*end_index],
+                    });
+                }
             }
         }
 
         for edge in graph.get_edges_from_node_fast(&node_id) {
-            if !visited.contains(&edge.to) {
-                let new_dist = distance + edge.weight;
+            let state = match next_state(graph, options, Some(&key), edge) {
+                Some(state) => state,
+                None => continue,
+            };
+
+            let new_dist = distance + options.edge_cost(graph, edge);
+
+            if let Some(next_index) = states.relax(state, new_dist, Some(index), edge.clone()) {
+                heap.push(QueueEntry {
+                    index: next_index,
+                    distance: new_dist,
+                });
+            }
+        }
+    }
+
+    costs
+}
+
+/// Bidirectional Dijkstra algorithm to find the shortest path between two points snapped onto edges
+/// A forward search from the start and a backward search from the end, using the reverse
+/// adjacency `OSMGraph::edges_to_node`, are run alternately until the sum of the smallest
+/// costs in both heaps exceeds the best route found where the searches meet
+/// Returns the same result as `dijkstra`
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `start` - The snapped start point
+/// * `end` - The snapped end point
+/// * `options` - The options of the route
+pub fn bidirectional_dijkstra(
+    graph: &OSMGraph,
+    start: &SnappedPoint,
+    end: &SnappedPoint,
+    options: &RouteOptions,
+) -> (Option<Vec<NodeId>>, Option<Vec<Edge>>) {
+    let mut forward_distances: HashMap<NodeId, f64> = HashMap::new();
+    let mut backward_distances: HashMap<NodeId, f64> = HashMap::new();
+
+    let mut forward_visited: HashSet<NodeId> = HashSet::new();
+    let mut backward_visited: HashSet<NodeId> = HashSet::new();
 
-                if !distances.contains_key(&edge.to) || new_dist < distances[&edge.to] {
-                    heap.push(State::new(edge.to, new_dist));
-                    distances.insert(edge.to, new_dist);
+    let mut forward_heap = BinaryHeap::new();
+    let mut backward_heap = BinaryHeap::new();
+
+    // edge used to reach a node from the start, and edge used to go from a node to the end
+    let mut prev_nodes: HashMap<NodeId, NodeId> = HashMap::new();
+    let mut prev_edges: HashMap<NodeId, Edge> = HashMap::new();
+    let mut next_nodes: HashMap<NodeId, NodeId> = HashMap::new();
+    let mut next_edges: HashMap<NodeId, Edge> = HashMap::new();
+
+    for source in get_sources(graph, start, options) {
+        if source.cost
+            < *forward_distances
+                .get(&source.node_id)
+                .unwrap_or(&f64::INFINITY)
+        {
+            forward_distances.insert(source.node_id, source.cost);
+            forward_heap.push(State::new(source.node_id, source.cost));
+            prev_edges.insert(source.node_id, source.edge);
+        }
+    }
+
+    for target in get_targets(graph, end, options) {
+        if target.cost
+            < *backward_distances
+                .get(&target.node_id)
+                .unwrap_or(&f64::INFINITY)
+        {
+            backward_distances.insert(target.node_id, target.cost);
+            backward_heap.push(State::new(target.node_id, target.cost));
+            next_edges.insert(target.node_id, target.edge);
+        }
+    }
+
+    // route staying on the snapped edge, used if no route through the graph is shorter
+    let direct_edge = get_direct_edge(graph, start, end, options);
+    let mut best_distance = direct_edge
+        .as_ref()
+        .map_or(f64::INFINITY, |(_, cost)| *cost);
+    let mut meeting_node: Option<NodeId> = None;
+
+    for (node_id, forward_distance) in &forward_distances {
+        if let Some(backward_distance) = backward_distances.get(node_id) {
+            if forward_distance + backward_distance < best_distance {
+                best_distance = forward_distance + backward_distance;
+                meeting_node = Some(*node_id);
+            }
+        }
+    }
+
+    loop {
+        let forward_min = forward_heap
+            .peek()
+            .map_or(f64::INFINITY, |state: &State| state.distance);
+        let backward_min = backward_heap
+            .peek()
+            .map_or(f64::INFINITY, |state: &State| state.distance);
+
+        if forward_min + backward_min >= best_distance
+            || (forward_heap.is_empty() && backward_heap.is_empty())
+        {
+            break;
+        }
+
+        // expand the search with the smallest heap, which usually keeps both searches balanced
+        if forward_heap.len() <= backward_heap.len() && !forward_heap.is_empty()
+            || backward_heap.is_empty()
+        {
+            let State { node_id, distance } = forward_heap.pop().unwrap();
+
+            if !forward_visited.insert(node_id) || distance > forward_distances[&node_id] {
+                continue;
+            }
+
+            for edge in graph.get_edges_from_node_fast(&node_id) {
+                let new_dist = distance + options.edge_cost(graph, edge);
+
+                if new_dist < *forward_distances.get(&edge.to).unwrap_or(&f64::INFINITY) {
+                    forward_heap.push(State::new(edge.to, new_dist));
+                    forward_distances.insert(edge.to, new_dist);
                     prev_nodes.insert(edge.to, node_id);
                     prev_edges.insert(edge.to, edge.clone());
+
+                    if let Some(backward_distance) = backward_distances.get(&edge.to) {
+                        if new_dist + backward_distance < best_distance {
+                            best_distance = new_dist + backward_distance;
+                            meeting_node = Some(edge.to);
+                        }
+                    }
+                }
+            }
+        } else {
+            let State { node_id, distance } = backward_heap.pop().unwrap();
+
+            if !backward_visited.insert(node_id) || distance > backward_distances[&node_id] {
+                continue;
+            }
+
+            for edge in graph.get_edges_to_node_fast(&node_id) {
+                let new_dist = distance + options.edge_cost(graph, edge);
+
+                if new_dist < *backward_distances.get(&edge.from).unwrap_or(&f64::INFINITY) {
+                    backward_heap.push(State::new(edge.from, new_dist));
+                    backward_distances.insert(edge.from, new_dist);
+                    next_nodes.insert(edge.from, node_id);
+                    next_edges.insert(edge.from, edge.clone());
+
+                    if let Some(forward_distance) = forward_distances.get(&edge.from) {
+                        if new_dist + forward_distance < best_distance {
+                            best_distance = new_dist + forward_distance;
+                            meeting_node = Some(edge.from);
+                        }
+                    }
                 }
             }
         }
     }
 
+    if let Some(meeting_node) = meeting_node {
+        let mut path: Vec<NodeId> = Vec::new();
+        let mut edge_path: Vec<Edge> = Vec::new();
+
+        // from the meeting node back to the start, the start edges have no previous node
+        let mut current_node = meeting_node;
+
+        loop {
+            path.push(current_node);
+            edge_path.push(prev_edges[&current_node].clone());
+
+            match prev_nodes.get(&current_node) {
+                Some(prev_node) => current_node = *prev_node,
+                None => break,
+            }
+        }
+
+        path.reverse();
+        edge_path.reverse();
+
+        // from the meeting node to the end, the end edges have no next node
+        current_node = meeting_node;
+
+        loop {
+            edge_path.push(next_edges[&current_node].clone());
+
+            match next_nodes.get(&current_node) {
+                Some(next_node) => {
+                    current_node = *next_node;
+                    path.push(current_node);
+                }
+                None => break,
+            }
+        }
+
+        return (Some(path), Some(edge_path));
+    }
+
+    if let Some((edge, _)) = direct_edge {
+        return (Some(vec![edge.from, edge.to]), Some(vec![edge]));
+    }
+
     warn!("No path found");
 
     (None, None) // No path found
 }
 
-/// Function generating random points around a start node
-/// Returns a vector of node ids
+/// Function generating random points around a start point
+/// Returns a vector of points snapped onto the edges of the graph, None if a point can't be snapped
 /// # Arguments
 /// * `distance_km` - The distance in kilometers for the path. We divide by 2 to get the radius
 /// * `graph` - The graph to search in
-/// * `start_node` - The start node
+/// * `start_point` - The start point
 pub fn generate_random_points(
     distance_km: f64,
     graph: &OSMGraph,
-    start_node: &NodeId,
-) -> Vec<NodeId> {
+    start_point: &SnappedPoint,
+) -> Option<Vec<SnappedPoint>> {
     let mut rng = rand::thread_rng();
     let mut points = Vec::new();
 
-    let start_lat = graph.get_node(*start_node).unwrap().lat();
-    let start_lon = graph.get_node(*start_node).unwrap().lon();
+    let start_lat = start_point.lat;
+    let start_lon = start_point.lon;
 
     // Convert distance_km from kilometers to degrees (assuming Mercator projection)
     // 1 degree of latitude is approximately 111.32 km
@@ -138,38 +1131,42 @@ pub fn generate_random_points(
         let lat = start_lat + distance_deg_random * angle.cos();
         let lon = start_lon + distance_deg_random * angle.sin();
 
-        let node_id = graph.get_nearest_node(lat, lon).unwrap();
-        points.push(node_id);
+        let point = graph.snap_to_edge(lat, lon)?;
+        points.push(point);
     }
 
-    points
+    Some(points)
 }
 
-/// Function generating a random loop around a start node
-/// Returns a vector of tuples containing the nodes ids and the edges of the path
+/// Function generating a random loop around a start point
+/// Returns a vector of tuples containing the nodes ids and the edges of each leg of the loop
+/// If a random point can't be snapped or a leg has no path, returns None. This is a normal
+/// outcome when the edges around a point are forbidden or closed at the departure time
 /// # Arguments
 /// * `distance` - The distance in kilometers for the path.
 /// * `graph` - The graph to search in
-/// * `start_node` - The start node
+/// * `start_point` - The start point
+/// * `options` - The options of the route
 pub fn generate_random_loop(
     distance: f64,
     graph: &OSMGraph,
-    start_node: &NodeId,
-) -> Vec<(Vec<NodeId>, Vec<Edge>)> {
-    let mut points = generate_random_points(distance, graph, start_node);
+    start_point: &SnappedPoint,
+    options: &RouteOptions,
+) -> Option<Vec<RouteLeg>> {
+    let mut points = generate_random_points(distance, graph, start_point)?;
 
     let mut path = Vec::new();
 
-    let mut first_lat = graph.get_node(*start_node).unwrap().lat();
-    let mut first_lon = graph.get_node(*start_node).unwrap().lon();
+    let mut first_lat = start_point.lat;
+    let mut first_lon = start_point.lon;
 
     for _ in 0..points.len() {
         let mut min_distance = f64::INFINITY;
         let mut min_index = 0;
 
         for (index, point) in points.iter().enumerate() {
-            let lat = graph.get_node(*point).unwrap().lat();
-            let lon = graph.get_node(*point).unwrap().lon();
+            let lat = point.lat;
+            let lon = point.lon;
 
             let distance = (lat - first_lat).powi(2) + (lon - first_lon).powi(2);
 
@@ -179,24 +1176,154 @@ pub fn generate_random_loop(
             }
         }
 
-        first_lat = graph.get_node(points[min_index]).unwrap().lat();
-        first_lon = graph.get_node(points[min_index]).unwrap().lon();
+        first_lat = points[min_index].lat;
+        first_lon = points[min_index].lon;
+
+        path.push(points.remove(min_index));
+    }
+
+    path.insert(0, start_point.clone());
+    path.push(start_point.clone());
+
+    find_path_through(graph, &path, Algorithm::Dijkstra, options).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::contraction_hierarchies::ContractionHierarchy;
+    use crate::test_graph::{grid, path_ways, tags};
+    use crate::turn_restrictions::TurnRestriction;
+    use osmpbfreader::objects::{OsmId, Ref, Relation, RelationId};
+
+    /// Build a restriction relation
+    /// # Arguments
+    /// * `restriction` - The value of the restriction tag, such as `no_left_turn`
+    /// * `from` - The id of the `from` way
+    /// * `via` - The `via` node or ways
+    /// * `to` - The id of the `to` way
+    /// # Returns
+    /// * `Relation` - The relation
+    fn relation(restriction: &str, from: i64, via: &[OsmId], to: i64) -> Relation {
+        let mut refs = vec![Ref {
+            member: OsmId::Way(WayId(from)),
+            role: "from".into(),
+        }];
+
+        for member in via {
+            refs.push(Ref {
+                member: *member,
+                role: "via".into(),
+            });
+        }
+
+        refs.push(Ref {
+            member: OsmId::Way(WayId(to)),
+            role: "to".into(),
+        });
 
-        path.push(points[min_index]);
-        points.remove(min_index);
+        Relation {
+            id: RelationId(1),
+            tags: tags(&[("type", "restriction"), ("restriction", restriction)]),
+            refs,
+        }
     }
 
-    path.insert(0, *start_node);
-    path.push(*start_node);
+    /// Route from the first horizontal road of the grid to a point, without and with a restriction
+    /// Checks that the route without restriction takes the forbidden turn, and that every
+    /// algorithm avoids it once the restriction is added
+    /// # Arguments
+    /// * `restriction` - The restriction relation
+    /// * `to` - The latitude and longitude of the end point
+    /// * `forbidden_ways` - The ways of the shortest route, with the forbidden turn
+    /// * `legal_ways` - The ways of the shortest route following the restriction
+    fn check_restriction(
+        restriction: Relation,
+        to: (f64, f64),
+        forbidden_ways: &[i64],
+        legal_ways: &[i64],
+    ) {
+        let mut graph = grid();
+        let options = RouteOptions::default();
+        let start = graph.snap_to_edge(47.0, 7.0025).unwrap();
+        let end = graph.snap_to_edge(to.0, to.1).unwrap();
+
+        let (_, edges) = dijkstra(&graph, &start, &end, &options);
+        assert_eq!(path_ways(&edges.unwrap()), forbidden_ways);
+
+        for turn_restriction in TurnRestriction::from_relation(&restriction) {
+            graph.turn_restrictions.add(turn_restriction);
+        }
+        assert_eq!(graph.turn_restrictions.len(), 1);
 
-    let mut route = Vec::new();
+        graph.contraction_hierarchy = Some(ContractionHierarchy::build(&graph));
 
-    for i in 0..path.len() - 1 {
-        let (nodes, edges) = dijkstra(graph, &path[i], &path[i + 1]);
-        // unwrap edges and nodes
-        let nodes = nodes.unwrap();
+        let (_, edges) = dijkstra(&graph, &start, &end, &options);
         let edges = edges.unwrap();
-        route.push((nodes, edges));
+        assert_eq!(path_ways(&edges), legal_ways);
+        assert!(graph.turn_restrictions.allows_path(&edges));
+
+        let (a_star_nodes, a_star_edges) = a_star(&graph, &start, &end, &options);
+        assert_eq!(path_ways(a_star_edges.as_ref().unwrap()), legal_ways);
+
+        // the node-based searches ignore the restriction, find_path falls back to A*
+        let (_, edges) = bidirectional_dijkstra(&graph, &start, &end, &options);
+        assert_eq!(path_ways(&edges.unwrap()), forbidden_ways);
+
+        for algorithm in [Algorithm::Bidirectional, Algorithm::ContractionHierarchies] {
+            let (nodes, edges) = find_path(&graph, &start, &end, algorithm, &options);
+
+            assert_eq!(nodes, a_star_nodes, "{:?}", algorithm);
+            assert_eq!(
+                path_ways(&edges.unwrap()),
+                path_ways(a_star_edges.as_ref().unwrap()),
+                "{:?}",
+                algorithm
+            );
+        }
+    }
+
+    #[test]
+    fn no_left_turn_is_avoided() {
+        // heading east on way 2, turning north onto way 9 at node 3 is a left turn
+        check_restriction(
+            relation("no_left_turn", 2, &[OsmId::Node(NodeId(3))], 9),
+            (47.005, 7.02),
+            &[1, 2, 9],
+            &[1, 8, 4, 9],
+        );
+    }
+
+    #[test]
+    fn only_straight_on_is_followed() {
+        // from way 1, only way 2 can be taken at node 2, so way 8 is reached from node 5
+        check_restriction(
+            relation("only_straight_on", 1, &[OsmId::Node(NodeId(2))], 2),
+            (47.005, 7.01),
+            &[1, 8],
+            &[1, 7, 3, 8],
+        );
+    }
+
+    #[test]
+    fn via_way_restriction_is_avoided() {
+        // from way 1, turning onto way 4 after following way 8 is forbidden, but way 4 can
+        // still be reached straight on from way 3
+        check_restriction(
+            relation("no_right_turn", 1, &[OsmId::Way(WayId(8))], 4),
+            (47.01, 7.015),
+            &[1, 8, 4],
+            &[1, 7, 3, 4],
+        );
+    }
+
+    #[test]
+    fn motorcycle_exception_is_ignored() {
+        let mut restriction = relation("no_left_turn", 2, &[OsmId::Node(NodeId(3))], 9);
+        restriction
+            .tags
+            .insert("except".into(), "bicycle;motorcycle".into());
+
+        assert!(TurnRestriction::from_relation(&restriction).is_empty());
     }
-    route
 }
@@ -0,0 +1,408 @@
+use crate::osm_graph::OSMGraph;
+use crate::osm_graph::SnappedPoint;
+use crate::route_calculation::{dijkstra_to_many, RouteOptions};
+
+/// Longest sequence of stops moved at once by the Or-opt moves
+const OR_OPT_MAX_SEGMENT: usize = 3;
+
+/// Constraints on the visiting order of the stops
+/// # Fields
+/// * `fixed_start` - The first stop stays the first one
+/// * `fixed_end` - The last stop stays the last one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrderConstraints {
+    pub fixed_start: bool,
+    pub fixed_end: bool,
+}
+
+/// Compute the cost matrix between stops, with one Dijkstra search per stop
+/// # Arguments
+/// * `graph` - The graph to search in
+/// * `stops` - The snapped stops
+/// * `options` - The options of the route
+/// # Returns
+/// * `Vec<Vec<f64>>` - The cost from each stop (row) to each stop (column), infinite if there is no path
+pub fn cost_matrix(
+    graph: &OSMGraph,
+    stops: &[SnappedPoint],
+    options: &RouteOptions,
+) -> Vec<Vec<f64>> {
+    stops
+        .iter()
+        .enumerate()
+        .map(|(index, stop)| {
+            let mut costs = dijkstra_to_many(graph, stop, stops, options);
+            costs[index] = 0.0;
+            costs
+        })
+        .collect()
+}
+
+/// Get the cost of visiting the stops in an order
+/// # Arguments
+/// * `matrix` - The cost matrix between the stops
+/// * `order` - The indexes of the stops in visiting order
+/// # Returns
+/// * `f64` - The sum of the costs between consecutive stops
+pub fn order_cost(matrix: &[Vec<f64>], order: &[usize]) -> f64 {
+    order.windows(2).map(|pair| matrix[pair[0]][pair[1]]).sum()
+}
+
+/// Find a short visiting order of the stops
+/// A nearest neighbor order is improved with 2-opt and Or-opt moves until no move reduces the
+/// cost. The costs may be asymmetric, so every move is evaluated on the whole order
+/// # Arguments
+/// * `matrix` - The cost matrix between the stops
+/// * `constraints` - The constraints on the first and last stops
+/// # Returns
+/// * `Vec<usize>` - The indexes of the stops in visiting order
+pub fn optimize_order(matrix: &[Vec<f64>], constraints: OrderConstraints) -> Vec<usize> {
+    let count = matrix.len();
+
+    if count < 3 {
+        return (0..count).collect();
+    }
+
+    // range of the positions that can change
+    let first = if constraints.fixed_start { 1 } else { 0 };
+    let last = if constraints.fixed_end {
+        count - 2
+    } else {
+        count - 1
+    };
+
+    let mut order = nearest_neighbor_order(matrix, constraints);
+    let mut cost = order_cost(matrix, &order);
+
+    let mut improved = true;
+
+    while improved {
+        improved = false;
+
+        // 2-opt: reverse a part of the order
+        for i in first..last {
+            for j in i + 1..=last {
+                let mut candidate = order.clone();
+                candidate[i..=j].reverse();
+
+                let candidate_cost = order_cost(matrix, &candidate);
+
+                if candidate_cost < cost {
+                    order = candidate;
+                    cost = candidate_cost;
+                    improved = true;
+                }
+            }
+        }
+
+        // Or-opt: move a short sequence of stops to another position
+        for length in 1..=OR_OPT_MAX_SEGMENT {
+            for i in first..=last {
+                if i + length > last + 1 {
+                    break;
+                }
+
+                for position in first..=last + 1 - length {
+                    if position == i {
+                        continue;
+                    }
+
+                    let mut candidate = order.clone();
+                    let segment: Vec<usize> = candidate.drain(i..i + length).collect();
+                    candidate.splice(position..position, segment);
+
+                    let candidate_cost = order_cost(matrix, &candidate);
+
+                    if candidate_cost < cost {
+                        order = candidate;
+                        cost = candidate_cost;
+                        improved = true;
+                    }
+                }
+            }
+        }
+    }
+
+    order
+}
+
+/// Build a visiting order by always going to the nearest stop not yet visited
+/// Without fixed start, the order is built from every stop and the cheapest one is kept
+/// # Arguments
+/// * `matrix` - The cost matrix between the stops
+/// * `constraints` - The constraints on the first and last stops
+/// # Returns
+/// * `Vec<usize>` - The indexes of the stops in visiting order
+fn nearest_neighbor_order(matrix: &[Vec<f64>], constraints: OrderConstraints) -> Vec<usize> {
+    let count = matrix.len();
+    let end = if constraints.fixed_end {
+        Some(count - 1)
+    } else {
+        None
+    };
+
+    let starts: Vec<usize> = if constraints.fixed_start {
+        vec![0]
+    } else {
+        (0..count).filter(|stop| Some(*stop) != end).collect()
+    };
+
+    let mut best_order: Vec<usize> = (0..count).collect();
+    let mut best_cost = f64::INFINITY;
+
+    for start in starts {
+        let mut order = vec![start];
+        let mut remaining: Vec<usize> = (0..count)
+            .filter(|stop| *stop != start && Some(*stop) != end)
+            .collect();
+
+        while !remaining.is_empty() {
+            let current = order[order.len() - 1];
+
+            let (position, _) = remaining
+                .iter()
+                .enumerate()
+                .min_by(|(_, a), (_, b)| matrix[current][**a].total_cmp(&matrix[current][**b]))
+                .unwrap();
+
+            order.push(remaining.remove(position));
+        }
+
+        if let Some(end) = end {
+            order.push(end);
+        }
+
+        let cost = order_cost(matrix, &order);
+
+        if cost < best_cost {
+            best_cost = cost;
+            best_order = order;
+        }
+    }
+
+    best_order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::route_calculation::dijkstra;
+    use crate::test_graph::{add_road, jittered_grid, node};
+
+    /// Build the cost matrix of points in a plane, the cost being the euclidean distance
+    /// # Arguments
+    /// * `points` - The coordinates of the points
+    /// # Returns
+    /// * `Vec<Vec<f64>>` - The cost matrix
+    fn plane_matrix(points: &[(f64, f64)]) -> Vec<Vec<f64>> {
+        points
+            .iter()
+            .map(|from| {
+                points
+                    .iter()
+                    .map(|to| ((to.0 - from.0).powi(2) + (to.1 - from.1).powi(2)).sqrt())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Get the cost of the best order by trying every permutation of the stops
+    /// # Arguments
+    /// * `matrix` - The cost matrix between the stops
+    /// * `constraints` - The constraints on the first and last stops
+    /// # Returns
+    /// * `f64` - The cost of the best order
+    fn best_cost(matrix: &[Vec<f64>], constraints: OrderConstraints) -> f64 {
+        fn visit(
+            matrix: &[Vec<f64>],
+            constraints: OrderConstraints,
+            order: &mut Vec<usize>,
+            best: &mut f64,
+        ) {
+            let count = matrix.len();
+
+            if order.len() == count {
+                let valid = (!constraints.fixed_start || order[0] == 0)
+                    && (!constraints.fixed_end || order[count - 1] == count - 1);
+
+                if valid {
+                    *best = best.min(order_cost(matrix, order));
+                }
+
+                return;
+            }
+
+            for stop in 0..count {
+                if !order.contains(&stop) {
+                    order.push(stop);
+                    visit(matrix, constraints, order, best);
+                    order.pop();
+                }
+            }
+        }
+
+        let mut best = f64::INFINITY;
+        visit(matrix, constraints, &mut Vec::new(), &mut best);
+
+        best
+    }
+
+    /// Check that an order visits every stop once and follows the constraints
+    /// # Arguments
+    /// * `order` - The indexes of the stops in visiting order
+    /// * `count` - The number of stops
+    /// * `constraints` - The constraints on the first and last stops
+    fn assert_valid_order(order: &[usize], count: usize, constraints: OrderConstraints) {
+        let mut stops = order.to_vec();
+        stops.sort();
+        assert_eq!(stops, (0..count).collect::<Vec<_>>());
+
+        if constraints.fixed_start {
+            assert_eq!(order[0], 0);
+        }
+
+        if constraints.fixed_end {
+            assert_eq!(order[count - 1], count - 1);
+        }
+    }
+
+    const FREE: OrderConstraints = OrderConstraints {
+        fixed_start: false,
+        fixed_end: false,
+    };
+
+    const FIXED_START: OrderConstraints = OrderConstraints {
+        fixed_start: true,
+        fixed_end: false,
+    };
+
+    const FIXED_END: OrderConstraints = OrderConstraints {
+        fixed_start: false,
+        fixed_end: true,
+    };
+
+    const BOTH_FIXED: OrderConstraints = OrderConstraints {
+        fixed_start: true,
+        fixed_end: true,
+    };
+
+    #[test]
+    fn stops_on_a_line() {
+        let matrix = plane_matrix(&[
+            (5.0, 0.0),
+            (0.0, 0.0),
+            (9.0, 0.0),
+            (2.0, 0.0),
+            (7.0, 0.0),
+            (1.0, 0.0),
+        ]);
+
+        let order = optimize_order(&matrix, FREE);
+        assert_valid_order(&order, 6, FREE);
+        assert_eq!(order_cost(&matrix, &order), 9.0);
+
+        // from 5, to the closer end of the line first
+        let order = optimize_order(&matrix, FIXED_START);
+        assert_eq!(order, vec![0, 4, 2, 3, 5, 1]);
+        assert_eq!(order_cost(&matrix, &order), 13.0);
+
+        let order = optimize_order(&matrix, FIXED_END);
+        assert_eq!(order, vec![2, 4, 0, 3, 1, 5]);
+        assert_eq!(order_cost(&matrix, &order), 10.0);
+
+        let order = optimize_order(&matrix, BOTH_FIXED);
+        assert_eq!(order, vec![0, 4, 2, 3, 1, 5]);
+        assert_eq!(order_cost(&matrix, &order), 14.0);
+    }
+
+    #[test]
+    fn orders_are_optimal_on_small_sets() {
+        let points: Vec<(f64, f64)> = (0..7)
+            .map(|index| (((index * 37) % 11) as f64, ((index * 53) % 13) as f64))
+            .collect();
+        let matrix = plane_matrix(&points);
+
+        for constraints in [FREE, FIXED_START, FIXED_END, BOTH_FIXED] {
+            let order = optimize_order(&matrix, constraints);
+
+            assert_valid_order(&order, 7, constraints);
+            assert!(
+                (order_cost(&matrix, &order) - best_cost(&matrix, constraints)).abs() < 1e-9,
+                "{:?} {:?}",
+                constraints,
+                order
+            );
+        }
+    }
+
+    #[test]
+    fn asymmetric_costs() {
+        // going around the stops clockwise is cheap, counterclockwise is expensive
+        let count = 5;
+        let matrix: Vec<Vec<f64>> = (0..count)
+            .map(|from| {
+                (0..count)
+                    .map(|to| match (to + count - from) % count {
+                        0 => 0.0,
+                        1 => 1.0,
+                        _ => 10.0,
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let order = optimize_order(&matrix, FIXED_START);
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+
+        let order = optimize_order(&matrix, FIXED_END);
+        assert_eq!(order, vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn small_sets_keep_their_order() {
+        assert_eq!(optimize_order(&[], FREE), Vec::<usize>::new());
+        assert_eq!(
+            optimize_order(&[vec![0.0, 5.0], vec![1.0, 0.0]], FREE),
+            vec![0, 1]
+        );
+    }
+
+    #[test]
+    fn cost_matrix_matches_dijkstra() {
+        let mut graph = jittered_grid(4);
+
+        // a road that can't be reached from the grid
+        graph.add_node(&node(1001, 47.2, 7.2));
+        graph.add_node(&node(1002, 47.2, 7.21));
+        add_road(&mut graph, 1001, &[1001, 1002]);
+        graph.build_spatial_index();
+
+        let options = RouteOptions::default();
+        let stops: Vec<SnappedPoint> = [
+            (47.0, 7.005),
+            (47.021, 7.026),
+            (47.2, 7.205),
+            (47.032, 7.003),
+        ]
+        .iter()
+        .map(|(lat, lon)| graph.snap_to_edge(*lat, *lon).unwrap())
+        .collect();
+
+        let matrix = cost_matrix(&graph, &stops, &options);
+
+        for (from, row) in matrix.iter().enumerate() {
+            for (to, cost) in row.iter().enumerate() {
+                if from == to {
+                    assert_eq!(*cost, 0.0);
+                } else if from == 2 || to == 2 {
+                    assert_eq!(*cost, f64::INFINITY);
+                } else {
+                    let (_, edges) = dijkstra(&graph, &stops[from], &stops[to], &options);
+                    let expected = options.path_cost(&graph, &edges.unwrap());
+
+                    assert!((cost - expected).abs() < 1e-6, "{} {}", from, to);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,548 @@
+use crate::osm_graph::OSMGraph;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Size of a grid cell in degrees (about 1.1 km in latitude, 0.75 km in longitude in Switzerland)
+const CELL_SIZE_DEG: f64 = 0.01;
+
+/// Length of one degree of latitude in meters, consistent with `OSMGraph::haversine_distance`
+const METERS_PER_DEGREE: f64 = 6371.0 * 1000.0 * std::f64::consts::PI / 180.0;
+
+/// Get the cell containing a coordinate
+/// # Arguments
+/// * `lat` - The latitude
+/// * `lon` - The longitude
+/// # Returns
+/// * `(i32, i32)` - The cell coordinates (latitude index, longitude index)
+fn cell_of(lat: f64, lon: f64) -> (i32, i32) {
+    (
+        (lat / CELL_SIZE_DEG).floor() as i32,
+        (lon / CELL_SIZE_DEG).floor() as i32,
+    )
+}
+
+/// Get the smallest width of a cell in meters between the given cell latitudes
+/// # Arguments
+/// * `min_lat_cell` - The smallest latitude index
+/// * `max_lat_cell` - The biggest latitude index
+/// # Returns
+/// * `f64` - The width in meters
+fn min_cell_width(min_lat_cell: i32, max_lat_cell: i32) -> f64 {
+    let max_abs_lat = (min_lat_cell as f64 * CELL_SIZE_DEG)
+        .abs()
+        .max(((max_lat_cell + 1) as f64 * CELL_SIZE_DEG).abs())
+        .min(90.0);
+
+    CELL_SIZE_DEG * METERS_PER_DEGREE * max_abs_lat.to_radians().cos()
+}
+
+/// Get the cells at a given Chebyshev distance from a center cell
+/// # Arguments
+/// * `center` - The center cell
+/// * `ring` - The distance in cells
+/// # Returns
+/// * `Vec<(i32, i32)>` - The cells of the ring
+fn ring_cells(center: (i32, i32), ring: i32) -> Vec<(i32, i32)> {
+    if ring == 0 {
+        return vec![center];
+    }
+
+    let mut cells = Vec::with_capacity(8 * ring as usize);
+
+    for d in -ring..=ring {
+        cells.push((center.0 - ring, center.1 + d));
+        cells.push((center.0 + ring, center.1 + d));
+    }
+
+    for d in -ring + 1..ring {
+        cells.push((center.0 + d, center.1 - ring));
+        cells.push((center.0 + d, center.1 + ring));
+    }
+
+    cells
+}
+
+/// Segment of an edge stored in the edge index
+/// # Fields
+/// * `edge_index` - The index of the edge in `OSMGraph::edges`
+/// * `segment_index` - The index of the segment in the edge (segment `i` goes from node `i` to node `i + 1`)
+/// * `from` - The latitude and longitude of the first point of the segment
+/// * `to` - The latitude and longitude of the last point of the segment
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct IndexedSegment {
+    pub edge_index: usize,
+    pub segment_index: usize,
+    pub from: (f64, f64),
+    pub to: (f64, f64),
+}
+
+/// Projection of a coordinate onto a segment
+/// # Fields
+/// * `segment` - The segment
+/// * `fraction` - The position of the projected point on the segment (0.0 = first point, 1.0 = last point)
+/// * `lat` - The latitude of the projected point
+/// * `lon` - The longitude of the projected point
+/// * `distance_m` - The distance between the coordinate and the projected point in meters
+#[derive(Debug, Clone, Copy)]
+pub struct SegmentProjection {
+    pub segment: IndexedSegment,
+    pub fraction: f64,
+    pub lat: f64,
+    pub lon: f64,
+    pub distance_m: f64,
+}
+
+/// Uniform grid spatial index over the segments of the edges of the graph
+/// A segment is stored in every cell intersecting its bounding box
+/// # Fields
+/// * `cells` - The segments of each cell (key: cell coordinates, value: segments in the cell)
+/// * `min_cell` - The smallest cell coordinates containing a segment
+/// * `max_cell` - The biggest cell coordinates containing a segment
+/// * `min_cell_width_m` - The smallest width of a cell in meters, used to stop the ring search
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EdgeIndex {
+    cells: HashMap<(i32, i32), Vec<IndexedSegment>>,
+    min_cell: (i32, i32),
+    max_cell: (i32, i32),
+    min_cell_width_m: f64,
+}
+
+/// EdgeIndex implementation
+impl EdgeIndex {
+    /// Create a new empty EdgeIndex
+    /// # Returns
+    /// * `EdgeIndex` - The new EdgeIndex
+    pub fn new() -> Self {
+        EdgeIndex::default()
+    }
+
+    /// Build a spatial index containing every segment of every edge of a graph
+    /// # Arguments
+    /// * `graph` - The graph to index
+    /// # Returns
+    /// * `EdgeIndex` - The spatial index of the segments of the graph
+    pub fn from_graph(graph: &OSMGraph) -> Self {
+        let mut index = EdgeIndex::new();
+
+        for (edge_index, edge) in graph.get_edges().iter().enumerate() {
+            for segment_index in 0..edge.nodes_ids.len().saturating_sub(1) {
+                let from = graph.get_node(edge.nodes_ids[segment_index]);
+                let to = graph.get_node(edge.nodes_ids[segment_index + 1]);
+
+                if let (Some(from), Some(to)) = (from, to) {
+                    index.insert(IndexedSegment {
+                        edge_index,
+                        segment_index,
+                        from: (from.lat(), from.lon()),
+                        to: (to.lat(), to.lon()),
+                    });
+                }
+            }
+        }
+
+        index
+    }
+
+    /// Add a segment to the index
+    /// # Arguments
+    /// * `segment` - The segment to add
+    pub fn insert(&mut self, segment: IndexedSegment) {
+        let (min_lat_cell, min_lon_cell) = cell_of(
+            segment.from.0.min(segment.to.0),
+            segment.from.1.min(segment.to.1),
+        );
+        let (max_lat_cell, max_lon_cell) = cell_of(
+            segment.from.0.max(segment.to.0),
+            segment.from.1.max(segment.to.1),
+        );
+
+        if self.cells.is_empty() {
+            self.min_cell = (min_lat_cell, min_lon_cell);
+            self.max_cell = (max_lat_cell, max_lon_cell);
+        } else {
+            self.min_cell = (
+                self.min_cell.0.min(min_lat_cell),
+                self.min_cell.1.min(min_lon_cell),
+            );
+            self.max_cell = (
+                self.max_cell.0.max(max_lat_cell),
+                self.max_cell.1.max(max_lon_cell),
+            );
+        }
+
+        self.min_cell_width_m = min_cell_width(self.min_cell.0, self.max_cell.0);
+
+        for lat_cell in min_lat_cell..=max_lat_cell {
+            for lon_cell in min_lon_cell..=max_lon_cell {
+                self.cells
+                    .entry((lat_cell, lon_cell))
+                    .or_default()
+                    .push(segment);
+            }
+        }
+    }
+
+    /// Check if the index is empty
+    /// # Returns
+    /// * `bool` - True if the index contains no segment
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Get the segment nearest to a given latitude and longitude, and the projection of the coordinate onto it
+    /// # Arguments
+    /// * `lat` - The latitude
+    /// * `lon` - The longitude
+    /// # Returns
+    /// * `Option<SegmentProjection>` - The projection onto the nearest segment, None if the index is empty
+    pub fn nearest(&self, lat: f64, lon: f64) -> Option<SegmentProjection> {
+        self.k_nearest(lat, lon, 1).into_iter().next()
+    }
+
+    /// Get the k edges nearest to a given latitude and longitude
+    /// The search visits rings of cells around the cell of the coordinate and stops as soon as no
+    /// segment of the next ring can be closer than the k-th edge found. Each edge is given by the
+    /// projection of the coordinate onto its nearest segment
+    /// # Arguments
+    /// * `lat` - The latitude
+    /// * `lon` - The longitude
+    /// * `k` - The number of edges to return
+    /// # Returns
+    /// * `Vec<SegmentProjection>` - The projections onto the nearest segment of each edge, sorted by distance
+    pub fn k_nearest(&self, lat: f64, lon: f64, k: usize) -> Vec<SegmentProjection> {
+        let mut best_by_edge: HashMap<usize, SegmentProjection> = HashMap::new();
+
+        if k == 0 || self.is_empty() {
+            return Vec::new();
+        }
+
+        let center = cell_of(lat, lon);
+        let cell_width = self
+            .min_cell_width_m
+            .min(min_cell_width(center.0, center.0));
+
+        // number of rings needed to cover every cell of the index from the center cell
+        let max_ring = (center.0 - self.min_cell.0)
+            .abs()
+            .max((center.0 - self.max_cell.0).abs())
+            .max((center.1 - self.min_cell.1).abs())
+            .max((center.1 - self.max_cell.1).abs());
+
+        for ring in 0..=max_ring {
+            for cell in ring_cells(center, ring) {
+                if let Some(segments) = self.cells.get(&cell) {
+                    for segment in segments {
+                        EdgeIndex::keep_nearest(
+                            &mut best_by_edge,
+                            EdgeIndex::project(lat, lon, segment),
+                        );
+                    }
+                }
+            }
+
+            // segments not seen yet are only stored in cells at least `ring` cells away
+            if best_by_edge.len() >= k {
+                let mut distances: Vec<f64> = best_by_edge
+                    .values()
+                    .map(|projection| projection.distance_m)
+                    .collect();
+                distances.sort_by(f64::total_cmp);
+
+                if distances[k - 1] <= ring as f64 * cell_width {
+                    break;
+                }
+            }
+        }
+
+        let mut projections = EdgeIndex::sorted_by_distance(best_by_edge);
+        projections.truncate(k);
+
+        projections
+    }
+
+    /// Get the edges within a given radius from a latitude and longitude
+    /// Each edge is given by the projection of the coordinate onto its nearest segment
+    /// # Arguments
+    /// * `lat` - The latitude
+    /// * `lon` - The longitude
+    /// * `radius_m` - The radius in meters
+    /// # Returns
+    /// * `Vec<SegmentProjection>` - The projections onto the nearest segment of each edge, sorted by distance
+    pub fn within_radius(&self, lat: f64, lon: f64, radius_m: f64) -> Vec<SegmentProjection> {
+        let mut best_by_edge: HashMap<usize, SegmentProjection> = HashMap::new();
+
+        let delta_lat = radius_m / METERS_PER_DEGREE;
+        let max_abs_lat = (lat.abs() + delta_lat).min(89.9);
+        let delta_lon = radius_m / (METERS_PER_DEGREE * max_abs_lat.to_radians().cos());
+
+        for segment in self.segments_in_bbox(
+            lat - delta_lat,
+            lon - delta_lon,
+            lat + delta_lat,
+            lon + delta_lon,
+        ) {
+            let projection = EdgeIndex::project(lat, lon, &segment);
+
+            if projection.distance_m <= radius_m {
+                EdgeIndex::keep_nearest(&mut best_by_edge, projection);
+            }
+        }
+
+        EdgeIndex::sorted_by_distance(best_by_edge)
+    }
+
+    /// Keep a projection if it is the nearest one found for its edge
+    /// # Arguments
+    /// * `best_by_edge` - The nearest projection found for each edge (key: index of the edge)
+    /// * `projection` - The projection onto a segment
+    fn keep_nearest(
+        best_by_edge: &mut HashMap<usize, SegmentProjection>,
+        projection: SegmentProjection,
+    ) {
+        let best = best_by_edge
+            .entry(projection.segment.edge_index)
+            .or_insert(projection);
+
+        if projection.distance_m < best.distance_m {
+            *best = projection;
+        }
+    }
+
+    /// Sort the projections of the edges by distance
+    /// # Arguments
+    /// * `best_by_edge` - The nearest projection found for each edge
+    /// # Returns
+    /// * `Vec<SegmentProjection>` - The projections, sorted by distance then by edge index
+    fn sorted_by_distance(
+        best_by_edge: HashMap<usize, SegmentProjection>,
+    ) -> Vec<SegmentProjection> {
+        let mut projections: Vec<SegmentProjection> = best_by_edge.into_values().collect();
+
+        projections.sort_by(|a, b| {
+            a.distance_m
+                .total_cmp(&b.distance_m)
+                .then(a.segment.edge_index.cmp(&b.segment.edge_index))
+        });
+
+        projections
+    }
+
+    /// Get the segments stored in the cells intersecting a bounding box
+    /// A segment can be returned more than once if it spans several cells
+    /// # Arguments
+    /// * `min_lat` - The minimum latitude
+    /// * `min_lon` - The minimum longitude
+    /// * `max_lat` - The maximum latitude
+    /// * `max_lon` - The maximum longitude
+    /// # Returns
+    /// * `Vec<IndexedSegment>` - The candidate segments
+    pub fn segments_in_bbox(
+        &self,
+        min_lat: f64,
+        min_lon: f64,
+        max_lat: f64,
+        max_lon: f64,
+    ) -> Vec<IndexedSegment> {
+        let mut segments = Vec::new();
+
+        if self.is_empty() {
+            return segments;
+        }
+
+        let (min_lat_cell, min_lon_cell) = cell_of(min_lat, min_lon);
+        let (max_lat_cell, max_lon_cell) = cell_of(max_lat, max_lon);
+
+        for lat_cell in min_lat_cell.max(self.min_cell.0)..=max_lat_cell.min(self.max_cell.0) {
+            for lon_cell in min_lon_cell.max(self.min_cell.1)..=max_lon_cell.min(self.max_cell.1) {
+                if let Some(cell_segments) = self.cells.get(&(lat_cell, lon_cell)) {
+                    segments.extend_from_slice(cell_segments);
+                }
+            }
+        }
+
+        segments
+    }
+
+    /// Project a coordinate onto a segment
+    /// The projection is done in a local equirectangular projection centered on the coordinate,
+    /// which is accurate for the length of a road segment
+    /// # Arguments
+    /// * `lat` - The latitude
+    /// * `lon` - The longitude
+    /// * `segment` - The segment
+    /// # Returns
+    /// * `SegmentProjection` - The projection of the coordinate onto the segment
+    pub fn project(lat: f64, lon: f64, segment: &IndexedSegment) -> SegmentProjection {
+        let cos_lat = lat.to_radians().cos();
+
+        let ax = (segment.from.1 - lon) * cos_lat;
+        let ay = segment.from.0 - lat;
+        let bx = (segment.to.1 - lon) * cos_lat;
+        let by = segment.to.0 - lat;
+
+        let dx = bx - ax;
+        let dy = by - ay;
+        let length_squared = dx * dx + dy * dy;
+
+        // the coordinate is the origin of the projection
+        let fraction = if length_squared > 0.0 {
+            (-(ax * dx + ay * dy) / length_squared).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+
+        let projected_lat = segment.from.0 + fraction * (segment.to.0 - segment.from.0);
+        let projected_lon = segment.from.1 + fraction * (segment.to.1 - segment.from.1);
+
+        SegmentProjection {
+            segment: *segment,
+            fraction,
+            lat: projected_lat,
+            lon: projected_lon,
+            distance_m: OSMGraph::haversine_distance(lat, lon, projected_lat, projected_lon),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_graph::grid;
+
+    /// Get the distance from a coordinate to each edge of a graph by projecting it onto every segment
+    /// # Arguments
+    /// * `graph` - The graph
+    /// * `lat` - The latitude
+    /// * `lon` - The longitude
+    /// # Returns
+    /// * `Vec<f64>` - The distance to each edge in meters, sorted
+    fn edge_distances(graph: &OSMGraph, lat: f64, lon: f64) -> Vec<f64> {
+        let index = EdgeIndex::from_graph(graph);
+        let mut best_by_edge = HashMap::new();
+
+        for segments in index.cells.values() {
+            for segment in segments {
+                EdgeIndex::keep_nearest(&mut best_by_edge, EdgeIndex::project(lat, lon, segment));
+            }
+        }
+
+        EdgeIndex::sorted_by_distance(best_by_edge)
+            .iter()
+            .map(|projection| projection.distance_m)
+            .collect()
+    }
+
+    /// Get the ways of the edges of projections
+    /// # Arguments
+    /// * `graph` - The graph of the edges
+    /// * `projections` - The projections onto the edges
+    /// # Returns
+    /// * `Vec<i64>` - The id of the way of each edge, sorted
+    fn ways(graph: &OSMGraph, projections: &[SegmentProjection]) -> Vec<i64> {
+        let mut ways: Vec<i64> = projections
+            .iter()
+            .map(|projection| graph.get_edges()[projection.segment.edge_index].way_id.0)
+            .collect();
+        ways.sort();
+
+        ways
+    }
+
+    #[test]
+    fn empty_index() {
+        let index = EdgeIndex::new();
+
+        assert!(index.nearest(47.0, 7.0).is_none());
+        assert!(index.k_nearest(47.0, 7.0, 3).is_empty());
+        assert!(index.within_radius(47.0, 7.0, 1000.0).is_empty());
+    }
+
+    #[test]
+    fn nearest_projects_onto_the_road() {
+        let graph = grid();
+
+        let projection = graph.edge_index.nearest(47.0005, 7.0025).unwrap();
+
+        assert_eq!(ways(&graph, &[projection]), vec![1]);
+        assert!((projection.lat - 47.0).abs() < 1e-9);
+        assert!((projection.lon - 7.0025).abs() < 1e-9);
+        assert!((projection.distance_m - 55.6).abs() < 0.1);
+    }
+
+    #[test]
+    fn k_nearest_returns_each_edge_once() {
+        let graph = grid();
+
+        // both directions of the way 1, then both directions of the way 7
+        let projections = graph.edge_index.k_nearest(47.0, 7.0025, 4);
+
+        assert_eq!(ways(&graph, &projections), vec![1, 1, 7, 7]);
+        assert!(projections
+            .windows(2)
+            .all(|pair| pair[0].distance_m <= pair[1].distance_m));
+        assert!(projections[1].distance_m < 1e-6);
+        assert!((projections[2].distance_m - 189.5).abs() < 0.5);
+
+        assert!(graph.edge_index.k_nearest(47.0, 7.0025, 0).is_empty());
+        assert_eq!(
+            graph.edge_index.k_nearest(47.0, 7.0025, 100).len(),
+            graph.get_edge_count()
+        );
+    }
+
+    #[test]
+    fn k_nearest_matches_a_full_scan() {
+        let graph = grid();
+
+        for (lat, lon) in [
+            (47.004, 7.013),
+            (47.0199, 7.0101),
+            (46.95, 6.95),
+            (47.1, 7.015),
+        ] {
+            let expected = edge_distances(&graph, lat, lon);
+            let distances: Vec<f64> = graph
+                .edge_index
+                .k_nearest(lat, lon, 5)
+                .iter()
+                .map(|projection| projection.distance_m)
+                .collect();
+
+            assert_eq!(distances, expected[..5], "{} {}", lat, lon);
+        }
+    }
+
+    #[test]
+    fn within_radius_matches_a_full_scan() {
+        let graph = grid();
+
+        for (lat, lon, radius_m) in [
+            (47.004, 7.013, 300.0),
+            (47.01, 7.01, 0.0),
+            (47.0199, 7.0101, 800.0),
+            (46.95, 6.95, 100.0),
+        ] {
+            let expected: Vec<f64> = edge_distances(&graph, lat, lon)
+                .into_iter()
+                .filter(|distance| *distance <= radius_m)
+                .collect();
+            let distances: Vec<f64> = graph
+                .edge_index
+                .within_radius(lat, lon, radius_m)
+                .iter()
+                .map(|projection| projection.distance_m)
+                .collect();
+
+            assert_eq!(distances, expected, "{} {} {}", lat, lon, radius_m);
+        }
+
+        // the center of the grid is on the ways 3, 4, 8 and 11, in both directions
+        let center = graph.edge_index.within_radius(47.01, 7.01, 1.0);
+        assert_eq!(ways(&graph, &center), vec![3, 3, 4, 4, 8, 8, 11, 11]);
+
+        assert_eq!(
+            graph.edge_index.within_radius(47.01, 7.01, 5000.0).len(),
+            graph.get_edge_count()
+        );
+    }
+}
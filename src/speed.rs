@@ -0,0 +1,134 @@
+use osmpbfreader::objects::Tags;
+
+/// Speed used for `maxspeed=walk`, in km/h
+const WALK_SPEED_KMH: f64 = 7.0;
+
+/// Speed used for the ferry routes without maxspeed tag, in km/h, including the boarding
+const FERRY_SPEED_KMH: f64 = 15.0;
+
+/// Conversion factor from miles per hour to km/h
+const MPH_TO_KMH: f64 = 1.609344;
+
+/// Get the speed limit of a country zone, such as `CH:urban`
+/// # Arguments
+/// * `zone` - The value of the maxspeed tag
+/// # Returns
+/// * `Option<f64>` - The speed limit in km/h, None if the zone is unknown
+pub fn zone_speed(zone: &str) -> Option<f64> {
+    let (country, zone_type) = zone.split_once(':')?;
+
+    let speed = match (country, zone_type) {
+        (_, "living_street") => 20.0,
+        (_, "urban") => 50.0,
+        ("CH", "rural") | ("FR", "rural") | ("IT", "rural") => 80.0,
+        ("DE", "rural") | ("AT", "rural") => 100.0,
+        (_, "rural") => 80.0,
+        ("CH", "trunk") => 100.0,
+        ("IT", "trunk") => 110.0,
+        ("CH", "motorway") => 120.0,
+        ("DE", "motorway") => 130.0,
+        ("FR", "motorway") | ("AT", "motorway") | ("IT", "motorway") => 130.0,
+        (_, "motorway") => 120.0,
+        _ => return None,
+    };
+
+    Some(speed)
+}
+
+/// Parse the value of a maxspeed tag
+/// Numeric values (`50`, `50 km/h`, `30 mph`), `walk` and country zones (`CH:urban`, `CH:rural`,
+/// `CH:motorway`...) are supported. For multiple values (`50;30`) the first one is used
+/// # Arguments
+/// * `value` - The value of the tag
+/// # Returns
+/// * `Option<f64>` - The speed limit in km/h, None if the value can't be parsed
+pub fn parse_maxspeed(value: &str) -> Option<f64> {
+    let value = value.split(';').next()?.trim();
+
+    if value.eq_ignore_ascii_case("walk") {
+        return Some(WALK_SPEED_KMH);
+    }
+
+    if let Some(speed) = zone_speed(value) {
+        return Some(speed);
+    }
+
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+        Some(index) => (value[..index].trim(), value[index..].trim()),
+        None => (value, ""),
+    };
+
+    let speed: f64 = number.parse().ok()?;
+
+    if speed <= 0.0 {
+        return None;
+    }
+
+    match unit {
+        "" | "km/h" | "kmh" | "kph" => Some(speed),
+        "mph" => Some(speed * MPH_TO_KMH),
+        "knots" => Some(speed * 1.852),
+        _ => None,
+    }
+}
+
+/// Get the default speed of a highway class, used when the way has no usable maxspeed tag
+/// # Arguments
+/// * `highway` - The value of the highway tag
+/// # Returns
+/// * `f64` - The speed in km/h
+pub fn default_speed(highway: &str) -> f64 {
+    match highway {
+        "motorway" => 120.0,
+        "motorway_link" => 80.0,
+        "trunk" => 100.0,
+        "trunk_link" => 60.0,
+        "primary" => 80.0,
+        "primary_link" => 60.0,
+        "secondary" => 70.0,
+        "secondary_link" => 50.0,
+        "tertiary" => 60.0,
+        "tertiary_link" => 40.0,
+        "unclassified" => 50.0,
+        "residential" => 40.0,
+        "living_street" => 20.0,
+        "service" => 20.0,
+        "track" => 15.0,
+        _ => 40.0,
+    }
+}
+
+/// Get the speed on a way in one direction
+/// The directional tag (`maxspeed:forward` or `maxspeed:backward`) is used first, then
+/// `maxspeed`, then the default speed of the highway class, or the ferry speed for a ferry route
+/// # Arguments
+/// * `tags` - The tags of the way
+/// * `forward` - True for the direction of the way, false for the opposite direction
+/// # Returns
+/// * `f64` - The speed in km/h
+pub fn way_speed(tags: &Tags, forward: bool) -> f64 {
+    let directional_key = if forward {
+        "maxspeed:forward"
+    } else {
+        "maxspeed:backward"
+    };
+
+    tags.get(directional_key)
+        .and_then(|value| parse_maxspeed(value))
+        .or_else(|| tags.get("maxspeed").and_then(|value| parse_maxspeed(value)))
+        .unwrap_or_else(|| match tags.get("highway") {
+            Some(highway) => default_speed(highway),
+            None if tags.get("route").is_some_and(|route| route == "ferry") => FERRY_SPEED_KMH,
+            None => default_speed(""),
+        })
+}
+
+/// Get the time needed to travel a distance at a given speed
+/// # Arguments
+/// * `distance_m` - The distance in meters
+/// * `speed_kmh` - The speed in km/h
+/// # Returns
+/// * `f64` - The time in seconds
+pub fn travel_time(distance_m: f64, speed_kmh: f64) -> f64 {
+    distance_m / (speed_kmh / 3.6)
+}
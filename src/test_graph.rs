@@ -0,0 +1,201 @@
+use crate::osm_graph::{Edge, OSMGraph};
+use osmpbfreader::objects::{Node, NodeId, Tags, Way, WayId};
+
+/// Create a node
+/// # Arguments
+/// * `id` - The id of the node
+/// * `lat` - The latitude of the node
+/// * `lon` - The longitude of the node
+/// # Returns
+/// * `Node` - The node, without tags
+pub fn node(id: i64, lat: f64, lon: f64) -> Node {
+    Node {
+        id: NodeId(id),
+        decimicro_lat: (lat * 1e7).round() as i32,
+        decimicro_lon: (lon * 1e7).round() as i32,
+        tags: Tags::new(),
+    }
+}
+
+/// Create the tags of a way or a relation
+/// # Arguments
+/// * `pairs` - The keys and values of the tags
+/// # Returns
+/// * `Tags` - The tags
+pub fn tags(pairs: &[(&str, &str)]) -> Tags {
+    let mut tags = Tags::new();
+
+    for (key, value) in pairs {
+        tags.insert((*key).into(), (*value).into());
+    }
+
+    tags
+}
+
+/// Add a two-way primary road named `road <way_id>` along nodes of the graph, as one edge in
+/// each direction whose weight is its length
+/// # Arguments
+/// * `graph` - The graph, containing the nodes
+/// * `way_id` - The id of the way
+/// * `nodes` - The ids of the nodes of the way, in order
+pub fn add_road(graph: &mut OSMGraph, way_id: i64, nodes: &[i64]) {
+    let nodes_ids: Vec<NodeId> = nodes.iter().map(|id| NodeId(*id)).collect();
+    let name = format!("road {}", way_id);
+
+    graph.add_way(&Way {
+        id: WayId(way_id),
+        tags: tags(&[("highway", "primary"), ("name", &name)]),
+        nodes: nodes_ids.clone(),
+    });
+
+    let distance: f64 = nodes_ids
+        .windows(2)
+        .map(|segment| graph.get_segment_length(segment[0], segment[1]))
+        .sum();
+
+    for nodes_ids in [nodes_ids.clone(), nodes_ids.into_iter().rev().collect()] {
+        let edge = Edge::new(
+            nodes_ids[0],
+            nodes_ids[nodes_ids.len() - 1],
+            distance,
+            distance,
+            distance,
+            nodes_ids,
+            WayId(way_id),
+        );
+
+        graph.add_edge(edge.clone());
+        graph.add_edge_from_node(edge.from, edge.clone());
+        graph.add_edge_to_node(edge.to, edge);
+    }
+}
+
+/// Build a 3 x 3 grid of roads, with nodes 1 to 9 spaced by 0.01 degree, row by row from the
+/// south-west corner at (47.0, 7.0)
+/// The horizontal roads are the ways 1 to 6 (1: 1-2, 2: 2-3, 3: 4-5, 4: 5-6, 5: 7-8, 6: 8-9)
+/// and the vertical roads the ways 7 to 12 (7: 1-4, 8: 2-5, 9: 3-6, 10: 4-7, 11: 5-8, 12: 6-9).
+/// Each road has a node in its middle, numbered from 100 in the order of the ways
+/// ```text
+/// 7 --5-- 8 --6-- 9
+/// |       |       |
+/// 10      11      12
+/// |       |       |
+/// 4 --3-- 5 --4-- 6
+/// |       |       |
+/// 7       8       9
+/// |       |       |
+/// 1 --1-- 2 --2-- 3
+/// ```
+/// # Returns
+/// * `OSMGraph` - The grid, with its spatial index
+pub fn grid() -> OSMGraph {
+    let mut graph = OSMGraph::new();
+
+    for row in 0..3 {
+        for column in 0..3 {
+            graph.add_node(&node(
+                1 + row * 3 + column,
+                47.0 + row as f64 * 0.01,
+                7.0 + column as f64 * 0.01,
+            ));
+        }
+    }
+
+    let mut way_id = 1;
+
+    for row in 0..3 {
+        for column in 0..2 {
+            let from = 1 + row * 3 + column;
+            let middle = 99 + way_id;
+
+            graph.add_node(&node(
+                middle,
+                47.0 + row as f64 * 0.01,
+                7.005 + column as f64 * 0.01,
+            ));
+            add_road(&mut graph, way_id, &[from, middle, from + 1]);
+
+            way_id += 1;
+        }
+    }
+
+    for row in 0..2 {
+        for column in 0..3 {
+            let from = 1 + row * 3 + column;
+            let middle = 99 + way_id;
+
+            graph.add_node(&node(
+                middle,
+                47.005 + row as f64 * 0.01,
+                7.0 + column as f64 * 0.01,
+            ));
+            add_road(&mut graph, way_id, &[from, middle, from + 3]);
+
+            way_id += 1;
+        }
+    }
+
+    graph.build_spatial_index();
+    graph.update_min_weight_per_meter();
+
+    graph
+}
+
+/// Build a square grid of roads whose nodes are moved by up to 0.003 degree, so that the
+/// shortest paths are unique
+/// The nodes are numbered from 1, row by row from the south-west corner at (47.0, 7.0), with a
+/// spacing of 0.01 degree. The horizontal roads are numbered from 1, then the vertical roads
+/// # Arguments
+/// * `size` - The number of nodes per side
+/// # Returns
+/// * `OSMGraph` - The grid, with its spatial index
+pub fn jittered_grid(size: i64) -> OSMGraph {
+    let mut graph = OSMGraph::new();
+    let id = |row: i64, column: i64| 1 + row * size + column;
+
+    for row in 0..size {
+        for column in 0..size {
+            let jitter_lat = ((row * 7 + column * 13) % 11) as f64 * 0.0003;
+            let jitter_lon = ((row * 17 + column * 5) % 11) as f64 * 0.0003;
+
+            graph.add_node(&node(
+                id(row, column),
+                47.0 + row as f64 * 0.01 + jitter_lat,
+                7.0 + column as f64 * 0.01 + jitter_lon,
+            ));
+        }
+    }
+
+    let mut way_id = 1;
+
+    for row in 0..size {
+        for column in 0..size - 1 {
+            add_road(&mut graph, way_id, &[id(row, column), id(row, column + 1)]);
+            way_id += 1;
+        }
+    }
+
+    for row in 0..size - 1 {
+        for column in 0..size {
+            add_road(&mut graph, way_id, &[id(row, column), id(row + 1, column)]);
+            way_id += 1;
+        }
+    }
+
+    graph.build_spatial_index();
+    graph.update_min_weight_per_meter();
+
+    graph
+}
+
+/// Get the ways followed by a path, each way once
+/// # Arguments
+/// * `edges` - The edges of the path
+/// # Returns
+/// * `Vec<i64>` - The ids of the ways, in order
+pub fn path_ways(edges: &[Edge]) -> Vec<i64> {
+    let mut ways: Vec<i64> = edges.iter().map(|edge| edge.way_id.0).collect();
+    ways.dedup();
+
+    ways
+}
@@ -0,0 +1,222 @@
+use crate::osm_graph::Edge;
+use osmpbfreader::objects::{NodeId, OsmId, Relation, WayId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Vehicle the routes are calculated for, its turn restrictions are used and its exceptions are ignored
+const VEHICLE: &str = "motorcycle";
+
+/// Member connecting the `from` and `to` ways of a turn restriction
+/// # Variants
+/// * `Node` - The node where the turn is restricted
+/// * `Ways` - The ways followed between the `from` and `to` ways, in order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RestrictionVia {
+    Node(NodeId),
+    Ways(Vec<WayId>),
+}
+
+/// Turn restriction read from a `type=restriction` relation
+/// # Fields
+/// * `from` - The way the turn starts from
+/// * `via` - The node or the ways of the turn
+/// * `to` - The way the turn goes to
+/// * `mandatory` - True for the `only_*` restrictions, where `to` is the only way allowed after
+///   `from` and `via`, false for the `no_*` restrictions, where `to` is forbidden
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnRestriction {
+    pub from: WayId,
+    pub via: RestrictionVia,
+    pub to: WayId,
+    pub mandatory: bool,
+}
+
+/// TurnRestriction implementation
+impl TurnRestriction {
+    /// Read the turn restrictions of a relation
+    /// The `restriction:motorcycle` tag is used instead of `restriction` if present, and the
+    /// relations with motorcycles in `except` are ignored. A relation with several `from` or `to`
+    /// ways, such as `no_entry` or `no_exit`, gives one restriction per pair of ways
+    /// # Arguments
+    /// * `relation` - The relation
+    /// # Returns
+    /// * `Vec<TurnRestriction>` - The restrictions of the relation, empty if it is not a valid restriction
+    pub fn from_relation(relation: &Relation) -> Vec<TurnRestriction> {
+        let tag = |key: &str| relation.tags.get(key).map(|value| value.as_str());
+
+        if tag("type") != Some("restriction")
+            || tag("except").is_some_and(|except| except.split(';').any(|v| v.trim() == VEHICLE))
+        {
+            return Vec::new();
+        }
+
+        let mandatory = match tag(&format!("restriction:{}", VEHICLE)).or(tag("restriction")) {
+            Some(restriction) if restriction.starts_with("no_") => false,
+            Some(restriction) if restriction.starts_with("only_") => true,
+            _ => return Vec::new(),
+        };
+
+        let mut from_ways = Vec::new();
+        let mut to_ways = Vec::new();
+        let mut via_nodes = Vec::new();
+        let mut via_ways = Vec::new();
+
+        for member in &relation.refs {
+            match (member.role.as_str(), member.member) {
+                ("from", OsmId::Way(way_id)) => from_ways.push(way_id),
+                ("to", OsmId::Way(way_id)) => to_ways.push(way_id),
+                ("via", OsmId::Node(node_id)) => via_nodes.push(node_id),
+                ("via", OsmId::Way(way_id)) => via_ways.push(way_id),
+                _ => {}
+            }
+        }
+
+        let via = match (via_nodes.as_slice(), via_ways.is_empty()) {
+            ([node_id], true) => RestrictionVia::Node(*node_id),
+            ([], false) => RestrictionVia::Ways(via_ways),
+            _ => return Vec::new(),
+        };
+
+        from_ways
+            .iter()
+            .flat_map(|from| {
+                to_ways.iter().map(|to| TurnRestriction {
+                    from: *from,
+                    via: via.clone(),
+                    to: *to,
+                    mandatory,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Turn restrictions of a graph, indexed by their `from` way
+/// # Fields
+/// * `restrictions` - The turn restrictions
+/// * `by_from_way` - The indexes of the restrictions starting from each way
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TurnRestrictions {
+    pub restrictions: Vec<TurnRestriction>,
+    pub by_from_way: HashMap<WayId, Vec<usize>>,
+}
+
+/// TurnRestrictions implementation
+impl TurnRestrictions {
+    /// Add a turn restriction
+    /// # Arguments
+    /// * `restriction` - The restriction to add
+    pub fn add(&mut self, restriction: TurnRestriction) {
+        self.by_from_way
+            .entry(restriction.from)
+            .or_default()
+            .push(self.restrictions.len());
+        self.restrictions.push(restriction);
+    }
+
+    /// Get the number of turn restrictions
+    /// # Returns
+    /// * `usize` - The number of turn restrictions
+    pub fn len(&self) -> usize {
+        self.restrictions.len()
+    }
+
+    /// Check if there is no turn restriction
+    /// # Returns
+    /// * `bool` - True if there is no turn restriction
+    pub fn is_empty(&self) -> bool {
+        self.restrictions.is_empty()
+    }
+
+    /// Check a turn from a way to another at a node
+    /// The via-way restrictions in progress are the restrictions whose `from` way and first via
+    /// ways have been followed. They are kept while the route stays on their via ways, and checked
+    /// when it leaves them
+    /// # Arguments
+    /// * `node_id` - The node of the turn
+    /// * `from_way` - The way used to reach the node
+    /// * `active` - The indexes of the via-way restrictions in progress
+    /// * `to_way` - The way used to leave the node
+    /// # Returns
+    /// * `Option<Vec<usize>>` - The indexes of the via-way restrictions in progress after the
+    ///   turn, None if the turn is forbidden
+    pub fn turn(
+        &self,
+        node_id: NodeId,
+        from_way: WayId,
+        active: &[usize],
+        to_way: WayId,
+    ) -> Option<Vec<usize>> {
+        let mut next_active = Vec::new();
+
+        for index in active {
+            let restriction = &self.restrictions[*index];
+
+            let via = match &restriction.via {
+                RestrictionVia::Ways(via) => via,
+                RestrictionVia::Node(_) => continue,
+            };
+
+            let position = via.iter().position(|way_id| *way_id == from_way);
+
+            // still on the via ways
+            if to_way == from_way || position.is_some_and(|p| via.get(p + 1) == Some(&to_way)) {
+                next_active.push(*index);
+                continue;
+            }
+
+            let completed = position == Some(via.len() - 1) && to_way == restriction.to;
+
+            if completed != restriction.mandatory {
+                return None;
+            }
+        }
+
+        if let Some(indexes) = self.by_from_way.get(&from_way) {
+            for index in indexes {
+                let restriction = &self.restrictions[*index];
+
+                match &restriction.via {
+                    // a prohibited turn, or another turn than the mandatory one
+                    RestrictionVia::Node(via)
+                        if *via == node_id
+                            && (restriction.to == to_way) != restriction.mandatory =>
+                    {
+                        return None;
+                    }
+                    RestrictionVia::Ways(via) if via[0] == to_way && to_way != from_way => {
+                        next_active.push(*index);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        next_active.sort_unstable();
+        next_active.dedup();
+
+        Some(next_active)
+    }
+
+    /// Check if a path has no forbidden turn
+    /// # Arguments
+    /// * `edges` - The edges of the path
+    /// # Returns
+    /// * `bool` - True if every turn between two consecutive edges is allowed
+    pub fn allows_path(&self, edges: &[Edge]) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+
+        let mut active = Vec::new();
+
+        for pair in edges.windows(2) {
+            match self.turn(pair[0].to, pair[0].way_id, &active, pair[1].way_id) {
+                Some(next_active) => active = next_active,
+                None => return false,
+            }
+        }
+
+        true
+    }
+}
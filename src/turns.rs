@@ -0,0 +1,228 @@
+use crate::maneuver::ManeuverModifier;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// Thresholds of the turn classes, as the largest absolute turn angle of each class in degrees
+/// A turn with a larger angle than `sharp` is a U-turn
+/// # Fields
+/// * `straight` - The largest angle of a straight-on
+/// * `slight` - The largest angle of a slight turn
+/// * `normal` - The largest angle of a normal turn
+/// * `sharp` - The largest angle of a sharp turn
+/// # Example
+/// ```json
+/// {"straight": 20, "slight": 60, "normal": 135, "sharp": 170}
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct TurnThresholds {
+    pub straight: f64,
+    pub slight: f64,
+    pub normal: f64,
+    pub sharp: f64,
+}
+
+/// Default thresholds
+impl Default for TurnThresholds {
+    fn default() -> Self {
+        TurnThresholds {
+            straight: 20.0,
+            slight: 60.0,
+            normal: 135.0,
+            sharp: 170.0,
+        }
+    }
+}
+
+/// TurnThresholds implementation
+impl TurnThresholds {
+    /// Load thresholds from a JSON config file
+    /// # Arguments
+    /// * `file_path` - The path of the config file
+    /// # Returns
+    /// * `Result<TurnThresholds, String>` - The thresholds, or an error message if the file is missing or invalid
+    pub fn from_file(file_path: &Path) -> Result<Self, String> {
+        let file = File::open(file_path).map_err(|error| error.to_string())?;
+
+        let thresholds: TurnThresholds =
+            serde_json::from_reader(BufReader::new(file)).map_err(|error| error.to_string())?;
+
+        thresholds.validate()?;
+
+        Ok(thresholds)
+    }
+
+    /// Check that the thresholds increase from the straight-on to the sharp turn, between 0 and 180 degrees
+    /// # Returns
+    /// * `Result<(), String>` - An error message if the thresholds are invalid
+    pub fn validate(&self) -> Result<(), String> {
+        let thresholds = [self.straight, self.slight, self.normal, self.sharp];
+
+        let increasing = thresholds.windows(2).all(|pair| pair[0] <= pair[1]);
+        let in_range = thresholds
+            .iter()
+            .all(|threshold| (0.0..=180.0).contains(threshold));
+
+        if increasing && in_range {
+            Ok(())
+        } else {
+            Err(String::from(
+                "the thresholds must increase from straight to sharp, between 0 and 180 degrees",
+            ))
+        }
+    }
+
+    /// Classify a turn from its angle
+    /// # Arguments
+    /// * `angle` - The turn angle in degrees, positive to the right, see `turn_angle`
+    /// # Returns
+    /// * `ManeuverModifier` - The class of the turn
+    pub fn classify(&self, angle: f64) -> ManeuverModifier {
+        let magnitude = angle.abs();
+        let right = angle > 0.0;
+
+        if magnitude <= self.straight {
+            ManeuverModifier::Straight
+        } else if magnitude <= self.slight {
+            if right {
+                ManeuverModifier::SlightRight
+            } else {
+                ManeuverModifier::SlightLeft
+            }
+        } else if magnitude <= self.normal {
+            if right {
+                ManeuverModifier::Right
+            } else {
+                ManeuverModifier::Left
+            }
+        } else if magnitude <= self.sharp {
+            if right {
+                ManeuverModifier::SharpRight
+            } else {
+                ManeuverModifier::SharpLeft
+            }
+        } else {
+            ManeuverModifier::UTurn
+        }
+    }
+}
+
+/// Get the turn angle between two bearings
+/// # Arguments
+/// * `bearing_before` - The bearing before the turn in degrees
+/// * `bearing_after` - The bearing after the turn in degrees
+/// # Returns
+/// * `f64` - The turn angle in degrees between -180 and 180, positive to the right
+pub fn turn_angle(bearing_before: f64, bearing_after: f64) -> f64 {
+    let angle = (bearing_after - bearing_before).rem_euclid(360.0);
+
+    if angle > 180.0 {
+        angle - 360.0
+    } else {
+        angle
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn angle_wraps_around_north() {
+        assert_eq!(turn_angle(350.0, 10.0), 20.0);
+        assert_eq!(turn_angle(10.0, 350.0), -20.0);
+        assert_eq!(turn_angle(90.0, 90.0), 0.0);
+        assert_eq!(turn_angle(0.0, 180.0), 180.0);
+        assert_eq!(turn_angle(180.0, 0.0), 180.0);
+        assert_eq!(turn_angle(270.0, 45.0), 135.0);
+
+        let thresholds = TurnThresholds::default();
+        assert_eq!(
+            thresholds.classify(turn_angle(350.0, 10.0)),
+            ManeuverModifier::Straight
+        );
+    }
+
+    #[test]
+    fn sign_gives_the_side() {
+        let thresholds = TurnThresholds::default();
+
+        assert_eq!(thresholds.classify(40.0), ManeuverModifier::SlightRight);
+        assert_eq!(thresholds.classify(-40.0), ManeuverModifier::SlightLeft);
+        assert_eq!(thresholds.classify(90.0), ManeuverModifier::Right);
+        assert_eq!(thresholds.classify(-90.0), ManeuverModifier::Left);
+        assert_eq!(thresholds.classify(150.0), ManeuverModifier::SharpRight);
+        assert_eq!(thresholds.classify(-150.0), ManeuverModifier::SharpLeft);
+        assert_eq!(thresholds.classify(175.0), ManeuverModifier::UTurn);
+        assert_eq!(thresholds.classify(-175.0), ManeuverModifier::UTurn);
+    }
+
+    #[test]
+    fn boundaries_belong_to_the_smaller_class() {
+        let thresholds = TurnThresholds::default();
+
+        let cases = [
+            (0.0, ManeuverModifier::Straight),
+            (20.0, ManeuverModifier::Straight),
+            (20.1, ManeuverModifier::SlightRight),
+            (60.0, ManeuverModifier::SlightRight),
+            (60.1, ManeuverModifier::Right),
+            (135.0, ManeuverModifier::Right),
+            (135.1, ManeuverModifier::SharpRight),
+            (170.0, ManeuverModifier::SharpRight),
+            (170.1, ManeuverModifier::UTurn),
+            (180.0, ManeuverModifier::UTurn),
+            (-20.0, ManeuverModifier::Straight),
+            (-20.1, ManeuverModifier::SlightLeft),
+            (-60.0, ManeuverModifier::SlightLeft),
+            (-60.1, ManeuverModifier::Left),
+            (-135.0, ManeuverModifier::Left),
+            (-135.1, ManeuverModifier::SharpLeft),
+            (-170.0, ManeuverModifier::SharpLeft),
+            (-170.1, ManeuverModifier::UTurn),
+        ];
+
+        for (angle, modifier) in cases {
+            assert_eq!(thresholds.classify(angle), modifier, "{}", angle);
+        }
+    }
+
+    #[test]
+    fn thresholds_are_validated() {
+        let valid = TurnThresholds::default();
+        assert!(valid.validate().is_ok());
+
+        let equal = TurnThresholds {
+            slight: 20.0,
+            ..valid
+        };
+        assert!(equal.validate().is_ok());
+
+        let out_of_order = TurnThresholds {
+            straight: 30.0,
+            slight: 20.0,
+            ..valid
+        };
+        assert!(out_of_order.validate().is_err());
+
+        let sharp_before_normal = TurnThresholds {
+            normal: 150.0,
+            sharp: 140.0,
+            ..valid
+        };
+        assert!(sharp_before_normal.validate().is_err());
+
+        let negative = TurnThresholds {
+            straight: -10.0,
+            ..valid
+        };
+        assert!(negative.validate().is_err());
+
+        let too_large = TurnThresholds {
+            sharp: 190.0,
+            ..valid
+        };
+        assert!(too_large.validate().is_err());
+    }
+}